@@ -0,0 +1,159 @@
+use crate::config::Config;
+use crate::password;
+use crate::rate_limit::PasswordAttempts;
+use rocket::http::{CookieJar, Status};
+use rocket::request::{self, FromRequest, Request};
+use sha2::{Digest, Sha256};
+use std::fmt::Display;
+use std::net::IpAddr;
+use subtle::ConstantTimeEq;
+
+/// Prefix of an ADMIN_TOKEN stored as the SHA-256 hash of the actual token
+pub const SHA256_PREFIX: &str = "sha256:";
+
+/// Path under which the admin session is granted in the session cookies, like the paths protected by
+/// a password. Hidden directories are never indexed, so it can't clash with a path of the gallery.
+const SESSION_PATH: &str = ".admin";
+
+/// Request guard that only succeeds if the request is authenticated as coming from the admin,
+/// either with the ADMIN_TOKEN in an `Authorization: Bearer` header or with an admin session
+/// cookie. Routes should take it as a `Result<Admin, AdminError>` in order to be able to respond
+/// to unauthenticated requests themselves.
+pub struct Admin;
+
+/// Possible reasons for which a request is not authenticated as coming from the admin
+#[derive(Debug)]
+pub enum AdminError {
+    /// ADMIN_TOKEN is empty, so no one can access the admin routes
+    Disabled,
+    /// Neither a token nor an admin session was provided
+    Required,
+    /// The provided token doesn't match ADMIN_TOKEN
+    Invalid,
+    /// Too many invalid tokens have been tried, the client has to wait this number of seconds
+    TooManyAttempts(u64),
+}
+
+impl Display for AdminError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AdminError::Disabled => {
+                f.write_str("The admin routes are disabled, set ADMIN_TOKEN to enable them")
+            }
+            AdminError::Required => f.write_str("An admin token is required to access this page"),
+            AdminError::Invalid => f.write_str("Invalid admin token"),
+            AdminError::TooManyAttempts(retry_after) => write!(
+                f,
+                "Too many invalid admin tokens, try again in {retry_after} seconds"
+            ),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = AdminError;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let config = request
+            .rocket()
+            .state::<Config>()
+            .expect("Error : unable to obtain the config");
+        let attempts = request
+            .rocket()
+            .state::<PasswordAttempts>()
+            .expect("Error : unable to obtain the password attempts");
+        match authenticate(
+            request
+                .headers()
+                .get_one(rocket::http::hyper::header::AUTHORIZATION.as_str()),
+            request.cookies(),
+            config,
+            attempts,
+            request.client_ip(),
+        )
+        .await
+        {
+            Ok(()) => request::Outcome::Success(Admin),
+            Err(error) => request::Outcome::Error((Status::Unauthorized, error)),
+        }
+    }
+}
+
+/// Check the given Authorization header, or the admin session cookie if there is no header, against
+/// the ADMIN_TOKEN
+async fn authenticate(
+    header: Option<&str>,
+    cookies: &CookieJar<'_>,
+    config: &Config,
+    attempts: &PasswordAttempts,
+    client_ip: Option<IpAddr>,
+) -> Result<(), AdminError> {
+    if config.ADMIN_TOKEN.is_empty() {
+        return Err(AdminError::Disabled);
+    }
+    if let Some(header) = header {
+        let token = header.strip_prefix("Bearer ").ok_or(AdminError::Required)?;
+        return try_token(token.trim(), config, attempts, client_ip).await;
+    }
+    // The session is only valid as long as the token it has been opened with is unchanged
+    match password::is_in_session(cookies, SESSION_PATH, &config.ADMIN_TOKEN) {
+        true => Ok(()),
+        false => Err(AdminError::Required),
+    }
+}
+
+/// Check if the given token matches the ADMIN_TOKEN, in constant time. Besides the `sha256:` form,
+/// the ADMIN_TOKEN can be hashed like the passwords in the config files.
+pub async fn check_token(token: &str, config: &Config) -> bool {
+    match config.ADMIN_TOKEN.strip_prefix(SHA256_PREFIX) {
+        Some(hash) => {
+            let token_hash = format!("{:x}", Sha256::digest(token.as_bytes()));
+            token_hash
+                .as_bytes()
+                .ct_eq(hash.to_lowercase().as_bytes())
+                .into()
+        }
+        None => password::verify(token, &config.ADMIN_TOKEN).await,
+    }
+}
+
+/// Check the given token sent by the given client, unless it has tried too many invalid ones
+async fn try_token(
+    token: &str,
+    config: &Config,
+    attempts: &PasswordAttempts,
+    client_ip: Option<IpAddr>,
+) -> Result<(), AdminError> {
+    attempts
+        .check(client_ip, SESSION_PATH)
+        .map_err(AdminError::TooManyAttempts)?;
+    if check_token(token, config).await {
+        attempts.succeed(client_ip, SESSION_PATH);
+        Ok(())
+    } else {
+        attempts.fail(client_ip, SESSION_PATH, token);
+        Err(AdminError::Invalid)
+    }
+}
+
+/// Open an admin session in the private cookies if the given token is valid
+pub async fn login(
+    token: &str,
+    cookies: &CookieJar<'_>,
+    config: &Config,
+    attempts: &PasswordAttempts,
+    client_ip: Option<IpAddr>,
+) -> Result<(), AdminError> {
+    if config.ADMIN_TOKEN.is_empty() {
+        return Err(AdminError::Disabled);
+    }
+    try_token(token, config, attempts, client_ip).await?;
+    password::add_to_session(cookies, SESSION_PATH, &config.ADMIN_TOKEN);
+    Ok(())
+}
+
+/// Close the admin session, if any
+pub fn logout(cookies: &CookieJar<'_>) {
+    cookies.remove_private(password::cookie_name(SESSION_PATH));
+}
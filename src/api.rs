@@ -0,0 +1,557 @@
+use crate::config::Config;
+use crate::db::{self, DB};
+use crate::filter::{Facets, PhotoFilter};
+use crate::password::{self, OptionalPassword, PasswordError};
+use crate::photos::{DownloadVariant, Gallery, Photo, PhotoQuery, PhotoQueryError};
+use crate::privacy;
+use crate::rate_limit::PasswordAttempts;
+use crate::share::SharedPaths;
+use crate::uid::UID;
+use crate::TooManyRequests;
+use rocket::form;
+use rocket::http::uri::Origin;
+use rocket::http::CookieJar;
+use rocket::serde::json::{self, Value};
+use rocket::serde::Serialize;
+use rocket::{Route, State};
+use rocket_db_pools::Connection;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use utoipa::{OpenApi, ToSchema};
+
+/// Prefix under which the routes of the JSON API are mounted
+pub const BASE: &str = "/api/v1";
+
+/// Routes of the JSON API, to be mounted under `BASE`
+pub fn routes() -> Vec<Route> {
+    routes![
+        get_openapi,
+        get_folder,
+        get_photos,
+        get_facets,
+        get_collections,
+        get_photo,
+        get_photo_metadata
+    ]
+}
+
+/// OpenAPI document describing the routes of the API, generated from their definitions and from
+/// the types they return
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Niobium API",
+        license(name = "GPL-2.0", url = "https://www.gnu.org/licenses/old-licenses/gpl-2.0.html"),
+        description = "Read-only access to the folders, photos and collections of the gallery. \
+            The password of a directory or a collection is sent base64-encoded in the Authorization \
+            header and, once accepted, stored in the session cookie."
+    ),
+    servers((url = "/api/v1")),
+    paths(
+        get_openapi,
+        get_folder,
+        get_photos,
+        get_facets,
+        get_collections,
+        get_photo,
+        get_photo_metadata
+    )
+)]
+struct ApiDoc;
+
+/// Responder used by the routes of the API, which always answer with a JSON body
+#[derive(Responder)]
+pub enum ApiResult {
+    Json(Value),
+    #[response(status = 400)]
+    BadRequest(Value),
+    #[response(status = 401)]
+    PasswordRequired(Value),
+    TooManyAttempts(TooManyRequests<Value>),
+    #[response(status = 404)]
+    NotFound(Value),
+    #[response(status = 500)]
+    Err(Value),
+}
+
+/// Body of the responses to the requests that failed
+#[derive(Serialize, ToSchema, Debug)]
+struct ApiError {
+    error: String,
+}
+
+/// A directory of the gallery or of a collection, with its subdirectories. This is the
+/// counterpart of the NavData used to render the navigation panel.
+#[derive(Serialize, ToSchema, Debug)]
+struct Folder {
+    path: String,
+    name: String,
+    title: String,
+    description: String,
+    /// Name of the collection this directory is part of, if any
+    collection: Option<String>,
+    /// Path of the parent directory, or None at the root of the gallery or of a collection
+    parent: Option<String>,
+    /// Number of photos listed in this directory that can be accessed with the granted passwords,
+    /// which includes the photos of its subdirectories if SHOW_PHOTOS_FROM_SUBDIRS is enabled
+    n_photos: usize,
+    subdirs: Vec<Subdir>,
+    url: String,
+    url_photos: String,
+}
+
+/// A subdirectory of a Folder
+#[derive(Serialize, ToSchema, Debug)]
+struct Subdir {
+    name: String,
+    path: String,
+    /// True if a password that hasn't been granted yet is required to access this subdirectory
+    locked: bool,
+    url: String,
+}
+
+/// A page of the list of photos in a directory
+#[derive(Serialize, ToSchema, Debug)]
+struct PhotoList<'a> {
+    path: String,
+    /// Index of the first photo of this page in the directory
+    start: usize,
+    /// Number of photos in the directory that can be accessed with the granted passwords
+    total: usize,
+    photos: Vec<ApiPhoto<'a>>,
+}
+
+/// A photo, along with the URLs of its files
+#[derive(Serialize, ToSchema, Debug)]
+struct ApiPhoto<'a> {
+    #[serde(flatten)]
+    photo: &'a Photo,
+    urls: PhotoUrls,
+}
+
+/// URLs of the files of a photo
+#[derive(Serialize, ToSchema, Debug)]
+struct PhotoUrls {
+    thumbnail: String,
+    large: String,
+    photo: String,
+    download: String,
+    /// Camera RAW file paired with the photo, if any
+    download_raw: Option<String>,
+    /// Stream of the video, for video clips
+    video: Option<String>,
+    /// Metadata of the photo, if SHOW_METADATA is enabled
+    metadata: Option<String>,
+}
+
+/// A collection listed in the navigation panel
+#[derive(Serialize, ToSchema, Debug)]
+struct CollectionInfo {
+    name: String,
+    title: String,
+    description: Option<String>,
+    /// True if the password of the collection is required and hasn't been granted yet
+    locked: bool,
+    url: String,
+    url_folder: String,
+}
+
+/// Route handler that returns the OpenAPI document of the API
+#[utoipa::path(responses((status = 200, description = "OpenAPI 3 document of the API")))]
+#[get("/openapi.json")]
+fn get_openapi() -> ApiResult {
+    ApiResult::Json(json::to_value(ApiDoc::openapi()).unwrap_or_default())
+}
+
+/// Route handler that returns a directory of the gallery or of a collection and its subdirectories
+#[utoipa::path(
+    params(
+        ("Authorization" = Option<String>, Header, description = "Base64-encoded password of the path"),
+    ),
+    responses(
+        (status = 200, body = Folder),
+        (status = 401, description = "A password is required, or the provided one is invalid", body = ApiError),
+        (status = 404, description = "This path doesn't exist", body = ApiError),
+        (status = 429, description = "Too many invalid passwords have been tried", body = ApiError),
+    )
+)]
+#[get("/folders/<path..>")]
+async fn get_folder(
+    path: PathBuf,
+    gallery: &State<Arc<Gallery>>,
+    config: &State<Config>,
+    cookies: &CookieJar<'_>,
+    password: OptionalPassword,
+    attempts: &State<PasswordAttempts>,
+    shared_paths: SharedPaths,
+) -> ApiResult {
+    // Check if a password is required to access this path
+    let passwords = match gallery
+        .check_password(&path, cookies, &password, attempts, &shared_paths)
+        .await
+    {
+        Ok(passwords) => passwords,
+        Err(error) => return password_error(error),
+    };
+
+    // Count the photos that can be displayed with the granted passwords, without returning any
+    let n_photos = match gallery
+        .read(&path, None, Some(0), None, passwords.clone())
+        .await
+    {
+        Some(gallery_lock) => gallery_lock.total,
+        None => return not_found(),
+    };
+
+    // Find the collection this path is inside of, if any
+    let (title, description, collection_name) = {
+        let collections = gallery.collections.read().await;
+        let (collection, collection_name, _) = collections.find(&path);
+        (
+            collection
+                .and_then(|c| c.title.clone())
+                .unwrap_or(config.TITLE.clone()),
+            collection
+                .and_then(|c| c.description.clone())
+                .unwrap_or(config.DESCRIPTION.clone()),
+            collection_name,
+        )
+    };
+    let is_root = match &collection_name {
+        Some(collection_name) => path == Path::new(collection_name),
+        None => path.parent().is_none(),
+    };
+
+    // Check which subdirs are locked. Subdir passwords are not supported for collections.
+    let subdirs = gallery.get_subdirs(&path, None).await;
+    let gallery_passwords = gallery.get_passwords().await;
+    let subdirs = subdirs
+        .into_iter()
+        .map(|name| {
+            let subdir_path = path.join(&name);
+            let subdir_path_str = subdir_path.to_string_lossy().to_string();
+            Subdir {
+                locked: collection_name.is_none()
+                    && gallery_passwords.contains_key(&subdir_path_str)
+                    && !passwords.contains_key(&subdir_path_str),
+                url: uri!(crate::get_gallery(&subdir_path)).to_string(),
+                name,
+                path: subdir_path_str,
+            }
+        })
+        .collect();
+
+    ApiResult::Json(
+        json::to_value(Folder {
+            path: path.to_string_lossy().to_string(),
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            title,
+            description,
+            collection: collection_name,
+            parent: match is_root {
+                true => None,
+                false => path.parent().map(|p| p.to_string_lossy().to_string()),
+            },
+            n_photos,
+            subdirs,
+            url: uri!(crate::get_gallery(&path)).to_string(),
+            url_photos: api_url(uri!(get_photos(
+                &path,
+                None as Option<usize>,
+                None as Option<usize>,
+                None as Option<&str>,
+                _
+            ))),
+        })
+        .unwrap_or_default(),
+    )
+}
+
+/// Route handler that returns a page of the photos in the given path, with the same pagination
+/// as the grid : at most 100 photos are returned at a time. If a `search` is provided, only the photos
+/// that match every one of its words are returned, and the metadata filters are applied the same way.
+#[utoipa::path(
+    params(
+        ("Authorization" = Option<String>, Header, description = "Base64-encoded password of the path"),
+        ("search" = Option<String>, Query, description = "Words to look for in the filename, path, title, place, keywords, camera and lens of the photos"),
+        PhotoFilter,
+    ),
+    responses(
+        (status = 200, body = PhotoList),
+        (status = 400, description = "A filter has an invalid value", body = ApiError),
+        (status = 401, description = "A password is required, or the provided one is invalid", body = ApiError),
+        (status = 404, description = "This path doesn't exist", body = ApiError),
+        (status = 429, description = "Too many invalid passwords have been tried", body = ApiError),
+    )
+)]
+#[get("/photos/<path..>?<start>&<count>&<search>&<filter..>")]
+#[allow(clippy::too_many_arguments)]
+async fn get_photos(
+    path: PathBuf,
+    start: Option<usize>,
+    count: Option<usize>,
+    search: Option<&str>,
+    filter: Result<PhotoFilter, form::Errors<'_>>,
+    gallery: &State<Arc<Gallery>>,
+    config: &State<Config>,
+    cookies: &CookieJar<'_>,
+    password: OptionalPassword,
+    attempts: &State<PasswordAttempts>,
+    shared_paths: SharedPaths,
+    mut db_conn: Connection<DB>,
+) -> ApiResult {
+    // Check if a password is required to access this path
+    let passwords = match gallery
+        .check_password(&path, cookies, &password, attempts, &shared_paths)
+        .await
+    {
+        Ok(passwords) => passwords,
+        Err(error) => return password_error(error),
+    };
+
+    // Keep only the photos displayed in this path that match the search and the filters, if any
+    match PhotoQuery::new(&mut db_conn, search, filter).await {
+        Ok(Some(query)) => {
+            return match gallery
+                .read_matching(&path, &query, start, count, &passwords)
+                .await
+            {
+                Some(results) => ApiResult::Json(
+                    json::to_value(PhotoList {
+                        path: path.to_string_lossy().to_string(),
+                        start: results.start,
+                        total: results.total,
+                        photos: results
+                            .photos
+                            .iter()
+                            .map(|photo| api_photo(photo, config))
+                            .collect(),
+                    })
+                    .unwrap_or_default(),
+                ),
+                None => not_found(),
+            };
+        }
+        Ok(None) => (),
+        Err(PhotoQueryError::InvalidFilters(message)) => {
+            return error_json(ApiResult::BadRequest, &message)
+        }
+        Err(PhotoQueryError::SearchFailed) => {
+            return error_json(ApiResult::Err, "Unable to search for these words")
+        }
+    }
+
+    match gallery.read(&path, start, count, None, passwords).await {
+        Some(gallery_lock) => ApiResult::Json(
+            json::to_value(PhotoList {
+                path: path.to_string_lossy().to_string(),
+                start: gallery_lock.start,
+                total: gallery_lock.total,
+                photos: gallery_lock
+                    .iter()
+                    .map(|photo| api_photo(photo, config))
+                    .collect(),
+            })
+            .unwrap_or_default(),
+        ),
+        None => not_found(),
+    }
+}
+
+/// Route handler that returns the values of the metadata of the photos in the given path, with the
+/// number of photos for each of them, which can be used as filters on `/photos`
+#[utoipa::path(
+    params(
+        ("Authorization" = Option<String>, Header, description = "Base64-encoded password of the path"),
+    ),
+    responses(
+        (status = 200, body = Facets),
+        (status = 401, description = "A password is required, or the provided one is invalid", body = ApiError),
+        (status = 404, description = "This path doesn't exist", body = ApiError),
+        (status = 429, description = "Too many invalid passwords have been tried", body = ApiError),
+    )
+)]
+#[get("/facets/<path..>")]
+async fn get_facets(
+    path: PathBuf,
+    gallery: &State<Arc<Gallery>>,
+    cookies: &CookieJar<'_>,
+    password: OptionalPassword,
+    attempts: &State<PasswordAttempts>,
+    shared_paths: SharedPaths,
+) -> ApiResult {
+    // Check if a password is required to access this path
+    match gallery
+        .check_password(&path, cookies, &password, attempts, &shared_paths)
+        .await
+    {
+        Ok(passwords) => match gallery.facets(&path, &passwords).await {
+            Some(facets) => ApiResult::Json(json::to_value(facets).unwrap_or_default()),
+            None => not_found(),
+        },
+        Err(error) => password_error(error),
+    }
+}
+
+/// Route handler that returns the collections listed in the navigation panel
+#[utoipa::path(responses((status = 200, body = Vec<CollectionInfo>)))]
+#[get("/collections")]
+async fn get_collections(
+    gallery: &State<Arc<Gallery>>,
+    config: &State<Config>,
+    cookies: &CookieJar<'_>,
+    shared_paths: SharedPaths,
+) -> ApiResult {
+    if !config.SHOW_COLLECTIONS_IN_NAVIGATION_PANEL {
+        return ApiResult::Json(Value::Array(Vec::new()));
+    }
+    let collections = gallery.collections.read().await;
+    let collections = collections
+        .list()
+        .into_iter()
+        .map(|collection| {
+            let path = PathBuf::from(&collection.name);
+            CollectionInfo {
+                name: collection.name.clone(),
+                title: collection.title.clone().unwrap_or(collection.name.clone()),
+                description: collection.description.clone(),
+                locked: collection
+                    .password
+                    .as_ref()
+                    .is_some_and(|required_password| {
+                        !password::is_in_session(cookies, &collection.name, required_password)
+                            && !shared_paths.grants(&collection.name, &collection.name)
+                    }),
+                url: uri!(crate::get_gallery(&path)).to_string(),
+                url_folder: api_url(uri!(get_folder(&path))),
+            }
+        })
+        .collect::<Vec<_>>();
+    ApiResult::Json(json::to_value(collections).unwrap_or_default())
+}
+
+/// Route handler that returns a single photo
+#[utoipa::path(
+    params(("uid" = String, Path, description = "UID of the photo, prefixed by a dot")),
+    responses(
+        (status = 200, body = ApiPhoto),
+        (status = 404, description = "This photo doesn't exist or can't be accessed", body = ApiError),
+    )
+)]
+#[get("/photo/<uid>")]
+async fn get_photo(
+    uid: UID,
+    cookies: &CookieJar<'_>,
+    shared_paths: SharedPaths,
+    gallery: &State<Arc<Gallery>>,
+    config: &State<Config>,
+) -> ApiResult {
+    match gallery
+        .get_from_uid_checked(&uid, cookies, &shared_paths)
+        .await
+    {
+        Some(photo) => {
+            ApiResult::Json(json::to_value(api_photo(&photo, config)).unwrap_or_default())
+        }
+        None => not_found(),
+    }
+}
+
+/// Route handler that returns every EXIF field and XMP property of a single photo, except for the
+/// ones removed by PRIVACY_STRIP_METADATA
+#[utoipa::path(
+    params(("uid" = String, Path, description = "UID of the photo, prefixed by a dot")),
+    responses(
+        (status = 200, description = "EXIF fields and XMP properties, indexed by their name", body = BTreeMap<String, String>),
+        (status = 404, description = "This photo doesn't exist or can't be accessed", body = ApiError),
+    )
+)]
+#[get("/photo/<uid>/metadata")]
+async fn get_photo_metadata(
+    uid: UID,
+    cookies: &CookieJar<'_>,
+    shared_paths: SharedPaths,
+    gallery: &State<Arc<Gallery>>,
+    config: &State<Config>,
+    mut db_conn: Connection<DB>,
+) -> ApiResult {
+    match gallery
+        .get_from_uid_checked(&uid, cookies, &shared_paths)
+        .await
+    {
+        Some(photo) if config.SHOW_METADATA => {
+            match db::get_all_metadata(&mut db_conn, &uid).await {
+                Ok(mut metadata) => {
+                    metadata
+                        .retain(|name, _| !privacy::is_stripped_field(name, &photo.strip_metadata));
+                    ApiResult::Json(json::to_value(metadata).unwrap_or_default())
+                }
+                Err(error) => {
+                    eprintln!("Error : unable to read the metadata of UID #{uid} : {error}");
+                    error_json(ApiResult::Err, "Unable to read the metadata of this photo")
+                }
+            }
+        }
+        _ => not_found(),
+    }
+}
+
+/// Return a photo as sent by the API, along with the URLs of its files
+fn api_photo<'a>(photo: &'a Photo, config: &Config) -> ApiPhoto<'a> {
+    let optional_url = |url: String| (!url.is_empty()).then_some(url);
+    ApiPhoto {
+        photo,
+        urls: PhotoUrls {
+            thumbnail: uri!(crate::get_thumbnail(&photo.uid)).to_string(),
+            large: uri!(crate::get_large(&photo.uid)).to_string(),
+            photo: uri!(crate::get_photo(&photo.uid)).to_string(),
+            download: uri!(crate::download_photo(
+                &photo.uid,
+                None as Option<DownloadVariant>
+            ))
+            .to_string(),
+            download_raw: optional_url(crate::url_download_raw(photo)),
+            video: optional_url(crate::url_get_video(photo)),
+            metadata: config
+                .SHOW_METADATA
+                .then(|| api_url(uri!(get_photo_metadata(&photo.uid)))),
+        },
+    }
+}
+
+/// Return the full URL of a route of the API
+fn api_url(uri: Origin<'_>) -> String {
+    format!("{BASE}{uri}")
+}
+
+/// Return a response with the given status containing the given error message
+fn error_json(variant: fn(Value) -> ApiResult, message: &str) -> ApiResult {
+    variant(
+        json::to_value(ApiError {
+            error: message.to_string(),
+        })
+        .unwrap_or_default(),
+    )
+}
+
+/// Return the response to a request for a path or a photo that doesn't exist or can't be accessed
+fn not_found() -> ApiResult {
+    error_json(ApiResult::NotFound, "Not found")
+}
+
+/// Convert an error of `check_password` into a JSON error, with the matching status
+fn password_error(error: PasswordError) -> ApiResult {
+    let message = json::to_value(ApiError {
+        error: error.message(),
+    })
+    .unwrap_or_default();
+    match error {
+        PasswordError::TooManyAttempts(_, retry_after) => {
+            ApiResult::TooManyAttempts(TooManyRequests::new(message, retry_after))
+        }
+        _ => ApiResult::PasswordRequired(message),
+    }
+}
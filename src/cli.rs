@@ -0,0 +1,88 @@
+use crate::password::{self, HashAlgorithm};
+use std::io::{self, BufRead, IsTerminal};
+
+/// Usage message printed by `niobium help`
+const USAGE: &str = "Usage :
+  niobium                              Start the server
+  niobium hash-password [--bcrypt]     Hash a password read from the standard input, to be used
+                                       as a PASSWORD or ADMIN_TOKEN in the config files. Argon2id
+                                       is used unless --bcrypt is specified.
+  niobium help                         Print this message";
+
+/// Run the subcommand given on the command line, if any, then exit. Return without doing anything
+/// if there is none, so that the server can be started.
+pub fn run_subcommand() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(subcommand) = args.first() else {
+        return;
+    };
+    let code = match subcommand.as_str() {
+        "hash-password" => hash_password(&args[1..]),
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            0
+        }
+        _ => {
+            eprintln!("Error : unknown subcommand \"{subcommand}\"\n\n{USAGE}");
+            -1
+        }
+    };
+    std::process::exit(code);
+}
+
+/// Subcommand that reads a password and prints its hash
+fn hash_password(args: &[String]) -> i32 {
+    let mut algorithm = HashAlgorithm::Argon2id;
+    for arg in args {
+        match arg.as_str() {
+            "--bcrypt" => algorithm = HashAlgorithm::Bcrypt,
+            _ => {
+                eprintln!("Error : unknown option \"{arg}\"\n\n{USAGE}");
+                return -1;
+            }
+        }
+    }
+
+    let password = match read_password() {
+        Ok(Some(password)) => password,
+        Ok(None) => return -1,
+        Err(error) => {
+            eprintln!("Error : unable to read the password : {error}");
+            return -1;
+        }
+    };
+    if password.is_empty() {
+        eprintln!("Error : the password is empty");
+        return -1;
+    }
+
+    match password::hash(&password, algorithm) {
+        Ok(hash) => {
+            println!("{hash}");
+            0
+        }
+        Err(error) => {
+            eprintln!("Error : unable to hash the password : {error}");
+            -1
+        }
+    }
+}
+
+/// Read the password to hash, either by prompting for it twice without echo when run from a
+/// terminal, or from the first line of the standard input otherwise. Return None if the two
+/// passwords entered in the terminal don't match.
+fn read_password() -> io::Result<Option<String>> {
+    if io::stdin().is_terminal() {
+        let password = rpassword::prompt_password("Password : ")?;
+        let confirmation = rpassword::prompt_password("Confirm password : ")?;
+        if password != confirmation {
+            eprintln!("Error : the passwords don't match");
+            return Ok(None);
+        }
+        Ok(Some(password))
+    } else {
+        let mut line = String::new();
+        io::stdin().lock().read_line(&mut line)?;
+        Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+    }
+}
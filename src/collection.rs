@@ -10,7 +10,9 @@ use regex::Regex;
 use serde::{de::Unexpected, Deserialize};
 
 use crate::{
+    filter::{self, DateBound},
     photos::{CachedPhoto, GalleryContent},
+    uid::UID,
     Error,
 };
 
@@ -188,11 +190,21 @@ pub struct Collection {
 
     pub dirs: Vec<CollectionDir>,
 
+    #[serde(default)]
+    pub rules: Option<CollectionRule>,
+
     #[serde(skip)]
     pub photos: GalleryContent,
 }
 
 impl Collection {
+    /// Check if the photo with the given UID is part of this collection
+    pub fn contains(&self, uid: &UID) -> bool {
+        self.photos
+            .values()
+            .any(|photos| photos.iter().any(|photo| &photo.uid == uid))
+    }
+
     /// Fill the collection with the photos from the given gallery that matches its requirements
     pub fn fill(&mut self, gallery: &GalleryContent) {
         for dir in &self.dirs {
@@ -201,7 +213,10 @@ impl Collection {
                 if dir.path_matches(&path_buf) {
                     let filtered_photos: Vec<CachedPhoto> = photos
                         .iter()
-                        .filter(|&photo| dir.photo_matches(photo))
+                        .filter(|&photo| {
+                            dir.photo_matches(photo)
+                                && self.rules.as_ref().is_none_or(|rules| rules.matches(photo))
+                        })
                         .map(|photo| CachedPhoto::clone_from(photo, vec![]))
                         .collect();
                     if !filtered_photos.is_empty() {
@@ -333,3 +348,128 @@ where
 fn collection_dir_filter_default() -> Option<CollectionFilter> {
     None
 }
+
+/// A rule on the metadata of the photos, which every photo must match to be added to the collection.
+/// All the conditions set in a rule must be met, and rules can be nested with `ALL` (every one of the
+/// subrules must match) and `ANY` (at least one of them must match).
+#[allow(non_snake_case)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub struct CollectionRule {
+    #[serde(default)]
+    all: Vec<CollectionRule>,
+
+    #[serde(default)]
+    any: Vec<CollectionRule>,
+
+    #[serde(default)]
+    from: Option<DateBound>,
+
+    #[serde(default)]
+    to: Option<DateBound>,
+
+    #[serde(default)]
+    camera: Option<String>,
+
+    #[serde(default)]
+    lens: Option<String>,
+
+    #[serde(default)]
+    keywords: Vec<String>,
+
+    #[serde(default)]
+    rating_min: Option<i32>,
+
+    #[serde(default)]
+    rating_max: Option<i32>,
+
+    #[serde(default)]
+    orientation: Option<CollectionOrientation>,
+
+    #[serde(default)]
+    gps_bounds: Option<GpsBounds>,
+}
+
+impl CollectionRule {
+    /// Check if the given photo matches every condition of this rule
+    pub fn matches(&self, photo: &CachedPhoto) -> bool {
+        let taken_on = filter::taken_on(photo);
+        self.all.iter().all(|rule| rule.matches(photo))
+            && (self.any.is_empty() || self.any.iter().any(|rule| rule.matches(photo)))
+            && self
+                .from
+                .is_none_or(|from| taken_on.is_some_and(|date| date >= from.first_day))
+            && self
+                .to
+                .is_none_or(|to| taken_on.is_some_and(|date| date <= to.last_day))
+            && self
+                .camera
+                .as_ref()
+                .is_none_or(|camera| camera.eq_ignore_ascii_case(photo.camera_model.trim()))
+            && self
+                .lens
+                .as_ref()
+                .is_none_or(|lens| lens.eq_ignore_ascii_case(photo.lens_model.trim()))
+            && self.keywords.iter().all(|keyword| {
+                photo
+                    .keywords
+                    .iter()
+                    .any(|k| k.trim().eq_ignore_ascii_case(keyword.trim()))
+            })
+            && self.rating_min.is_none_or(|min| photo.rating >= min)
+            && self.rating_max.is_none_or(|max| photo.rating <= max)
+            && self
+                .orientation
+                .is_none_or(|orientation| orientation.matches(photo))
+            && self
+                .gps_bounds
+                .as_ref()
+                .is_none_or(|bounds| bounds.contains(photo))
+    }
+}
+
+/// Orientation of the photos included in a collection, based on their displayed dimensions
+#[derive(Deserialize, Debug, Copy, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum CollectionOrientation {
+    Landscape,
+    Portrait,
+    Square,
+}
+
+impl CollectionOrientation {
+    /// Check if the given photo has this orientation
+    fn matches(&self, photo: &CachedPhoto) -> bool {
+        match self {
+            Self::Landscape => photo.width > photo.height,
+            Self::Portrait => photo.width < photo.height,
+            Self::Square => photo.width == photo.height,
+        }
+    }
+}
+
+/// A geographic area, in decimal degrees, in which the photos of a collection have been taken
+#[allow(non_snake_case)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub struct GpsBounds {
+    south: f64,
+    west: f64,
+    north: f64,
+    east: f64,
+}
+
+impl GpsBounds {
+    /// Check if the given photo has GPS coordinates inside these bounds. If `WEST` is greater than
+    /// `EAST`, the area crosses the 180th meridian.
+    fn contains(&self, photo: &CachedPhoto) -> bool {
+        let (Some(latitude), Some(longitude)) = (photo.latitude, photo.longitude) else {
+            return false;
+        };
+        let longitude_matches = match self.west <= self.east {
+            true => longitude >= self.west && longitude <= self.east,
+            false => longitude >= self.west || longitude <= self.east,
+        };
+        latitude >= self.south && latitude <= self.north && longitude_matches
+    }
+}
@@ -1,4 +1,9 @@
+use crate::admin;
+use crate::file_hash::HashAlgorithm;
 use crate::photos::ImageFormat;
+use crate::privacy::MetadataGroup;
+use crate::raw;
+use crate::sort_order::{SortField, SortOrder};
 use crate::Error;
 use base64::prelude::BASE64_STANDARD;
 use base64::Engine;
@@ -14,6 +19,9 @@ use toml::value::Table;
 /// Can be overriden with the NIOBIUM_CONFIG_FILE environment variable
 pub const DEFAULT_CONFIG_FILENAME: &str = "niobium.config";
 
+/// Name of the local config files that can be created in the subdirectories of the photos folder
+pub const SUBDIR_CONFIG_FILENAME: &str = ".niobium.config";
+
 /// Default name of the secret file in the app's folder
 /// Can be overriden with the NIOBIUM_CONFIG_FILE environment variable
 pub const DEFAULT_SECRET_FILENAME: &str = ".secret";
@@ -78,6 +86,52 @@ pub struct Config {
     #[serde(default = "config_default_loading_workers")]
     pub LOADING_WORKERS: usize,
 
+    /// If enabled, the app will watch the photos folder for changes and automatically index the
+    /// photos that are added, removed, moved or renamed, without requiring a call to `/.reload`.
+    /// Only supported on systems which provide a way to watch files, such as inotify on Linux.
+    /// Default : false
+    #[serde(default)]
+    pub WATCH_PHOTOS_DIR: bool,
+
+    /// When WATCH_PHOTOS_DIR is enabled, delay in milliseconds to wait after a change has been
+    /// detected in the photos folder before indexing it, during which further changes are grouped
+    /// together. This prevents indexing files that are still being copied.
+    /// Default : 2000
+    #[serde(default = "config_default_watch_debounce_delay")]
+    pub WATCH_DEBOUNCE_DELAY: u64,
+
+    /// Token required to access the admin routes, such as `/.reload`, either in an
+    /// `Authorization: Bearer <token>` header or through an admin session opened with
+    /// `/.admin/login`. It can be stored hashed, as `sha256:` followed by the hexadecimal SHA-256
+    /// hash of the token, or hashed like PASSWORD. Leave empty to disable the admin routes.
+    /// Default : empty (admin routes disabled)
+    #[serde(default)]
+    pub ADMIN_TOKEN: String,
+
+    /// Minimum delay in seconds between the end of a reload triggered through `/.reload` and the
+    /// start of the next one. Requests received during this delay are refused with a
+    /// "429 Too Many Requests" error, and requests received while a reload is already running
+    /// don't start a new one and only return its status.
+    /// Default : 60
+    #[serde(default = "config_default_reload_cooldown")]
+    pub RELOAD_COOLDOWN: u64,
+
+    /// Algorithm used to compute the hash of the content of the files, which is used to detect
+    /// photos that have been moved or renamed : MD5, BLAKE3 or XXH3. BLAKE3 and XXH3 are much
+    /// faster than MD5.
+    /// Default : MD5
+    #[serde(default)]
+    pub HASH_ALGORITHM: HashAlgorithm,
+
+    /// By default, the content of new files is only hashed when their size and modification time
+    /// are not enough to tell whether they are photos that have been moved or renamed, which avoids
+    /// reading every new file entirely. If enabled, every new file is hashed, which allows moved
+    /// photos to be detected even if their modification time has changed, at the cost of a much
+    /// slower loading.
+    /// Default : false
+    #[serde(default)]
+    pub ALWAYS_HASH_FILES: bool,
+
     /// If enable, the app will try to read EXIF metadata of photos and save them in the
     /// database.
     /// Default : true
@@ -91,12 +145,91 @@ pub struct Config {
     #[serde(default)]
     pub PRE_GENERATE_THUMBNAILS: bool,
 
+    /// List of file extensions (case-insensitive) that will be indexed in the photos folder.
+    /// Every extension must correspond to an image format supported by the decoder, such as
+    /// `jpg`, `png`, `webp`, `tif`, `gif` or `bmp`.
+    /// Default : ["jpg", "jpeg", "png", "webp", "tif", "tiff", "gif"]
+    #[serde(default = "config_default_file_extensions")]
+    pub FILE_EXTENSIONS: Vec<String>,
+
+    /// List of file extensions (case-insensitive) of camera RAW files that will be indexed in
+    /// the photos folder. These files are displayed using the JPEG preview embedded inside them
+    /// by the camera, but are still downloaded in their original format. Supported formats :
+    /// `cr2`, `nef`, `nrw`, `arw`, `srf`, `sr2`, `dng`, `orf`, `rw2`, `pef`. Set to an empty
+    /// list to ignore RAW files.
+    /// Default : ["cr2", "nef", "arw", "dng", "orf", "rw2", "pef"]
+    #[serde(default = "config_default_raw_file_extensions")]
+    pub RAW_FILE_EXTENSIONS: Vec<String>,
+
+    /// If enabled, a camera RAW file that has a sibling image with the same name (for instance
+    /// `IMG_0001.CR2` and `IMG_0001.JPG`) will not be displayed as a separate photo : the image
+    /// will be displayed instead, and the RAW file will be offered as an extra download.
+    /// Default : true
+    #[serde(default = "config_default_true")]
+    pub PAIR_RAW_FILES: bool,
+
+    /// Color used to fill the transparent areas of images that have an alpha channel (such as
+    /// some PNG or WEBP files), as an hexadecimal RGB value.
+    /// Default : "ffffff" (white)
+    #[serde(default = "config_default_transparency_background")]
+    pub TRANSPARENCY_BACKGROUND: String,
+
+    /// List of file extensions (case-insensitive) of video clips that will be indexed in the
+    /// photos folder. Videos are displayed in the grid using a poster frame extracted with ffmpeg,
+    /// and can be played in loupe mode. Set to an empty list to ignore video files.
+    /// Default : ["mp4", "mov", "m4v", "webm"]
+    #[serde(default = "config_default_video_file_extensions")]
+    pub VIDEO_FILE_EXTENSIONS: Vec<String>,
+
+    /// Path to the `ffmpeg` binary, used to extract the poster frames of videos. If it is not
+    /// found, videos are still indexed and can be played, but they are displayed without a
+    /// thumbnail. Set to an empty string to disable.
+    /// Default : "ffmpeg"
+    #[serde(default = "config_default_ffmpeg_path")]
+    pub FFMPEG_PATH: String,
+
+    /// Path to the `ffprobe` binary, used to read the dimensions and duration of videos.
+    /// Set to an empty string to disable.
+    /// Default : "ffprobe"
+    #[serde(default = "config_default_ffprobe_path")]
+    pub FFPROBE_PATH: String,
+
     /// Configure a password needed to access this gallery. Leave empty to disable.
+    /// It can be stored hashed with argon2id or bcrypt, using the hash generated by
+    /// `niobium hash-password`.
     /// Default : empty (no password needed)
     /// This setting is overridable.
     #[serde(default)]
     pub PASSWORD: String,
 
+    /// Number of invalid passwords a client can try for a protected path (or for the ADMIN_TOKEN)
+    /// before being locked out for PASSWORD_LOCKOUT_DELAY. Each further invalid password doubles
+    /// the lockout delay. Set to 0 to disable.
+    /// Default : 5
+    #[serde(default = "config_default_password_max_attempts")]
+    pub PASSWORD_MAX_ATTEMPTS: u32,
+
+    /// Number of invalid passwords a client can try across all the protected paths before being
+    /// locked out of every path, like with PASSWORD_MAX_ATTEMPTS. Set to 0 to disable.
+    /// Default : 20
+    #[serde(default = "config_default_password_max_attempts_per_ip")]
+    pub PASSWORD_MAX_ATTEMPTS_PER_IP: u32,
+
+    /// Number of invalid passwords that can be tried for a protected path by all the clients
+    /// together, such as a botnet, before every password for this path is refused for
+    /// PASSWORD_LOCKOUT_DELAY. This delay doesn't double, so that the legitimate users can't be
+    /// locked out for long. Set to 0 to disable.
+    /// Default : 100
+    #[serde(default = "config_default_password_max_attempts_per_path")]
+    pub PASSWORD_MAX_ATTEMPTS_PER_PATH: u32,
+
+    /// Delay in seconds for which a client is first locked out after PASSWORD_MAX_ATTEMPTS invalid
+    /// passwords, during which every password it sends is refused with a "429 Too Many Requests"
+    /// error.
+    /// Default : 30
+    #[serde(default = "config_default_password_lockout_delay")]
+    pub PASSWORD_LOCKOUT_DELAY: u64,
+
     /// If enabled, the grid display for a requested path will show every photo available in
     /// its subdirectories (therefore the root directory will show every photo in the database).
     /// Otherwise, only the photos actually inside the requested path will be shown, most like
@@ -124,15 +257,19 @@ pub struct Config {
     pub SHOW_COLLECTIONS_IN_NAVIGATION_PANEL: bool,
 
     /// Fields(s) to use to sort the photos being displayed. This can be a single field or a
-    /// comma-separated list of fields for multi-ordering. Available fields : `id`,
-    /// `filename`, `title`, `date_taken`, `sort_order`.
-    /// Default : "id" (the order in which they have been added to the database, which
-    /// is a natural sort on the filename)
+    /// comma-separated list of fields for multi-ordering. Available fields : `id` (the order
+    /// in which they have been added to the database, which is a natural sort on the filename),
+    /// `filename`, `title`, `date_taken` (read from the EXIF data of photos, or from their
+    /// filename or the modification time of their file if they have none), `sort_order`. Each field
+    /// can be followed by `asc` (the default) or `desc` to choose its direction, for instance
+    /// "date_taken desc, filename".
+    /// Default : "filename"
     /// This setting is overridable.
     #[serde(default = "config_default_sort_order")]
-    pub SORT_ORDER: String,
+    pub SORT_ORDER: SortOrder,
 
-    /// If enabled, the sort order of the photos will be reversed.
+    /// Deprecated, use `desc` in SORT_ORDER instead. If enabled, the direction of every field
+    /// of SORT_ORDER is reversed.
     /// Default : false
     /// This setting is overridable.
     #[serde(default)]
@@ -167,7 +304,9 @@ pub struct Config {
     pub MAX_CROP: usize,
 
     /// If enabled, show a button allowing the user to view metadata of photos (such as camera
-    /// model and aperture) in Loupe mode.
+    /// model and aperture) in Loupe mode. The title, description, place, rating and keywords of
+    /// photos are read from their XMP sidecar file (`IMG_0001.xmp` or `IMG_0001.jpg.xmp`) if
+    /// there is one, and from the XMP and IPTC metadata embedded in the file.
     /// Default : true
     #[serde(default = "config_default_true")]
     pub SHOW_METADATA: bool,
@@ -178,6 +317,16 @@ pub struct Config {
     #[serde(default = "config_default_true")]
     pub METADATA_VISIBLE_BY_DEFAULT: bool,
 
+    /// Additional EXIF fields or XMP properties to show with the metadata of photos in Loupe mode,
+    /// such as `["ExposureProgram", "Flash", "WhiteBalance", "LensSerialNumber", "Software"]`.
+    /// EXIF fields are named after their tag, and XMP properties are written with their namespace
+    /// prefix (such as "xmp:CreatorTool"). Every field of a photo can be listed at
+    /// `/<uid>/metadata`. Fields removed by PRIVACY_STRIP_METADATA are never shown. Requires
+    /// `SHOW_METADATA` to be enabled.
+    /// Default : [] (none)
+    #[serde(default)]
+    pub LOUPE_METADATA_FIELDS: Vec<String>,
+
     /// If enabled, the Loupe view will show a button allowing the user to download the photo
     /// in original quality.
     /// Default : true
@@ -190,11 +339,52 @@ pub struct Config {
     #[serde(default = "config_default_dowload_prefix")]
     pub DOWNLOAD_PREFIX: String,
 
+    /// Groups of metadata that are removed from the photos sent to users, either displayed in
+    /// full resolution or downloaded : `GPS` (position of the photo), `SERIAL_NUMBERS` (serial
+    /// numbers of the camera and lens, and name of the owner) and `MAKER_NOTES` (proprietary
+    /// data of the camera manufacturer, which may prevent some RAW processing software from
+    /// reading RAW files correctly). Original files are left untouched : a copy without these
+    /// metadata is created in the cache folder. Videos are sent unchanged. When `GPS` is set,
+    /// the photos are also excluded from the map.
+    /// Default : [] (files are sent as they are)
+    /// This setting is overridable.
+    #[serde(default)]
+    pub PRIVACY_STRIP_METADATA: Vec<MetadataGroup>,
+
     /// Delay (in milliseconds) to wait before switching to the next photo in Slideshow mode.
     /// Default : 5000 (5s)
     #[serde(default = "config_default_slideshow_delay")]
     pub SLIDESHOW_DELAY: usize,
 
+    /// If enabled, a button in the gallery opens a map showing where the photos of the current
+    /// folder or collection have been taken, based on their GPS coordinates.
+    /// Default : true
+    #[serde(default = "config_default_true")]
+    pub SHOW_MAP: bool,
+
+    /// URL of the tiles used to draw the map, where {z}, {x} and {y} are replaced by the zoom level
+    /// and the coordinates of each tile. Make sure to follow the usage policy of the tile server.
+    /// Default : "https://tile.openstreetmap.org/{z}/{x}/{y}.png"
+    #[serde(default = "config_default_map_tiles_url")]
+    pub MAP_TILES_URL: String,
+
+    /// Path to a local folder containing map tiles organized as {z}/{x}/{y}.png (jpg and webp are also
+    /// supported). If set, tiles are served by Niobium from this folder and MAP_TILES_URL is ignored,
+    /// which allows to display maps without relying on an external server.
+    /// Default : ""
+    #[serde(default)]
+    pub MAP_TILES_DIR: String,
+
+    /// Attribution text displayed in the corner of the map, as required by most tile providers.
+    /// Default : "© OpenStreetMap contributors"
+    #[serde(default = "config_default_map_attribution")]
+    pub MAP_ATTRIBUTION: String,
+
+    /// Maximum zoom level available from the tile server.
+    /// Default : 19
+    #[serde(default = "config_default_map_max_zoom")]
+    pub MAP_MAX_ZOOM: u32,
+
     /// Max size of thumbnails on any side, in pixels.
     /// Default : 600
     #[serde(default = "config_default_thumbnail_max_size")]
@@ -311,13 +501,140 @@ impl Config {
                 )))?,
             }
         };
+        if let Ok(var) = std::env::var("NIOBIUM_ADMIN_TOKEN") {
+            config.ADMIN_TOKEN = var;
+        };
+        if let Ok(var) = std::env::var("NIOBIUM_ADMIN_TOKEN_FILE") {
+            match fs::read_to_string(PathBuf::from(var.clone())) {
+                Ok(file_content) => {
+                    config.ADMIN_TOKEN = file_content.split('\n').next().unwrap_or("").to_string()
+                }
+                Err(error) => Err(Error::OtherError(format!(
+                    "Error : unable to open {var} specified in NIOBIUM_ADMIN_TOKEN_FILE : {error}"
+                )))?,
+            }
+        };
         if let Ok(var) = std::env::var("NIOBIUM_COLLECTIONS_FILE") {
             config.COLLECTIONS_FILE = PathBuf::from(var);
         };
 
+        // Check the settings that can't be fully validated by the deserializer
+        config.validate()?;
+
         Ok(config)
     }
 
+    /// Normalize and check the values of the settings that require it, and return an
+    /// InvalidConfigError describing the first invalid setting found, if any
+    pub fn validate(&mut self) -> Result<(), Error> {
+        // Extensions are compared in lowercase and without the leading dot
+        self.FILE_EXTENSIONS = normalize_extensions(&self.FILE_EXTENSIONS);
+        self.RAW_FILE_EXTENSIONS = normalize_extensions(&self.RAW_FILE_EXTENSIONS);
+        self.VIDEO_FILE_EXTENSIONS = normalize_extensions(&self.VIDEO_FILE_EXTENSIONS);
+        for ext in &self.FILE_EXTENSIONS {
+            let readable = image::ImageFormat::from_extension(ext)
+                .map(|format| format.reading_enabled())
+                .unwrap_or(false);
+            if !readable {
+                return Err(Error::InvalidConfigError(format!(
+                    "FILE_EXTENSIONS : \"{ext}\" is not a supported image format"
+                )));
+            }
+        }
+
+        for ext in &self.RAW_FILE_EXTENSIONS {
+            if !raw::SUPPORTED_EXTENSIONS.contains(&ext.as_str()) {
+                return Err(Error::InvalidConfigError(format!(
+                    "RAW_FILE_EXTENSIONS : \"{ext}\" is not a supported RAW format"
+                )));
+            }
+        }
+
+        for ext in &self.VIDEO_FILE_EXTENSIONS {
+            if self.FILE_EXTENSIONS.contains(ext) || self.RAW_FILE_EXTENSIONS.contains(ext) {
+                return Err(Error::InvalidConfigError(format!(
+                    "VIDEO_FILE_EXTENSIONS : \"{ext}\" is already listed as an image format"
+                )));
+            }
+        }
+
+        if !self.MAP_TILES_URL.contains("{z}")
+            || !self.MAP_TILES_URL.contains("{x}")
+            || !self.MAP_TILES_URL.contains("{y}")
+        {
+            return Err(Error::InvalidConfigError(format!(
+                "MAP_TILES_URL : \"{}\" must contain the {{z}}, {{x}} and {{y}} placeholders",
+                self.MAP_TILES_URL
+            )));
+        }
+
+        if self.REVERSE_SORT_ORDER {
+            eprintln!("Warning : REVERSE_SORT_ORDER is deprecated, add `desc` after the fields of SORT_ORDER instead");
+        }
+
+        if self.MAP_MAX_ZOOM > 22 {
+            return Err(Error::InvalidConfigError(format!(
+                "MAP_MAX_ZOOM : {} is not a valid zoom level (0 to 22)",
+                self.MAP_MAX_ZOOM
+            )));
+        }
+
+        if let Some(hash) = self.ADMIN_TOKEN.strip_prefix(admin::SHA256_PREFIX) {
+            if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(Error::InvalidConfigError(
+                    "ADMIN_TOKEN : the hash following \"sha256:\" must be 64 hexadecimal characters"
+                        .to_string(),
+                ));
+            }
+        }
+
+        if parse_hex_color(&self.TRANSPARENCY_BACKGROUND).is_none() {
+            return Err(Error::InvalidConfigError(format!(
+                "TRANSPARENCY_BACKGROUND : \"{}\" is not a valid hexadecimal RGB color",
+                self.TRANSPARENCY_BACKGROUND
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Check if the given filename has one of the extensions listed in FILE_EXTENSIONS,
+    /// RAW_FILE_EXTENSIONS or VIDEO_FILE_EXTENSIONS
+    pub fn is_indexed_file(&self, filename: &str) -> bool {
+        Path::new(filename)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .map(|ext| {
+                self.FILE_EXTENSIONS.contains(&ext)
+                    || self.RAW_FILE_EXTENSIONS.contains(&ext)
+                    || self.VIDEO_FILE_EXTENSIONS.contains(&ext)
+            })
+            .unwrap_or(false)
+    }
+
+    /// Check if the given filename has one of the extensions listed in VIDEO_FILE_EXTENSIONS
+    pub fn is_video_file(&self, filename: &str) -> bool {
+        Path::new(filename)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .map(|ext| self.VIDEO_FILE_EXTENSIONS.contains(&ext))
+            .unwrap_or(false)
+    }
+
+    /// Return the order in which photos must be sorted, according to SORT_ORDER and the deprecated
+    /// REVERSE_SORT_ORDER
+    pub fn sort_order(&self) -> SortOrder {
+        match self.REVERSE_SORT_ORDER {
+            true => self.SORT_ORDER.reversed(),
+            false => self.SORT_ORDER.clone(),
+        }
+    }
+
+    /// Return the color defined by TRANSPARENCY_BACKGROUND, or white if it is invalid
+    pub fn transparency_background(&self) -> image::Rgb<u8> {
+        image::Rgb(parse_hex_color(&self.TRANSPARENCY_BACKGROUND).unwrap_or([0xff, 0xff, 0xff]))
+    }
+
     /// Try to read and parse the config file
     /// In case of error, print it to stderr and exit with a status code of -1
     pub fn read_or_exit(config_file: &Path) -> Self {
@@ -338,6 +655,13 @@ impl Config {
                 );
                 std::process::exit(-1);
             }
+            Error::InvalidConfigError(error) => {
+                eprintln!(
+                    "Error, invalid setting in the config file \"{}\" : {error}",
+                    config_file.to_string_lossy()
+                );
+                std::process::exit(-1);
+            }
             _ => std::process::exit(-1),
         })
     }
@@ -401,7 +725,7 @@ impl Config {
     pub fn update_with_subdir(full_path: &PathBuf, into_value: &mut Table) -> Option<Table> {
         // Check if the config file exists
         let mut subdir_config_path = PathBuf::from(&full_path);
-        subdir_config_path.push(".niobium.config");
+        subdir_config_path.push(SUBDIR_CONFIG_FILENAME);
         if subdir_config_path.is_file() {
             // Try to read it as a TOML value
             match Config::read_path_as_table(&subdir_config_path) {
@@ -456,8 +780,8 @@ fn config_default_database_path() -> String {
     "niobium.sqlite".to_string()
 }
 
-fn config_default_sort_order() -> String {
-    "filename".to_string()
+fn config_default_sort_order() -> SortOrder {
+    SortOrder::by(SortField::Filename)
 }
 
 fn config_default_row_height() -> usize {
@@ -508,10 +832,96 @@ fn config_default_loading_workers() -> usize {
     16
 }
 
+fn config_default_watch_debounce_delay() -> u64 {
+    2000 // ms
+}
+
+fn config_default_reload_cooldown() -> u64 {
+    60 // s
+}
+
+fn config_default_password_max_attempts() -> u32 {
+    5
+}
+
+fn config_default_password_max_attempts_per_ip() -> u32 {
+    20
+}
+
+fn config_default_password_max_attempts_per_path() -> u32 {
+    100
+}
+
+fn config_default_password_lockout_delay() -> u64 {
+    30 // s
+}
+
 fn config_default_collections_file() -> PathBuf {
     PathBuf::from("niobium_collections.config")
 }
 
+fn config_default_file_extensions() -> Vec<String> {
+    ["jpg", "jpeg", "png", "webp", "tif", "tiff", "gif"]
+        .map(String::from)
+        .to_vec()
+}
+
+fn config_default_raw_file_extensions() -> Vec<String> {
+    ["cr2", "nef", "arw", "dng", "orf", "rw2", "pef"]
+        .map(String::from)
+        .to_vec()
+}
+
+fn config_default_transparency_background() -> String {
+    "ffffff".to_string()
+}
+
+fn config_default_video_file_extensions() -> Vec<String> {
+    ["mp4", "mov", "m4v", "webm"].map(String::from).to_vec()
+}
+
+fn config_default_ffmpeg_path() -> String {
+    "ffmpeg".to_string()
+}
+
+fn config_default_ffprobe_path() -> String {
+    "ffprobe".to_string()
+}
+
+fn config_default_map_tiles_url() -> String {
+    "https://tile.openstreetmap.org/{z}/{x}/{y}.png".to_string()
+}
+
+fn config_default_map_attribution() -> String {
+    "© OpenStreetMap contributors".to_string()
+}
+
+fn config_default_map_max_zoom() -> u32 {
+    19
+}
+
+/// Convert a list of extensions to lowercase and remove their leading dot, if any
+fn normalize_extensions(extensions: &[String]) -> Vec<String> {
+    extensions
+        .iter()
+        .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+        .filter(|ext| !ext.is_empty())
+        .collect()
+}
+
+/// Parse an hexadecimal RGB color such as "ff8000" (an optional leading '#' is accepted)
+fn parse_hex_color(value: &str) -> Option<[u8; 3]> {
+    let value = value.trim().trim_start_matches('#');
+    if value.len() != 6 || !value.is_ascii() {
+        return None;
+    }
+    let mut color = [0; 3];
+    for (i, component) in color.iter_mut().enumerate() {
+        *component = u8::from_str_radix(&value[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(color)
+}
+
 /// Try to open the .secret file in the app's directory and return
 /// the secret key inside. If this file doesn't exist, try to generate
 /// a new one. In case of an error, print the error on stderr and exit.
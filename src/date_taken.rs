@@ -0,0 +1,279 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+use regex::Regex;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// Date and time written in a filename, such as `IMG_20230512_153012.jpg`
+static FILENAME_DATE_TIME_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?:^|\D)((?:19|20)\d{2})[-_.]?(\d{2})[-_.]?(\d{2})[-_. T]?(\d{2})[-_.:h]?(\d{2})[-_.:m]?(\d{2})",
+    )
+    .unwrap()
+});
+
+/// Date written in a filename without a time, such as `IMG-20230512-WA0001.jpg`
+static FILENAME_DATE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:^|\D)((?:19|20)\d{2})[-_.]?(\d{2})[-_.]?(\d{2})(?:\D|$)").unwrap()
+});
+
+/// Moment at which a photo was taken
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DateTaken {
+    /// Unix timestamp of this moment. When the time zone is unknown, the local time is stored
+    /// as if it was UTC, so that it is displayed as it was read.
+    pub timestamp: i64,
+    /// Offset of the time zone from UTC, in minutes, if known
+    pub offset: Option<i32>,
+}
+
+impl DateTaken {
+    /// Create a DateTaken from the local time at which the photo was taken, and the offset of its
+    /// time zone if known
+    fn from_local(local: NaiveDateTime, offset: Option<i32>) -> Self {
+        Self {
+            timestamp: local.and_utc().timestamp() - offset.unwrap_or(0) as i64 * 60,
+            offset,
+        }
+    }
+}
+
+// Pairs of EXIF fields containing the date the photo was taken and the offset of its time zone,
+// in order of preference
+const EXIF_DATE_FIELDS: [(exif::Tag, exif::Tag); 2] = [
+    (exif::Tag::DateTimeOriginal, exif::Tag::OffsetTimeOriginal),
+    (exif::Tag::DateTimeDigitized, exif::Tag::OffsetTimeDigitized),
+];
+
+/// Read the date the photo was taken from its EXIF data, with the offset of its time zone if the
+/// file contains one
+pub fn from_exif(exif: &exif::Exif) -> Option<DateTaken> {
+    for (date_tag, offset_tag) in EXIF_DATE_FIELDS {
+        let Some(mut datetime) =
+            read_ascii(exif, date_tag).and_then(|value| exif::DateTime::from_ascii(value).ok())
+        else {
+            continue;
+        };
+        if let Some(value) = read_ascii(exif, offset_tag) {
+            datetime.parse_offset(value).ok();
+        }
+        let local = NaiveDate::from_ymd_opt(
+            datetime.year as i32,
+            datetime.month as u32,
+            datetime.day as u32,
+        )
+        .and_then(|date| {
+            date.and_hms_opt(
+                datetime.hour as u32,
+                datetime.minute as u32,
+                datetime.second as u32,
+            )
+        });
+        if let Some(local) = local {
+            return Some(DateTaken::from_local(
+                local,
+                datetime.offset.map(|offset| offset as i32),
+            ));
+        }
+    }
+    None
+}
+
+/// Read the date the photo was taken from its filename, as written by most smartphones and some
+/// cameras or software : `IMG_20230512_153012.jpg`, `PXL_20230512_153012345.jpg`,
+/// `2023-05-12 15.30.12.jpg`, `IMG-20230512-WA0001.jpg` (date only)... The time zone is unknown.
+pub fn from_filename(filename: &str) -> Option<DateTaken> {
+    let number = |captures: &regex::Captures, index: usize| -> u32 {
+        captures
+            .get(index)
+            .and_then(|value| value.as_str().parse().ok())
+            .unwrap_or(0)
+    };
+    let date = |captures: &regex::Captures| {
+        NaiveDate::from_ymd_opt(
+            number(captures, 1) as i32,
+            number(captures, 2),
+            number(captures, 3),
+        )
+    };
+
+    let local = FILENAME_DATE_TIME_REGEX
+        .captures_iter(filename)
+        .find_map(|captures| {
+            date(&captures).and_then(|date| {
+                date.and_hms_opt(
+                    number(&captures, 4),
+                    number(&captures, 5),
+                    number(&captures, 6),
+                )
+            })
+        })
+        .or_else(|| {
+            FILENAME_DATE_REGEX
+                .captures_iter(filename)
+                .find_map(|captures| date(&captures).and_then(|date| date.and_hms_opt(0, 0, 0)))
+        })?;
+    Some(DateTaken::from_local(local, None))
+}
+
+/// Use the last modification time of the given file as the date the photo was taken, in the time
+/// zone of the server
+pub fn from_mtime(path: &Path) -> Option<DateTaken> {
+    let modified = path
+        .metadata()
+        .and_then(|metadata| metadata.modified())
+        .ok()?;
+    let datetime: DateTime<Local> = modified.into();
+    Some(DateTaken {
+        timestamp: datetime.timestamp(),
+        offset: Some(datetime.offset().local_minus_utc() / 60),
+    })
+}
+
+/// Return the raw value of the given ASCII field of the main image
+fn read_ascii(exif: &exif::Exif, tag: exif::Tag) -> Option<&[u8]> {
+    match &exif.get_field(tag, exif::In::PRIMARY)?.value {
+        exif::Value::Ascii(values) => values.first().map(|value| value.as_slice()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+    const TAG_DATE_TIME_DIGITIZED: u16 = 0x9004;
+    const TAG_OFFSET_TIME_ORIGINAL: u16 = 0x9011;
+
+    /// Timestamp of the given local time, stored as if it was UTC
+    fn local(year: i32, month: u32, day: u32, hour: u32, minute: u32, second: u32) -> i64 {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .and_then(|date| date.and_hms_opt(hour, minute, second))
+            .unwrap()
+            .and_utc()
+            .timestamp()
+    }
+
+    /// Build the EXIF data of a photo with the given ASCII fields in its EXIF IFD
+    fn exif_fixture(fields: &[(u16, &str)]) -> exif::Exif {
+        // Header (8 bytes), IFD0 with a pointer to the EXIF IFD (18 bytes), EXIF IFD, then values
+        let exif_ifd = 26u32;
+        let mut value_offset = exif_ifd + 2 + fields.len() as u32 * 12 + 4;
+        let mut tiff = b"II*\0".to_vec();
+        tiff.extend(8u32.to_le_bytes());
+        tiff.extend(1u16.to_le_bytes());
+        tiff.extend([0x69, 0x87, 4, 0, 1, 0, 0, 0]);
+        tiff.extend(exif_ifd.to_le_bytes());
+        tiff.extend(0u32.to_le_bytes());
+        tiff.extend((fields.len() as u16).to_le_bytes());
+        let mut values = Vec::new();
+        for (tag, value) in fields {
+            let mut value = value.as_bytes().to_vec();
+            value.push(0);
+            tiff.extend(tag.to_le_bytes());
+            tiff.extend(2u16.to_le_bytes());
+            tiff.extend((value.len() as u32).to_le_bytes());
+            tiff.extend(value_offset.to_le_bytes());
+            value_offset += value.len() as u32;
+            values.extend(value);
+        }
+        tiff.extend(0u32.to_le_bytes());
+        tiff.extend(values);
+        exif::Reader::new().read_raw(tiff).unwrap()
+    }
+
+    #[test]
+    fn parse_filenames() {
+        let date_time = local(2023, 5, 12, 15, 30, 12);
+        let date = local(2023, 5, 12, 0, 0, 0);
+        for (filename, timestamp) in [
+            ("IMG_20230512_153012.jpg", date_time),
+            ("VID_20230512_153012.mp4", date_time),
+            ("PXL_20230512_153012345.jpg", date_time),
+            ("20230512_153012.heic", date_time),
+            ("2023-05-12 15.30.12.jpg", date_time),
+            ("2023-05-12T15:30:12.jpg", date_time),
+            ("Screenshot_2023-05-12-15-30-12.png", date_time),
+            ("Photo 2023-05-12 15h30m12.jpg", date_time),
+            ("IMG-20230512-WA0001.jpg", date),
+            ("2023-05-12.jpg", date),
+            ("holiday_2023.05.12.png", date),
+            ("IMG_20230512_256012.jpg", date),
+        ] {
+            assert_eq!(
+                from_filename(filename),
+                Some(DateTaken {
+                    timestamp,
+                    offset: None
+                }),
+                "{filename}"
+            );
+        }
+    }
+
+    #[test]
+    fn ignore_filenames_without_dates() {
+        for filename in [
+            "",
+            "IMG_1234.jpg",
+            "DSC01234.JPG",
+            "holiday.png",
+            "12345678.jpg",
+            "120230512.jpg",
+            "IMG_18990512_153012.jpg",
+            "IMG_20231345_153012.jpg",
+            "IMG_20230230.jpg",
+        ] {
+            assert_eq!(from_filename(filename), None, "{filename}");
+        }
+    }
+
+    #[test]
+    fn parse_exif_dates() {
+        let date_time = local(2023, 5, 12, 15, 30, 12);
+        for (fields, expected) in [
+            (
+                vec![
+                    (TAG_DATE_TIME_ORIGINAL, "2023:05:12 15:30:12"),
+                    (TAG_OFFSET_TIME_ORIGINAL, "+02:00"),
+                ],
+                Some((date_time - 7200, Some(120))),
+            ),
+            (
+                vec![
+                    (TAG_DATE_TIME_ORIGINAL, "2023:05:12 15:30:12"),
+                    (TAG_OFFSET_TIME_ORIGINAL, "-05:30"),
+                ],
+                Some((date_time + 19800, Some(-330))),
+            ),
+            (
+                vec![(TAG_DATE_TIME_ORIGINAL, "2023:05:12 15:30:12")],
+                Some((date_time, None)),
+            ),
+            (
+                vec![
+                    (TAG_DATE_TIME_ORIGINAL, "2023:05:12 15:30:12"),
+                    (TAG_OFFSET_TIME_ORIGINAL, "invalid"),
+                ],
+                Some((date_time, None)),
+            ),
+            (
+                vec![(TAG_DATE_TIME_DIGITIZED, "2023:05:12 15:30:12")],
+                Some((date_time, None)),
+            ),
+            (
+                vec![
+                    (TAG_DATE_TIME_ORIGINAL, "2023:13:45 15:30:12"),
+                    (TAG_DATE_TIME_DIGITIZED, "2023:05:12 15:30:12"),
+                ],
+                Some((date_time, None)),
+            ),
+            (vec![(TAG_DATE_TIME_ORIGINAL, "    :  :     :  :  ")], None),
+            (vec![(TAG_DATE_TIME_ORIGINAL, "unknown")], None),
+            (vec![], None),
+        ] {
+            let expected = expected.map(|(timestamp, offset)| DateTaken { timestamp, offset });
+            assert_eq!(from_exif(&exif_fixture(&fields)), expected, "{fields:?}");
+        }
+    }
+}
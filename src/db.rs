@@ -1,6 +1,15 @@
+use std::collections::{BTreeMap, HashSet};
 use std::path::{Path, PathBuf};
 
-use crate::{config::Config, photos::Photo, uid::UID, Error};
+use crate::{
+    config::Config,
+    photos::{MediaType, Photo},
+    share::ShareLink,
+    sort_order::SortOrder,
+    uid::UID,
+    Error,
+};
+use rocket::serde::json;
 use rocket::{fairing, Build, Rocket};
 use rocket_db_pools::{
     sqlx::{
@@ -35,10 +44,130 @@ CREATE TABLE photo (
     focal_length VARCHAR(16) NOT NULL DEFAULT '',
     aperture VARCHAR(16) NOT NULL DEFAULT '',
     exposure_time VARCHAR(16) NOT NULL DEFAULT '',
-    sensitivity VARCHAR(16) NOT NULL DEFAULT ''
+    sensitivity VARCHAR(16) NOT NULL DEFAULT '',
+    alternate_files TEXT NOT NULL DEFAULT '',
+    media_type VARCHAR(16) NOT NULL DEFAULT 'photo',
+    duration REAL NOT NULL DEFAULT 0,
+    description TEXT NOT NULL DEFAULT '',
+    rating INTEGER NOT NULL DEFAULT 0,
+    keywords TEXT NOT NULL DEFAULT '',
+    orientation INTEGER NOT NULL DEFAULT 0,
+    latitude REAL,
+    longitude REAL,
+    altitude REAL,
+    exif_version INTEGER NOT NULL DEFAULT 0,
+    taken_at INTEGER,
+    taken_at_offset INTEGER,
+    file_size INTEGER,
+    file_mtime INTEGER,
+    sidecar_mtime INTEGER
 );
 ";
 
+/// Columns added to the `photo` table after its creation, with the query used to add them to
+/// an existing database. Since rows are decoded based on the index of their columns, new columns
+/// must always be appended at the end of the table in SCHEMA, in the same order as this list.
+const SCHEMA_MIGRATIONS: [(&str, &str); 16] = [
+    (
+        "alternate_files",
+        "ALTER TABLE photo ADD COLUMN alternate_files TEXT NOT NULL DEFAULT ''",
+    ),
+    (
+        "media_type",
+        "ALTER TABLE photo ADD COLUMN media_type VARCHAR(16) NOT NULL DEFAULT 'photo'",
+    ),
+    (
+        "duration",
+        "ALTER TABLE photo ADD COLUMN duration REAL NOT NULL DEFAULT 0",
+    ),
+    (
+        "description",
+        "ALTER TABLE photo ADD COLUMN description TEXT NOT NULL DEFAULT ''",
+    ),
+    (
+        "rating",
+        "ALTER TABLE photo ADD COLUMN rating INTEGER NOT NULL DEFAULT 0",
+    ),
+    (
+        "keywords",
+        "ALTER TABLE photo ADD COLUMN keywords TEXT NOT NULL DEFAULT ''",
+    ),
+    (
+        "orientation",
+        "ALTER TABLE photo ADD COLUMN orientation INTEGER NOT NULL DEFAULT 0",
+    ),
+    ("latitude", "ALTER TABLE photo ADD COLUMN latitude REAL"),
+    ("longitude", "ALTER TABLE photo ADD COLUMN longitude REAL"),
+    ("altitude", "ALTER TABLE photo ADD COLUMN altitude REAL"),
+    (
+        "exif_version",
+        "ALTER TABLE photo ADD COLUMN exif_version INTEGER NOT NULL DEFAULT 0",
+    ),
+    ("taken_at", "ALTER TABLE photo ADD COLUMN taken_at INTEGER"),
+    (
+        "taken_at_offset",
+        "ALTER TABLE photo ADD COLUMN taken_at_offset INTEGER",
+    ),
+    (
+        "file_size",
+        "ALTER TABLE photo ADD COLUMN file_size INTEGER",
+    ),
+    (
+        "file_mtime",
+        "ALTER TABLE photo ADD COLUMN file_mtime INTEGER",
+    ),
+    (
+        "sidecar_mtime",
+        "ALTER TABLE photo ADD COLUMN sidecar_mtime INTEGER",
+    ),
+];
+
+/// Tables created alongside the `photo` table, with the query used to create them. They are also
+/// created in existing databases if they are missing.
+const SCHEMA_TABLES: [(&str, &str); 3] = [
+    (
+        "photo_metadata",
+        "CREATE TABLE IF NOT EXISTS photo_metadata (
+            photo_id INTEGER PRIMARY KEY,
+            metadata TEXT NOT NULL DEFAULT '{}'
+        )",
+    ),
+    (
+        "share_link",
+        "CREATE TABLE IF NOT EXISTS share_link (
+            token TEXT PRIMARY KEY,
+            path TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            expires_at INTEGER NOT NULL,
+            max_uses INTEGER,
+            uses INTEGER NOT NULL DEFAULT 0,
+            revoked INTEGER NOT NULL DEFAULT 0
+        )",
+    ),
+    (
+        "photo_search",
+        "CREATE VIRTUAL TABLE IF NOT EXISTS photo_search USING fts5(
+            filename,
+            path,
+            title,
+            place,
+            keywords,
+            camera_model,
+            lens_model,
+            tokenize = 'unicode61 remove_diacritics 2'
+        )",
+    ),
+];
+
+/// Columns of the `photo` table copied into the `photo_search` full-text index, in the same order
+const SEARCH_COLUMNS: &str = "filename, path, title, place, keywords, camera_model, lens_model";
+
+/// Separator used to store lists of filenames in a single column
+const FILENAMES_SEPARATOR: &str = "/";
+
+/// Separator used to store lists of keywords in a single column
+const KEYWORDS_SEPARATOR: &str = "\n";
+
 #[derive(Database)]
 #[database("niobium")]
 pub struct DB(pub sqlx::SqlitePool);
@@ -56,8 +185,14 @@ pub async fn init_schema(rocket: Rocket<Build>) -> fairing::Result {
                 .fetch_optional(db)
                 .await;
         match query_result {
-            // The table already exists, we can proceed with liftoff
-            Ok(Some(_)) => Ok(rocket),
+            // The table already exists, make sure it is up to date and proceed with liftoff
+            Ok(Some(_)) => match migrate_schema(db).await {
+                Ok(_) => Ok(rocket),
+                Err(error) => {
+                    eprintln!("Error, unable to update the database schema : {error}");
+                    Err(rocket)
+                }
+            },
 
             // The table doesn't exist, try to import the schema to create it
             Ok(None) => {
@@ -78,6 +213,11 @@ pub async fn init_schema(rocket: Rocket<Build>) -> fairing::Result {
                         return Err(rocket);
                     }
                 }
+                if let Err(error) = create_tables(db).await {
+                    println!();
+                    eprintln!("Error, unable to create the database tables : {error}");
+                    return Err(rocket);
+                }
                 println!("success");
                 Ok(rocket)
             }
@@ -93,6 +233,37 @@ pub async fn init_schema(rocket: Rocket<Build>) -> fairing::Result {
     }
 }
 
+/// Add the columns listed in SCHEMA_MIGRATIONS that are missing from the `photo` table
+async fn migrate_schema(db: &sqlx::SqlitePool) -> Result<(), Error> {
+    let existing_columns = sqlx::query("PRAGMA table_info(photo);")
+        .fetch_all(db)
+        .await?
+        .iter()
+        .filter_map(|row| row.try_get::<String, _>(1).ok())
+        .collect::<Vec<String>>();
+    for (column, sql_query) in SCHEMA_MIGRATIONS {
+        if !existing_columns.iter().any(|c| c == column) {
+            println!("Updating database schema : adding column `{column}`");
+            sqlx::query(sql_query).execute(db).await?;
+        }
+    }
+    create_tables(db).await
+}
+
+/// Create the tables listed in SCHEMA_TABLES that don't exist yet, and index the photos that are
+/// missing from the full-text index
+async fn create_tables(db: &sqlx::SqlitePool) -> Result<(), Error> {
+    for (_, sql_query) in SCHEMA_TABLES {
+        sqlx::query(sql_query).execute(db).await?;
+    }
+    sqlx::query(&format!(
+        "INSERT INTO photo_search(rowid, {SEARCH_COLUMNS}) SELECT id, {SEARCH_COLUMNS} FROM photo WHERE id NOT IN (SELECT rowid FROM photo_search);"
+    ))
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
 /// Get the list of UIDs that exist in the database
 pub async fn get_existing_uids(db_conn: &mut SqliteConnection) -> Result<Vec<UID>, Error> {
     Ok(sqlx::query("SELECT uid FROM photo;")
@@ -146,26 +317,32 @@ pub async fn get_photos_in_paths(
     Ok(photos)
 }
 
+/// Get the list of photos whose EXIF data has been read by a version older than `exif_version`
+pub async fn get_photos_with_outdated_exif(
+    db_conn: &mut SqliteConnection,
+    exif_version: u32,
+    config: &Config,
+) -> Result<Vec<Photo>, Error> {
+    let query = sqlx::query("SELECT * FROM photo WHERE exif_version<?;").bind(exif_version);
+    get_photos_from_query(db_conn, query, config).await
+}
+
 /// Get the list of photos known in the database that are registered in the given path, ordered
 pub async fn get_photos_in_path(
     db_conn: &mut SqliteConnection,
     path: &Path,
-    sort_columns: &[String],
+    sort_order: &SortOrder,
     config: &Config,
 ) -> Result<Vec<Photo>, Error> {
     let mut query_builder = QueryBuilder::new("SELECT * FROM photo WHERE path=");
     query_builder.push_bind(path.to_string_lossy());
 
-    let sort_columns = sort_columns
-        .iter()
-        .map(|c| c.trim())
-        .filter(|&c| !c.is_empty())
-        .collect::<Vec<&str>>();
-    if !sort_columns.is_empty() {
+    // Column names come from a fixed list of fields, so they can safely be written in the query
+    if !sort_order.keys().is_empty() {
         query_builder.push(" ORDER BY ");
         let mut separated = query_builder.separated(", ");
-        for col in sort_columns {
-            separated.push(col);
+        for key in sort_order.keys() {
+            separated.push(format!("{} {}", key.field.column(), key.direction.as_str()));
         }
         separated.push_unseparated(";");
     } else {
@@ -230,7 +407,23 @@ pub async fn insert_photos(db_conn: &mut SqliteConnection, photos: &[Photo]) ->
                 focal_length,
                 aperture,
                 exposure_time,
-                sensitivity
+                sensitivity,
+                alternate_files,
+                media_type,
+                duration,
+                description,
+                rating,
+                keywords,
+                orientation,
+                latitude,
+                longitude,
+                altitude,
+                exif_version,
+                taken_at,
+                taken_at_offset,
+                file_size,
+                file_mtime,
+                sidecar_mtime
         ) ",
         );
         query_builder.push_values(batch, |mut builder, photo| {
@@ -238,7 +431,7 @@ pub async fn insert_photos(db_conn: &mut SqliteConnection, photos: &[Photo]) ->
                 .push_bind(&photo.filename)
                 .push_bind(photo.path.to_string_lossy())
                 .push_bind(photo.uid.to_string())
-                .push_bind(&photo.md5)
+                .push_bind(&photo.hash)
                 .push_bind(photo.sort_order)
                 .push_bind(photo.hidden)
                 .push_bind(photo.metadata_parsed)
@@ -253,12 +446,33 @@ pub async fn insert_photos(db_conn: &mut SqliteConnection, photos: &[Photo]) ->
                 .push_bind(&photo.focal_length)
                 .push_bind(&photo.aperture)
                 .push_bind(&photo.exposure_time)
-                .push_bind(&photo.sensitivity);
+                .push_bind(&photo.sensitivity)
+                .push_bind(photo.alternate_files.join(FILENAMES_SEPARATOR))
+                .push_bind(photo.media_type.as_str())
+                .push_bind(photo.duration)
+                .push_bind(&photo.description)
+                .push_bind(photo.rating)
+                .push_bind(photo.keywords.join(KEYWORDS_SEPARATOR))
+                .push_bind(photo.orientation)
+                .push_bind(photo.latitude)
+                .push_bind(photo.longitude)
+                .push_bind(photo.altitude)
+                .push_bind(photo.exif_version)
+                .push_bind(photo.taken_at)
+                .push_bind(photo.taken_at_offset)
+                .push_bind(photo.file_size)
+                .push_bind(photo.file_mtime)
+                .push_bind(photo.sidecar_mtime);
         });
         let query = query_builder.build();
         query.execute(&mut *db_conn).await?;
     }
-    Ok(())
+
+    // Save the full dump of their metadata, now that they have an ID
+    for photo in photos {
+        update_all_metadata(db_conn, photo).await?;
+    }
+    update_search_index(db_conn, photos.iter().map(|photo| &photo.uid)).await
 }
 
 /// Remove a list of photos from the database, based on their UIDs
@@ -275,6 +489,14 @@ pub async fn remove_photos(db_conn: &mut SqliteConnection, photos: &[Photo]) ->
         let query = query_builder.build();
         query.execute(&mut *db_conn).await?;
     }
+
+    // Remove the metadata of the photos that don't exist anymore
+    sqlx::query("DELETE FROM photo_metadata WHERE photo_id NOT IN (SELECT id FROM photo);")
+        .execute(&mut *db_conn)
+        .await?;
+    sqlx::query("DELETE FROM photo_search WHERE rowid NOT IN (SELECT id FROM photo);")
+        .execute(&mut *db_conn)
+        .await?;
     Ok(())
 }
 
@@ -284,24 +506,285 @@ pub async fn move_photos(
     photos_pairs: &Vec<(Photo, Photo)>,
 ) -> Result<(), Error> {
     for photos_pair in photos_pairs {
-        sqlx::query("UPDATE photo SET filename=?, path=? WHERE uid=?;")
+        // The hash of the file is only updated if it has been computed from its new location
+        sqlx::query("UPDATE photo SET filename=?, path=?, alternate_files=?, md5=COALESCE(NULLIF(?, ''), md5), file_size=?, file_mtime=? WHERE uid=?;")
             .bind(&photos_pair.1.filename)
             .bind(photos_pair.1.path.to_string_lossy())
+            .bind(photos_pair.1.alternate_files.join(FILENAMES_SEPARATOR))
+            .bind(&photos_pair.1.hash)
+            .bind(photos_pair.1.file_size)
+            .bind(photos_pair.1.file_mtime)
             .bind(photos_pair.0.uid.to_string())
             .execute(&mut *db_conn)
             .await?;
     }
+    update_search_index(db_conn, photos_pairs.iter().map(|pair| &pair.0.uid)).await
+}
+
+/// Update the size and last modification time of the file of the given photo in the database, based
+/// on its UID
+pub async fn update_fingerprint(
+    db_conn: &mut SqliteConnection,
+    photo: &Photo,
+) -> Result<(), Error> {
+    sqlx::query("UPDATE photo SET file_size=?, file_mtime=? WHERE uid=?;")
+        .bind(photo.file_size)
+        .bind(photo.file_mtime)
+        .bind(photo.uid.to_string())
+        .execute(&mut *db_conn)
+        .await?;
+    Ok(())
+}
+
+/// Update the list of alternate files of the given photo in the database, based on its UID
+pub async fn update_alternate_files(
+    db_conn: &mut SqliteConnection,
+    photo: &Photo,
+) -> Result<(), Error> {
+    sqlx::query("UPDATE photo SET alternate_files=? WHERE uid=?;")
+        .bind(photo.alternate_files.join(FILENAMES_SEPARATOR))
+        .bind(photo.uid.to_string())
+        .execute(&mut *db_conn)
+        .await?;
+    Ok(())
+}
+
+/// Update the descriptive metadata (title, place, description, rating and keywords) of the given photo
+/// in the database, based on its UID
+pub async fn update_descriptive_metadata(
+    db_conn: &mut SqliteConnection,
+    photo: &Photo,
+) -> Result<(), Error> {
+    sqlx::query(
+        "UPDATE photo SET title=?, place=?, description=?, rating=?, keywords=?, sidecar_mtime=? WHERE uid=?;",
+    )
+    .bind(&photo.title)
+    .bind(&photo.place)
+    .bind(&photo.description)
+    .bind(photo.rating)
+    .bind(photo.keywords.join(KEYWORDS_SEPARATOR))
+    .bind(photo.sidecar_mtime)
+    .bind(photo.uid.to_string())
+    .execute(&mut *db_conn)
+    .await?;
+    update_search_index(db_conn, [&photo.uid]).await
+}
+
+/// Update the fields read from the EXIF data of the given photo in the database, as well as its
+/// dimensions which depend on its orientation, based on its UID
+pub async fn update_exif_metadata(
+    db_conn: &mut SqliteConnection,
+    photo: &Photo,
+) -> Result<(), Error> {
+    sqlx::query(
+        "
+        UPDATE photo SET
+            date_taken=?,
+            camera_model=?,
+            lens_model=?,
+            focal_length=?,
+            aperture=?,
+            exposure_time=?,
+            sensitivity=?,
+            orientation=?,
+            width=?,
+            height=?,
+            latitude=?,
+            longitude=?,
+            altitude=?,
+            exif_version=?,
+            taken_at=?,
+            taken_at_offset=?
+        WHERE uid=?;",
+    )
+    .bind(&photo.date_taken)
+    .bind(&photo.camera_model)
+    .bind(&photo.lens_model)
+    .bind(&photo.focal_length)
+    .bind(&photo.aperture)
+    .bind(&photo.exposure_time)
+    .bind(&photo.sensitivity)
+    .bind(photo.orientation)
+    .bind(photo.width)
+    .bind(photo.height)
+    .bind(photo.latitude)
+    .bind(photo.longitude)
+    .bind(photo.altitude)
+    .bind(photo.exif_version)
+    .bind(photo.taken_at)
+    .bind(photo.taken_at_offset)
+    .bind(photo.uid.to_string())
+    .execute(&mut *db_conn)
+    .await?;
+    update_search_index(db_conn, [&photo.uid]).await
+}
+
+/// Save the full dump of the EXIF and XMP metadata of the given photo in the database, based on
+/// its UID
+pub async fn update_all_metadata(
+    db_conn: &mut SqliteConnection,
+    photo: &Photo,
+) -> Result<(), Error> {
+    let metadata = json::to_string(&photo.all_metadata)
+        .map_err(|e| Error::OtherError(format!("unable to serialize metadata : {e}")))?;
+    sqlx::query(
+        "INSERT OR REPLACE INTO photo_metadata(photo_id, metadata) SELECT id, ? FROM photo WHERE uid=?;",
+    )
+    .bind(metadata)
+    .bind(photo.uid.to_string())
+    .execute(&mut *db_conn)
+    .await?;
+    Ok(())
+}
+
+/// Copy the searchable columns of the photos with the given UIDs into the full-text index
+async fn update_search_index<'a>(
+    db_conn: &mut SqliteConnection,
+    uids: impl IntoIterator<Item = &'a UID>,
+) -> Result<(), Error> {
+    let uids = uids
+        .into_iter()
+        .map(|uid| uid.to_string())
+        .collect::<Vec<String>>();
+    let sql_queries = [
+        (
+            "DELETE FROM photo_search WHERE rowid IN (SELECT id FROM photo WHERE uid IN (".to_string(),
+            "));",
+        ),
+        (
+            format!("INSERT INTO photo_search(rowid, {SEARCH_COLUMNS}) SELECT id, {SEARCH_COLUMNS} FROM photo WHERE uid IN ("),
+            ");",
+        ),
+    ];
+
+    // Update photos by batches of up to 100
+    for batch in uids.chunks(100) {
+        for (sql_query_start, sql_query_end) in &sql_queries {
+            let mut query_builder: QueryBuilder<Sqlite> = QueryBuilder::new(sql_query_start);
+            let mut separated = query_builder.separated(", ");
+            for uid in batch {
+                separated.push_bind(uid);
+            }
+            separated.push_unseparated(*sql_query_end);
+            query_builder.build().execute(&mut *db_conn).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Return the UIDs of the photos that match every word of the given search, as a prefix, in any of
+/// their indexed columns
+pub async fn search_photos(
+    db_conn: &mut SqliteConnection,
+    search: &str,
+) -> Result<HashSet<UID>, Error> {
+    // Quote every word to prevent them from being interpreted as FTS5 operators
+    let search = search
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<String>>()
+        .join(" ");
+    if search.is_empty() {
+        return Ok(HashSet::new());
+    }
+    Ok(sqlx::query(
+        "SELECT photo.uid FROM photo_search JOIN photo ON photo.id = photo_search.rowid WHERE photo_search MATCH ?;",
+    )
+    .bind(search)
+    .fetch_all(&mut *db_conn)
+    .await?
+    .iter()
+    .filter_map(|row| {
+        row.try_get(0)
+            .ok()
+            .and_then(|col: String| UID::try_from(&col).ok())
+    })
+    .collect())
+}
+
+/// Return the full dump of the EXIF and XMP metadata of the photo with the given UID, or an empty
+/// list if it hasn't been saved
+pub async fn get_all_metadata(
+    db_conn: &mut SqliteConnection,
+    uid: &UID,
+) -> Result<BTreeMap<String, String>, Error> {
+    let row = sqlx::query(
+        "SELECT photo_metadata.metadata FROM photo_metadata JOIN photo ON photo.id = photo_metadata.photo_id WHERE photo.uid=?;",
+    )
+    .bind(uid.to_string())
+    .fetch_optional(&mut *db_conn)
+    .await?;
+    match row {
+        Some(row) => json::from_str(row.try_get::<&str, _>(0)?)
+            .map_err(|e| Error::OtherError(format!("unable to parse metadata : {e}"))),
+        None => Ok(BTreeMap::new()),
+    }
+}
+
+/// Return every share link, including the expired and revoked ones
+pub async fn get_share_links(db_conn: &mut SqliteConnection) -> Result<Vec<ShareLink>, Error> {
+    sqlx::query(
+        "SELECT token, path, created_at, expires_at, max_uses, uses, revoked FROM share_link;",
+    )
+    .fetch_all(&mut *db_conn)
+    .await?
+    .iter()
+    .map(|row| -> Result<ShareLink, Error> {
+        Ok(ShareLink {
+            token: row.try_get(0)?,
+            path: row.try_get(1)?,
+            created_at: row.try_get(2)?,
+            expires_at: row.try_get(3)?,
+            max_uses: row.try_get(4)?,
+            uses: row.try_get(5)?,
+            revoked: row.try_get(6)?,
+        })
+    })
+    .collect()
+}
+
+/// Insert a new share link
+pub async fn insert_share_link(
+    db_conn: &mut SqliteConnection,
+    share_link: &ShareLink,
+) -> Result<(), Error> {
+    sqlx::query(
+        "INSERT INTO share_link(token, path, created_at, expires_at, max_uses, uses, revoked) VALUES (?, ?, ?, ?, ?, ?, ?);",
+    )
+    .bind(&share_link.token)
+    .bind(&share_link.path)
+    .bind(share_link.created_at)
+    .bind(share_link.expires_at)
+    .bind(share_link.max_uses)
+    .bind(share_link.uses)
+    .bind(share_link.revoked)
+    .execute(&mut *db_conn)
+    .await?;
+    Ok(())
+}
+
+/// Save the number of times the given share link has been used and whether it has been revoked
+pub async fn update_share_link(
+    db_conn: &mut SqliteConnection,
+    share_link: &ShareLink,
+) -> Result<(), Error> {
+    sqlx::query("UPDATE share_link SET uses=?, revoked=? WHERE token=?;")
+        .bind(share_link.uses)
+        .bind(share_link.revoked)
+        .bind(&share_link.token)
+        .execute(&mut *db_conn)
+        .await?;
     Ok(())
 }
 
-/// Deserialize an SQL row into a Photo struct, based on the order defined in schema.sql
+/// Deserialize an SQL row into a Photo struct, based on the order defined in SCHEMA
 fn row_to_photo(row: &SqliteRow) -> Result<Photo, sqlx::Error> {
     Ok(Photo {
         id: row.try_get(0)?,
         filename: row.try_get(1)?,
         path: PathBuf::from(row.try_get::<String, _>(2)?),
         uid: UID::try_from(row.try_get::<&str, _>(3)?).unwrap_or_else(|_| UID::empty()),
-        md5: row.try_get(4)?,
+        hash: row.try_get::<Option<String>, _>(4)?.unwrap_or_default(),
         sort_order: row.try_get(5)?,
         hidden: row.try_get(6)?,
         metadata_parsed: row.try_get(7)?,
@@ -317,6 +800,32 @@ fn row_to_photo(row: &SqliteRow) -> Result<Photo, sqlx::Error> {
         aperture: row.try_get(17)?,
         exposure_time: row.try_get(18)?,
         sensitivity: row.try_get(19)?,
+        alternate_files: row
+            .try_get::<&str, _>(20)?
+            .split(FILENAMES_SEPARATOR)
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect(),
+        media_type: MediaType::from(row.try_get::<&str, _>(21)?),
+        duration: row.try_get(22)?,
+        description: row.try_get(23)?,
+        rating: row.try_get(24)?,
+        keywords: row
+            .try_get::<&str, _>(25)?
+            .split(KEYWORDS_SEPARATOR)
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect(),
+        orientation: row.try_get(26)?,
+        latitude: row.try_get(27)?,
+        longitude: row.try_get(28)?,
+        altitude: row.try_get(29)?,
+        exif_version: row.try_get(30)?,
+        taken_at: row.try_get(31)?,
+        taken_at_offset: row.try_get(32)?,
+        file_size: row.try_get(33)?,
+        file_mtime: row.try_get(34)?,
+        sidecar_mtime: row.try_get(35)?,
         ..Default::default()
     })
 }
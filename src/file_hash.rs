@@ -0,0 +1,136 @@
+use crate::Error;
+use md5::{Digest, Md5};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio::fs::File;
+use rocket::tokio::io::AsyncReadExt;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use xxhash_rust::xxh3::Xxh3;
+
+/// Size of the chunks in which files are read to compute their hash
+const READ_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Algorithms available to compute the hash of the content of files
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Default)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum HashAlgorithm {
+    #[default]
+    MD5,
+    BLAKE3,
+    XXH3,
+}
+
+impl HashAlgorithm {
+    /// Prefix of the hashes computed with this algorithm, as stored in the database. MD5 hashes have
+    /// none for compatibility with the databases created by previous versions.
+    fn prefix(&self) -> &'static str {
+        match self {
+            HashAlgorithm::MD5 => "",
+            HashAlgorithm::BLAKE3 => "blake3:",
+            HashAlgorithm::XXH3 => "xxh3:",
+        }
+    }
+}
+
+/// Return the algorithm used to compute the given hash, based on its prefix
+pub fn algorithm_of(hash: &str) -> HashAlgorithm {
+    [HashAlgorithm::BLAKE3, HashAlgorithm::XXH3]
+        .into_iter()
+        .find(|algorithm| hash.starts_with(algorithm.prefix()))
+        .unwrap_or(HashAlgorithm::MD5)
+}
+
+/// Check if the two given hashes are known and have been computed with the same algorithm
+pub fn is_comparable(hash: &str, other_hash: &str) -> bool {
+    !hash.is_empty() && !other_hash.is_empty() && algorithm_of(hash) == algorithm_of(other_hash)
+}
+
+/// Check if the two given hashes are known and identical, meaning that the files have the same content
+pub fn is_same(hash: &str, other_hash: &str) -> bool {
+    is_comparable(hash, other_hash) && hash == other_hash
+}
+
+/// State of a hash being computed, for any of the available algorithms
+enum Hasher {
+    MD5(Md5),
+    BLAKE3(Box<blake3::Hasher>),
+    XXH3(Box<Xxh3>),
+}
+
+impl Hasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::MD5 => Hasher::MD5(Md5::new()),
+            HashAlgorithm::BLAKE3 => Hasher::BLAKE3(Box::new(blake3::Hasher::new())),
+            HashAlgorithm::XXH3 => Hasher::XXH3(Box::new(Xxh3::new())),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::MD5(hasher) => hasher.update(data),
+            Hasher::BLAKE3(hasher) => {
+                hasher.update(data);
+            }
+            Hasher::XXH3(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finalize(self) -> String {
+        match self {
+            Hasher::MD5(hasher) => format!("{:x}", hasher.finalize()),
+            Hasher::BLAKE3(hasher) => hasher.finalize().to_hex().to_string(),
+            Hasher::XXH3(hasher) => format!("{:032x}", hasher.digest128()),
+        }
+    }
+}
+
+/// Calculate the hashes of the file located at the given path with each of the given algorithms,
+/// reading it only once, and return them in the same order. Each hash is prefixed by the name of its
+/// algorithm (except for MD5).
+pub async fn calculate_all(
+    path: &PathBuf,
+    algorithms: &[HashAlgorithm],
+) -> Result<Vec<String>, Error> {
+    let mut file = File::open(path)
+        .await
+        .map_err(|e| Error::FileError(e, path.clone()))?;
+    let mut hashers = algorithms
+        .iter()
+        .map(|&algorithm| Hasher::new(algorithm))
+        .collect::<Vec<_>>();
+    let mut buffer = vec![0; READ_BUFFER_SIZE];
+    loop {
+        let n = file
+            .read(&mut buffer)
+            .await
+            .map_err(|e| Error::FileError(e, path.clone()))?;
+        if n == 0 {
+            break;
+        }
+        for hasher in hashers.iter_mut() {
+            hasher.update(&buffer[..n]);
+        }
+    }
+    Ok(algorithms
+        .iter()
+        .zip(hashers)
+        .map(|(algorithm, hasher)| format!("{}{}", algorithm.prefix(), hasher.finalize()))
+        .collect())
+}
+
+/// Return the size of the file located at the given path and its last modification time as a Unix
+/// timestamp, which together are used as a quick fingerprint of its content
+pub fn fingerprint(path: &Path) -> Result<(i64, i64), Error> {
+    let metadata = path
+        .metadata()
+        .map_err(|e| Error::FileError(e, path.to_path_buf()))?;
+    let modified = metadata
+        .modified()
+        .map_err(|e| Error::FileError(e, path.to_path_buf()))?;
+    let mtime = match modified.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(error) => -(error.duration().as_secs() as i64),
+    };
+    Ok((metadata.len() as i64, mtime))
+}
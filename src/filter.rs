@@ -0,0 +1,331 @@
+use crate::photos::Photo;
+use chrono::{Datelike, NaiveDate};
+use rocket::form::{self, DataField, FromForm, FromFormField, Options, ValueField};
+use rocket::serde::de::{self, Deserializer, Unexpected, Visitor};
+use rocket::serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Deref;
+use utoipa::{IntoParams, ToSchema};
+
+/// Filters on the metadata of the photos, read from the query parameters of the grid. Photos for
+/// which a filtered field is unknown never match this filter. The form fails if a filter has an
+/// invalid value.
+#[derive(FromForm, IntoParams, Default, Debug)]
+#[into_params(parameter_in = Query)]
+pub struct PhotoFilter {
+    /// Model of the camera, case-insensitive
+    camera: Option<String>,
+    /// Model of the lens, case-insensitive
+    lens: Option<String>,
+    /// Minimum focal length, in millimeters
+    #[param(value_type = Option<f64>)]
+    focal_min: OptionalValue<f64>,
+    /// Maximum focal length, in millimeters
+    #[param(value_type = Option<f64>)]
+    focal_max: OptionalValue<f64>,
+    /// Minimum f-number
+    #[param(value_type = Option<f64>)]
+    aperture_min: OptionalValue<f64>,
+    /// Maximum f-number
+    #[param(value_type = Option<f64>)]
+    aperture_max: OptionalValue<f64>,
+    /// Minimum ISO sensitivity
+    #[param(value_type = Option<u32>)]
+    iso_min: OptionalValue<u32>,
+    /// Maximum ISO sensitivity
+    #[param(value_type = Option<u32>)]
+    iso_max: OptionalValue<u32>,
+    /// First day on which the photos have been taken, as `YYYY`, `YYYY-MM` or `YYYY-MM-DD`
+    #[param(value_type = Option<String>)]
+    from: OptionalValue<DateBound>,
+    /// Last day on which the photos have been taken, as `YYYY`, `YYYY-MM` or `YYYY-MM-DD`
+    #[param(value_type = Option<String>)]
+    to: OptionalValue<DateBound>,
+}
+
+impl PhotoFilter {
+    /// Check if no filter is set, in which case every photo matches
+    pub fn is_empty(&self) -> bool {
+        self.camera.is_none()
+            && self.lens.is_none()
+            && self.focal_min.is_none()
+            && self.focal_max.is_none()
+            && self.aperture_min.is_none()
+            && self.aperture_max.is_none()
+            && self.iso_min.is_none()
+            && self.iso_max.is_none()
+            && self.from.is_none()
+            && self.to.is_none()
+    }
+
+    /// Check if the given photo matches every filter that is set
+    pub fn matches(&self, photo: &Photo) -> bool {
+        let in_range = |value: Option<f64>, min: Option<f64>, max: Option<f64>| {
+            (min.is_none() && max.is_none())
+                || value.is_some_and(|value| {
+                    min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max)
+                })
+        };
+        let taken_on = taken_on(photo);
+        self.camera
+            .as_ref()
+            .is_none_or(|camera| camera.eq_ignore_ascii_case(photo.camera_model.trim()))
+            && self
+                .lens
+                .as_ref()
+                .is_none_or(|lens| lens.eq_ignore_ascii_case(photo.lens_model.trim()))
+            && in_range(
+                parse_number(&photo.focal_length),
+                *self.focal_min,
+                *self.focal_max,
+            )
+            && in_range(
+                parse_number(&photo.aperture),
+                *self.aperture_min,
+                *self.aperture_max,
+            )
+            && in_range(
+                parse_number(&photo.sensitivity),
+                self.iso_min.map(f64::from),
+                self.iso_max.map(f64::from),
+            )
+            && self
+                .from
+                .is_none_or(|from| taken_on.is_some_and(|date| date >= from.first_day))
+            && self
+                .to
+                .is_none_or(|to| taken_on.is_some_and(|date| date <= to.last_day))
+    }
+}
+
+/// Describe the errors of an invalid PhotoFilter, on a single line that names the invalid fields
+pub fn describe_errors(errors: &form::Errors<'_>) -> String {
+    errors
+        .iter()
+        .map(|error| match &error.name {
+            Some(name) => format!("{name} : {}", error.kind),
+            None => error.kind.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// An optional form field, which unlike `Option<T>` fails if the field is present with an invalid
+/// value instead of ignoring it
+#[derive(Debug)]
+pub struct OptionalValue<T>(Option<T>);
+
+impl<T> Default for OptionalValue<T> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+impl<T> Deref for OptionalValue<T> {
+    type Target = Option<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'v, T: FromForm<'v>> FromForm<'v> for OptionalValue<T> {
+    type Context = T::Context;
+
+    fn init(_: Options) -> Self::Context {
+        // A missing field is only told apart from an invalid one in strict mode
+        T::init(Options::Strict)
+    }
+
+    fn push_value(ctxt: &mut Self::Context, field: ValueField<'v>) {
+        T::push_value(ctxt, field)
+    }
+
+    async fn push_data(ctxt: &mut Self::Context, field: DataField<'v, '_>) {
+        T::push_data(ctxt, field).await
+    }
+
+    fn finalize(ctxt: Self::Context) -> form::Result<'v, Self> {
+        match T::finalize(ctxt) {
+            Ok(value) => Ok(Self(Some(value))),
+            Err(errors)
+                if errors
+                    .iter()
+                    .all(|error| matches!(error.kind, form::error::ErrorKind::Missing)) =>
+            {
+                Ok(Self(None))
+            }
+            Err(errors) => Err(errors),
+        }
+    }
+}
+
+/// A date given with a precision of a year, a month or a day, which covers the period between
+/// `first_day` and `last_day` (inclusive)
+#[derive(Debug, Copy, Clone)]
+pub struct DateBound {
+    pub first_day: NaiveDate,
+    pub last_day: NaiveDate,
+}
+
+impl DateBound {
+    /// Parse a date formatted as `YYYY`, `YYYY-MM` or `YYYY-MM-DD`
+    fn parse(value: &str) -> Option<Self> {
+        let parts = value
+            .trim()
+            .split('-')
+            .map(|part| part.parse::<u32>().ok())
+            .collect::<Option<Vec<u32>>>()?;
+        match parts[..] {
+            [year] => Some(Self {
+                first_day: NaiveDate::from_ymd_opt(year as i32, 1, 1)?,
+                last_day: NaiveDate::from_ymd_opt(year as i32, 12, 31)?,
+            }),
+            [year, month] => {
+                let first_day = NaiveDate::from_ymd_opt(year as i32, month, 1)?;
+                let next_month = match month {
+                    12 => NaiveDate::from_ymd_opt(year as i32 + 1, 1, 1)?,
+                    _ => NaiveDate::from_ymd_opt(year as i32, month + 1, 1)?,
+                };
+                Some(Self {
+                    first_day,
+                    last_day: next_month.pred_opt()?,
+                })
+            }
+            [year, month, day] => {
+                let date = NaiveDate::from_ymd_opt(year as i32, month, day)?;
+                Some(Self {
+                    first_day: date,
+                    last_day: date,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromFormField<'r> for DateBound {
+    fn from_value(field: ValueField<'r>) -> form::Result<'r, Self> {
+        DateBound::parse(field.value).ok_or_else(|| {
+            form::Error::validation("expected a date formatted as YYYY, YYYY-MM or YYYY-MM-DD")
+                .into()
+        })
+    }
+}
+
+/// Deserialize a date from the collections config file, either as a string or as a year given as a
+/// number
+impl<'de> Deserialize<'de> for DateBound {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct DateBoundVisitor;
+
+        impl Visitor<'_> for DateBoundVisitor {
+            type Value = DateBound;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a date formatted as YYYY, YYYY-MM or YYYY-MM-DD")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                DateBound::parse(value)
+                    .ok_or_else(|| E::invalid_value(Unexpected::Str(value), &self))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+                self.visit_str(&value.to_string())
+            }
+        }
+
+        deserializer.deserialize_any(DateBoundVisitor)
+    }
+}
+
+/// Values of the metadata of the photos in a path, with the number of photos for each of them,
+/// which can be used to offer filters
+#[derive(Serialize, ToSchema, Default, Debug)]
+pub struct Facets {
+    /// Number of photos taken into account
+    total: usize,
+    camera: Vec<FacetValue>,
+    lens: Vec<FacetValue>,
+    focal_length: Vec<FacetValue>,
+    aperture: Vec<FacetValue>,
+    iso: Vec<FacetValue>,
+    /// Years in which the photos have been taken
+    year: Vec<FacetValue>,
+}
+
+/// A value of a facet and the number of photos that have it
+#[derive(Serialize, ToSchema, Debug)]
+pub struct FacetValue {
+    value: String,
+    count: usize,
+}
+
+impl Facets {
+    /// Count the values of the metadata of the given photos. Unknown values are ignored.
+    pub fn from_photos<'a>(photos: impl Iterator<Item = &'a Photo>) -> Self {
+        let mut total = 0;
+        let mut counts: [HashMap<String, usize>; 6] = Default::default();
+        for photo in photos {
+            total += 1;
+            let values = [
+                photo.camera_model.trim().to_string(),
+                photo.lens_model.trim().to_string(),
+                photo.focal_length.trim().to_string(),
+                photo.aperture.trim().to_string(),
+                photo.sensitivity.trim().to_string(),
+                taken_on(photo)
+                    .map(|date| date.year().to_string())
+                    .unwrap_or_default(),
+            ];
+            for (counts, value) in counts.iter_mut().zip(values) {
+                if !value.is_empty() {
+                    *counts.entry(value).or_default() += 1;
+                }
+            }
+        }
+        let [camera, lens, focal_length, aperture, iso, year] = counts.map(sorted_values);
+        Self {
+            total,
+            camera,
+            lens,
+            focal_length,
+            aperture,
+            iso,
+            year,
+        }
+    }
+}
+
+/// Convert a map of counts into a list of values, sorted by decreasing number of photos
+fn sorted_values(counts: HashMap<String, usize>) -> Vec<FacetValue> {
+    let mut values = counts
+        .into_iter()
+        .map(|(value, count)| FacetValue { value, count })
+        .collect::<Vec<_>>();
+    values.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| natord::compare(&a.value, &b.value))
+    });
+    values
+}
+
+/// Parse a numeric field read from the EXIF data, keeping only the first value if there are several
+fn parse_number(value: &str) -> Option<f64> {
+    value.split(',').next()?.trim().parse().ok()
+}
+
+/// Return the day on which the given photo has been taken, in the time zone in which it has been
+/// taken if it is known
+pub fn taken_on(photo: &Photo) -> Option<NaiveDate> {
+    let offset = i64::from(photo.taken_at_offset.unwrap_or(0)) * 60;
+    chrono::DateTime::from_timestamp(photo.taken_at? + offset, 0).map(|date| date.date_naive())
+}
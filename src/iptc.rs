@@ -0,0 +1,226 @@
+use crate::xmp::DescriptiveMetadata;
+
+/// Signature of the APP13 segment of JPEG files that contains Photoshop's image resources
+const PHOTOSHOP_SIGNATURE: &[u8] = b"Photoshop 3.0\0";
+
+/// ID of the Photoshop image resource that contains an IPTC-IIM block
+const RESOURCE_IPTC: u16 = 0x0404;
+
+// IPTC-IIM datasets of the Application record (2) that are read
+const DATASET_OBJECT_NAME: u8 = 5;
+const DATASET_KEYWORDS: u8 = 25;
+const DATASET_CITY: u8 = 90;
+const DATASET_SUBLOCATION: u8 = 92;
+const DATASET_COUNTRY: u8 = 101;
+const DATASET_CAPTION: u8 = 120;
+
+/// Look for a legacy IPTC-IIM block in the given content of a JPEG file and parse it
+pub fn parse_jpeg(data: &[u8]) -> Option<DescriptiveMetadata> {
+    if !data.starts_with(&[0xff, 0xd8]) {
+        return None;
+    }
+
+    // Walk through the segments of the file until the image data
+    let mut offset = 2;
+    while offset + 4 <= data.len() && data[offset] == 0xff {
+        let marker = data[offset + 1];
+        if marker == 0xda || marker == 0xd9 {
+            // Start of scan or end of image
+            break;
+        }
+        let length = read_u16(&data[offset + 2..]) as usize;
+        let segment = data.get(offset + 4..offset + 2 + length)?;
+        if marker == 0xed {
+            if let Some(resources) = segment.strip_prefix(PHOTOSHOP_SIGNATURE) {
+                if let Some(iim) = find_resource(resources, RESOURCE_IPTC) {
+                    return Some(parse_iim(iim));
+                }
+            }
+        }
+        offset += 2 + length;
+    }
+    None
+}
+
+/// Find the Photoshop image resource with the given ID
+fn find_resource(data: &[u8], id: u16) -> Option<&[u8]> {
+    let mut offset = 0;
+    while offset + 12 <= data.len() && &data[offset..offset + 4] == b"8BIM" {
+        let resource_id = read_u16(&data[offset + 4..]);
+
+        // Skip the name of the resource, which is a Pascal string padded to an even size
+        let name_length = data[offset + 6] as usize;
+        offset += 6 + (name_length + 2) / 2 * 2;
+
+        let size = read_u32(data.get(offset..offset + 4)?) as usize;
+        offset += 4;
+        let resource = data.get(offset..offset + size)?;
+        if resource_id == id {
+            return Some(resource);
+        }
+        offset += size + size % 2;
+    }
+    None
+}
+
+/// Parse the datasets of an IPTC-IIM block
+fn parse_iim(data: &[u8]) -> DescriptiveMetadata {
+    let mut metadata = DescriptiveMetadata::default();
+    let mut utf8 = false;
+    let mut offset = 0;
+    while offset + 5 <= data.len() && data[offset] == 0x1c {
+        let record = data[offset + 1];
+        let dataset = data[offset + 2];
+        let length = read_u16(&data[offset + 3..]) as usize;
+        if length & 0x8000 != 0 {
+            // Extended datasets are not used for text fields
+            break;
+        }
+        let Some(value) = data.get(offset + 5..offset + 5 + length) else {
+            break;
+        };
+        offset += 5 + length;
+
+        match (record, dataset) {
+            // Coded character set : ESC % G means UTF-8
+            (1, 90) => utf8 = value == [0x1b, 0x25, 0x47],
+            (2, _) => {
+                let value = decode_string(value, utf8);
+                match dataset {
+                    DATASET_OBJECT_NAME => metadata.title = value,
+                    DATASET_CAPTION => metadata.description = value,
+                    DATASET_SUBLOCATION => metadata.location = value,
+                    DATASET_CITY => metadata.city = value,
+                    DATASET_COUNTRY => metadata.country = value,
+                    DATASET_KEYWORDS if !value.is_empty() => metadata.keywords.push(value),
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    }
+    metadata
+}
+
+/// Decode a text value, which is either UTF-8 or, for older files, usually Latin-1
+fn decode_string(value: &[u8], utf8: bool) -> String {
+    let value = match std::str::from_utf8(value) {
+        Ok(value) => value.to_string(),
+        Err(_) if utf8 => String::from_utf8_lossy(value).into_owned(),
+        Err(_) => value.iter().map(|&c| c as char).collect(),
+    };
+    value
+        .trim_matches(|c: char| c.is_whitespace() || c == '\0')
+        .to_string()
+}
+
+fn read_u16(data: &[u8]) -> u16 {
+    u16::from_be_bytes([data[0], data[1]])
+}
+
+fn read_u32(data: &[u8]) -> u32 {
+    u32::from_be_bytes([data[0], data[1], data[2], data[3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build an IPTC-IIM dataset
+    fn dataset(record: u8, dataset: u8, value: &[u8]) -> Vec<u8> {
+        let mut data = vec![0x1c, record, dataset];
+        data.extend((value.len() as u16).to_be_bytes());
+        data.extend(value);
+        data
+    }
+
+    /// Build a JPEG file whose APP13 segment contains the given Photoshop image resources
+    fn jpeg_fixture(resources: &[(u16, &[u8])]) -> Vec<u8> {
+        let mut segment = PHOTOSHOP_SIGNATURE.to_vec();
+        for (id, resource) in resources {
+            segment.extend(b"8BIM");
+            segment.extend(id.to_be_bytes());
+            segment.extend([0, 0]); // Empty name
+            segment.extend((resource.len() as u32).to_be_bytes());
+            segment.extend(*resource);
+            if resource.len() % 2 == 1 {
+                segment.push(0);
+            }
+        }
+        let mut jpeg = vec![0xff, 0xd8, 0xff, 0xe0, 0x00, 0x04, 0x00, 0x00];
+        jpeg.extend([0xff, 0xed]);
+        jpeg.extend((segment.len() as u16 + 2).to_be_bytes());
+        jpeg.extend(segment);
+        jpeg.extend([0xff, 0xda, 0x00, 0x02, 0xff, 0xd9]);
+        jpeg
+    }
+
+    fn iim_fixture() -> Vec<u8> {
+        [
+            dataset(1, 90, &[0x1b, 0x25, 0x47]),
+            dataset(2, 0, &[0, 4]),
+            dataset(2, DATASET_OBJECT_NAME, "Coucher de soleil".as_bytes()),
+            dataset(2, DATASET_CAPTION, "Sur la Seine, à Paris\0".as_bytes()),
+            dataset(2, DATASET_KEYWORDS, b"sunset"),
+            dataset(2, DATASET_KEYWORDS, b""),
+            dataset(2, DATASET_KEYWORDS, b" travel "),
+            dataset(2, DATASET_CITY, b"Paris"),
+            dataset(2, DATASET_SUBLOCATION, b"Pont Neuf"),
+            dataset(2, DATASET_COUNTRY, b"France"),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn parse_iptc_block() {
+        let iim = iim_fixture();
+        let metadata =
+            parse_jpeg(&jpeg_fixture(&[(0x0425, b"digest"), (RESOURCE_IPTC, &iim)])).unwrap();
+        assert_eq!(metadata.title, "Coucher de soleil");
+        assert_eq!(metadata.description, "Sur la Seine, à Paris");
+        assert_eq!(metadata.keywords, ["sunset", "travel"]);
+        assert_eq!(metadata.city, "Paris");
+        assert_eq!(metadata.location, "Pont Neuf");
+        assert_eq!(metadata.country, "France");
+        assert_eq!(metadata.rating, None);
+    }
+
+    #[test]
+    fn decode_latin1() {
+        let iim = dataset(2, DATASET_CAPTION, b"Caf\xe9");
+        let metadata = parse_jpeg(&jpeg_fixture(&[(RESOURCE_IPTC, &iim)])).unwrap();
+        assert_eq!(metadata.description, "Café");
+    }
+
+    #[test]
+    fn ignore_missing_iptc() {
+        assert!(parse_jpeg(b"").is_none());
+        assert!(parse_jpeg(b"\x89PNG\r\n\x1a\n").is_none());
+        assert!(parse_jpeg(&jpeg_fixture(&[])).is_none());
+        assert!(parse_jpeg(&jpeg_fixture(&[(0x0425, b"digest")])).is_none());
+    }
+
+    #[test]
+    fn stop_on_malformed_data() {
+        // Truncated file
+        let jpeg = jpeg_fixture(&[(RESOURCE_IPTC, &iim_fixture())]);
+        for length in [3, 5, 10, 20, 40] {
+            assert!(parse_jpeg(&jpeg[..length]).is_none(), "{length}");
+        }
+
+        // Segment too short
+        assert!(parse_jpeg(b"\xff\xd8\xff\xed\x00\x00").is_none());
+
+        // Datasets whose length is extended or goes beyond the block, only the previous ones are kept
+        let mut iim = dataset(2, DATASET_OBJECT_NAME, b"Title");
+        iim.extend([0x1c, 2, DATASET_CAPTION, 0x80, 0x04, 0, 0, 0, 1, b'a']);
+        let metadata = parse_jpeg(&jpeg_fixture(&[(RESOURCE_IPTC, &iim)])).unwrap();
+        assert_eq!(metadata.title, "Title");
+        assert!(metadata.description.is_empty());
+        let mut iim = dataset(2, DATASET_OBJECT_NAME, b"Title");
+        iim.extend([0x1c, 2, DATASET_CAPTION, 0x00, 0xff, b'a']);
+        let metadata = parse_jpeg(&jpeg_fixture(&[(RESOURCE_IPTC, &iim)])).unwrap();
+        assert_eq!(metadata.title, "Title");
+        assert!(metadata.description.is_empty());
+    }
+}
@@ -3,36 +3,78 @@
 #[macro_use]
 extern crate rocket;
 
+mod admin;
+mod api;
+mod cli;
 mod collection;
 mod config;
+mod date_taken;
 mod db;
+mod file_hash;
+mod filter;
+mod iptc;
 mod nav_data;
 mod password;
 mod photos;
+mod privacy;
+mod range;
+mod rate_limit;
+mod raw;
+mod reload;
+mod share;
+mod sort_order;
+mod tiff;
 mod uid;
+mod video;
+mod watcher;
+mod xmp;
 
+use admin::{Admin, AdminError};
 use config::Config;
 use db::DB;
+use filter::PhotoFilter;
 use nav_data::NavData;
-use password::OptionalPassword;
-use photos::Gallery;
+use password::{OptionalPassword, PasswordError};
+use photos::{DownloadVariant, Gallery, MediaType, Photo, PhotoQuery, PhotoQueryError};
+use range::{ByteRange, RangedFile};
+use rate_limit::PasswordAttempts;
+use reload::ReloadStart;
 use rocket::fairing::AdHoc;
+use rocket::form::{self, Form};
 use rocket::fs::NamedFile;
 use rocket::http::{CookieJar, Header};
+use rocket::response::stream::{Event, EventStream};
 use rocket::response::Redirect;
+use rocket::serde::json::{self, Value};
+use rocket::serde::Serialize;
 use rocket::shield::{Frame, Shield};
-use rocket::{fs::FileServer, State};
+use rocket::tokio::select;
+use rocket::{fs::FileServer, Shutdown, State};
 use rocket_db_pools::{sqlx, Connection, Database};
 use rocket_dyn_templates::{context, Template};
+use share::{ShareLinks, SharedPaths};
 use std::net::IpAddr;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::{fmt::Display, io};
 use uid::UID;
 
+/// Image returned instead of the poster frame of videos when it can't be extracted
+const VIDEO_PLACEHOLDER_FILE: &str = "static/img/video.svg";
+
+/// URL of the map tiles served from MAP_TILES_DIR by the `get_map_tile` route
+const LOCAL_MAP_TILES_URL: &str = "/.map-tiles/{z}/{x}/{y}";
+
+/// Image formats looked for in MAP_TILES_DIR, in this order
+const LOCAL_MAP_TILES_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "webp"];
+
 #[launch]
 async fn rocket() -> _ {
     let niobium_version = env!("CARGO_PKG_VERSION");
 
+    // Subcommands don't start the server
+    cli::run_subcommand();
+
     // Try to read the config file
     let config_file_var_name = "NIOBIUM_CONFIG_FILE";
     let config_file_str = match std::env::var(config_file_var_name) {
@@ -78,16 +120,32 @@ async fn rocket() -> _ {
             "/",
             routes![
                 get_gallery,
+                search_gallery,
                 get_grid,
                 get_nav,
+                get_facets,
+                get_map,
+                get_map_tile,
                 get_grid_item,
                 get_thumbnail,
                 get_large,
                 get_photo,
+                get_video,
+                get_metadata,
                 download_photo,
-                reload,
+                start_reload,
+                get_reload_status,
+                get_reload_events,
+                admin_login,
+                admin_logout,
+                open_share_link,
+                open_short_share_link,
+                create_share_link,
+                get_share_links,
+                revoke_share_link,
             ],
         )
+        .mount(api::BASE, api::routes())
         .mount("/static", FileServer::from("static/").rank(0))
         .attach(shield)
         .attach(Template::fairing())
@@ -96,9 +154,14 @@ async fn rocket() -> _ {
             "Database schema init",
             db::init_schema,
         ))
+        .attach(AdHoc::try_on_ignite("Share links init", share::init))
+        .manage(PasswordAttempts::new(&config))
         .manage(config)
-        .manage(Gallery::new())
+        .manage(Arc::new(Gallery::new()))
         .attach(AdHoc::try_on_ignite("Photos init", photos::init))
+        .attach(AdHoc::on_liftoff("Photos watcher", |rocket| {
+            Box::pin(watcher::init(rocket))
+        }))
         .attach(AdHoc::on_liftoff("Startup message", move |_| {
             Box::pin(async move {
                 println!("## Niobium v{niobium_version} started on {address}:{port}");
@@ -110,13 +173,67 @@ async fn rocket() -> _ {
 #[get("/<path..>", rank = 15)]
 async fn get_gallery(
     path: PathBuf,
-    gallery: &State<Gallery>,
+    gallery: &State<Arc<Gallery>>,
+    config: &State<Config>,
+    cookies: &CookieJar<'_>,
+    attempts: &State<PasswordAttempts>,
+    shared_paths: SharedPaths,
+) -> PageResult {
+    render_gallery(
+        path,
+        None,
+        gallery,
+        config,
+        cookies,
+        attempts,
+        &shared_paths,
+    )
+    .await
+}
+
+/// Route handler called to render the main layout of the gallery, with a grid that only shows the
+/// photos that match the given search
+#[get("/<path..>?<search>", rank = 13)]
+async fn search_gallery(
+    path: PathBuf,
+    search: &str,
+    gallery: &State<Arc<Gallery>>,
     config: &State<Config>,
     cookies: &CookieJar<'_>,
+    attempts: &State<PasswordAttempts>,
+    shared_paths: SharedPaths,
+) -> PageResult {
+    render_gallery(
+        path,
+        Some(search),
+        gallery,
+        config,
+        cookies,
+        attempts,
+        &shared_paths,
+    )
+    .await
+}
+
+/// Render the main layout of the gallery for the given path
+async fn render_gallery(
+    path: PathBuf,
+    search: Option<&str>,
+    gallery: &Gallery,
+    config: &Config,
+    cookies: &CookieJar<'_>,
+    attempts: &PasswordAttempts,
+    shared_paths: &SharedPaths,
 ) -> PageResult {
     // Check if a password is required to access this path
     match gallery
-        .check_password(&path, cookies, &OptionalPassword::none())
+        .check_password(
+            &path,
+            cookies,
+            &OptionalPassword::none(),
+            attempts,
+            shared_paths,
+        )
         .await
     {
         // Either no password is required or a valid one has been provided
@@ -129,11 +246,11 @@ async fn get_gallery(
                     Ok(nav_data) => PageResult::Page(Template::render(
                         "main",
                         context! {
-                            config: config.inner(),
+                            config: config,
                             nav: nav_data,
                             uid_chars: UID::CHARS,
                             uid_length: UID::LENGTH,
-                            load_grid_url: uri!(get_grid(&path, None as Option<usize>, None as Option<usize>, None as Option<UID>)).to_string(),
+                            load_grid_url: uri!(get_grid(&path, None as Option<usize>, None as Option<usize>, None as Option<UID>, search, _)).to_string(),
                             load_nav_url: uri!(get_nav(&path)).to_string(),
                         },
                     )),
@@ -158,11 +275,11 @@ async fn get_gallery(
             PageResult::Page(Template::render(
                 "main",
                 context! {
-                    config: config.inner(),
+                    config: config,
                     nav: NavData::new(),
                     uid_chars: UID::CHARS,
                     uid_length: UID::LENGTH,
-                    load_grid_url: uri!(get_grid(&path, None as Option<usize>, None as Option<usize>, None as Option<UID>)).to_string(),
+                    load_grid_url: uri!(get_grid(&path, None as Option<usize>, None as Option<usize>, None as Option<UID>, search, _)).to_string(),
                     load_nav_url: uri!(get_nav(&path)).to_string(),
                 },
             ))
@@ -170,55 +287,66 @@ async fn get_gallery(
     }
 }
 
-/// Route handler called by javascript to return the grid items for the given path and parameters
-#[get("/<path..>?grid&<start>&<count>&<uid>", rank = 10)]
+/// Route handler called by javascript to return the grid items for the given path and parameters. If a
+/// `search` or some metadata filters are provided, only the photos displayed in this path that match
+/// them are returned.
+#[get("/<path..>?grid&<start>&<count>&<uid>&<search>&<filter..>", rank = 10)]
 #[allow(clippy::too_many_arguments)]
 async fn get_grid(
     path: PathBuf,
     start: Option<usize>,
     count: Option<usize>,
     uid: Option<UID>,
-    gallery: &State<Gallery>,
+    search: Option<&str>,
+    filter: Result<PhotoFilter, form::Errors<'_>>,
+    gallery: &State<Arc<Gallery>>,
     config: &State<Config>,
     cookies: &CookieJar<'_>,
     password: OptionalPassword,
+    attempts: &State<PasswordAttempts>,
+    shared_paths: SharedPaths,
+    mut db_conn: Connection<DB>,
 ) -> PageResult {
     // Check if a password is required to access this path
-    match gallery.check_password(&path, cookies, &password).await {
+    match gallery
+        .check_password(&path, cookies, &password, attempts, &shared_paths)
+        .await
+    {
         // Either no password is required or a valid one has been provided
         Ok(passwords) => {
+            // Keep only the photos displayed in this path that match the search and the filters, if
+            // any. Invalid filters are refused instead of forwarding the request to the gallery page.
+            match PhotoQuery::new(&mut db_conn, search, filter).await {
+                Ok(Some(query)) => {
+                    return match gallery
+                        .read_matching(&path, &query, start, count, &passwords)
+                        .await
+                    {
+                        Some(results) => render_grid(
+                            config,
+                            results.start,
+                            results.total,
+                            results.photos.iter().map(|photo| photo.as_ref()),
+                        ),
+                        None => PageResult::NotFoundEmpty(()),
+                    };
+                }
+                Ok(None) => (),
+                Err(PhotoQueryError::InvalidFilters(message)) => {
+                    return PageResult::BadRequest(message)
+                }
+                Err(PhotoQueryError::SearchFailed) => return PageResult::Err(()),
+            }
+
             // Try to obtain a read pointer to some photos in this path in the gallery based on the request parameters
             match gallery.read(&path, start, count, uid, passwords).await {
                 // We have a valid (possibly empty) list of photos, render it as a template
-                Some(gallery_lock) => {
-                    let n_photos = gallery_lock.total;
-                    let start = gallery_lock.start;
-                    // Convert the sublist of photos to a Vec with individual index and URLs
-                    let photos = gallery_lock
-                        .iter()
-                        .enumerate()
-                        .map(|(index, photo)| {
-                            (
-                                start + index,
-                                photo,
-                                uri!(get_grid_item(&photo.uid)).to_string(),
-                                uri!(get_thumbnail(&photo.uid)),
-                                uri!(get_large(&photo.uid)),
-                                uri!(get_photo(&photo.uid)),
-                                uri!(download_photo(&photo.uid)),
-                            )
-                        })
-                        .collect::<Vec<_>>();
-
-                    PageResult::Page(Template::render(
-                        "grid",
-                        context! {
-                            config: &config.inner(),
-                            photos: &photos,
-                            n_photos: n_photos,
-                        },
-                    ))
-                }
+                Some(gallery_lock) => render_grid(
+                    config,
+                    gallery_lock.start,
+                    gallery_lock.total,
+                    gallery_lock.iter(),
+                ),
 
                 // The path is either not found or invalid for the current config, return an empty 404 response
                 None => PageResult::NotFoundEmpty(()),
@@ -226,7 +354,71 @@ async fn get_grid(
         }
 
         // A password is required and is either missing or invalid
-        Err(error) => PageResult::PasswordRequired(error.message()),
+        Err(error) => password_error(error),
+    }
+}
+
+/// Render the grid items for the given photos, the first of which is at index `start` in the
+/// list of `n_photos` photos being displayed
+fn render_grid<'a>(
+    config: &Config,
+    start: usize,
+    n_photos: usize,
+    photos: impl Iterator<Item = &'a Photo>,
+) -> PageResult {
+    // Convert the sublist of photos to a Vec with individual index and URLs
+    let photos = photos
+        .enumerate()
+        .map(|(index, photo)| {
+            (
+                start + index,
+                photo,
+                uri!(get_grid_item(&photo.uid)).to_string(),
+                uri!(get_thumbnail(&photo.uid)),
+                uri!(get_large(&photo.uid)),
+                uri!(get_photo(&photo.uid)),
+                uri!(download_photo(&photo.uid, None as Option<DownloadVariant>)),
+                url_download_raw(photo),
+                url_get_video(photo),
+                uri!(get_metadata(&photo.uid)),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    PageResult::Page(Template::render(
+        "grid",
+        context! {
+            config: config,
+            photos: &photos,
+            n_photos: n_photos,
+        },
+    ))
+}
+
+/// Route handler that returns the values of the metadata of the photos displayed in the given path,
+/// with the number of photos for each of them, to offer filters on the grid
+#[get("/<path..>?facets", rank = 9)]
+async fn get_facets(
+    path: PathBuf,
+    gallery: &State<Arc<Gallery>>,
+    cookies: &CookieJar<'_>,
+    password: OptionalPassword,
+    attempts: &State<PasswordAttempts>,
+    shared_paths: SharedPaths,
+) -> PageResult {
+    // Check if a password is required to access this path
+    match gallery
+        .check_password(&path, cookies, &password, attempts, &shared_paths)
+        .await
+    {
+        // Either no password is required or a valid one has been provided
+        Ok(passwords) => match gallery.facets(&path, &passwords).await {
+            Some(facets) => PageResult::Json(json::to_value(facets).unwrap_or_default()),
+            None => PageResult::NotFoundEmpty(()),
+        },
+
+        // A password is required and is either missing or invalid
+        Err(error) => password_error(error),
     }
 }
 
@@ -234,13 +426,18 @@ async fn get_grid(
 #[get("/<path..>?nav", rank = 11)]
 async fn get_nav(
     path: PathBuf,
-    gallery: &State<Gallery>,
+    gallery: &State<Arc<Gallery>>,
     config: &State<Config>,
     cookies: &CookieJar<'_>,
     password: OptionalPassword,
+    attempts: &State<PasswordAttempts>,
+    shared_paths: SharedPaths,
 ) -> PageResult {
     // Check if a password is required to access this path
-    match gallery.check_password(&path, cookies, &password).await {
+    match gallery
+        .check_password(&path, cookies, &password, attempts, &shared_paths)
+        .await
+    {
         // Either no password is required or a valid one has been provided
         Ok(passwords) => match NavData::from_path(&path, gallery, config, Some(passwords)).await {
             Ok(nav_data) => PageResult::Page(Template::render(
@@ -261,23 +458,146 @@ async fn get_nav(
         },
 
         // A password is required and is either missing or invalid
-        Err(error) => PageResult::PasswordRequired(error.message()),
+        Err(error) => password_error(error),
+    }
+}
+
+/// Route handler that renders a map of the photos in the given path that have GPS coordinates
+#[get("/<path..>?map", rank = 12)]
+async fn get_map(
+    path: PathBuf,
+    gallery: &State<Arc<Gallery>>,
+    config: &State<Config>,
+    cookies: &CookieJar<'_>,
+    attempts: &State<PasswordAttempts>,
+    shared_paths: SharedPaths,
+) -> PageResult {
+    if !config.SHOW_MAP {
+        return page_404(config);
+    }
+
+    // Check if a password is required to access this path
+    match gallery
+        .check_password(
+            &path,
+            cookies,
+            &OptionalPassword::none(),
+            attempts,
+            &shared_paths,
+        )
+        .await
+    {
+        // Either no password is required or a valid one has been provided
+        Ok(passwords) => {
+            // Read every photo available in this path, by batches
+            let url_gallery = uri!(get_gallery(&path)).to_string();
+            let mut markers: Vec<MapMarker> = Vec::new();
+            let mut start = 0;
+            loop {
+                let Some(gallery_lock) = gallery
+                    .read(&path, Some(start), None, None, passwords.clone())
+                    .await
+                else {
+                    // The path is either not found or invalid for the current config
+                    return page_404(config);
+                };
+                markers.extend(gallery_lock.iter().filter_map(|photo| {
+                    Some(MapMarker {
+                        uid: photo.uid.to_string(),
+                        latitude: photo.latitude?,
+                        longitude: photo.longitude?,
+                        title: photo.title.clone(),
+                        url_thumbnail: uri!(get_thumbnail(&photo.uid)).to_string(),
+                        url_photo: format!("{}#{}", url_gallery, photo.uid),
+                    })
+                }));
+                start += gallery_lock.max_count;
+                if gallery_lock.max_count == 0 || start >= gallery_lock.total {
+                    break;
+                }
+            }
+
+            let tiles_url = match config.MAP_TILES_DIR.as_str() {
+                "" => config.MAP_TILES_URL.as_str(),
+                _ => LOCAL_MAP_TILES_URL,
+            };
+            PageResult::Page(Template::render(
+                "map",
+                context! {
+                    config: config.inner(),
+                    markers: markers,
+                    tiles_url: tiles_url,
+                    folder_name: path.file_name().map(|name| name.to_string_lossy()),
+                    url_gallery: url_gallery,
+                },
+            ))
+        }
+
+        // A password is required and is either missing or invalid : the password prompt is displayed
+        // by the gallery
+        Err(_) => PageResult::Redirect(Redirect::to(uri!(get_gallery(&path)))),
+    }
+}
+
+/// Photo displayed on the map
+#[derive(Serialize)]
+struct MapMarker {
+    uid: String,
+    latitude: f64,
+    longitude: f64,
+    title: String,
+    url_thumbnail: String,
+    url_photo: String,
+}
+
+/// Route handler that returns a map tile from the folder defined by MAP_TILES_DIR
+#[get("/.map-tiles/<z>/<x>/<y>", rank = 1)]
+async fn get_map_tile(z: u32, x: u32, y: &str, config: &State<Config>) -> PageResult {
+    if config.MAP_TILES_DIR.is_empty() {
+        return PageResult::NotFoundEmpty(());
+    }
+
+    // The extension of the file is ignored if one is provided in the URL
+    let Ok(y) = y.split('.').next().unwrap_or_default().parse::<u32>() else {
+        return PageResult::NotFoundEmpty(());
+    };
+    for extension in LOCAL_MAP_TILES_EXTENSIONS {
+        let mut path = PathBuf::from(&config.MAP_TILES_DIR);
+        path.push(z.to_string());
+        path.push(x.to_string());
+        path.push(format!("{y}.{extension}"));
+        if let Ok(file) = NamedFile::open(&path).await {
+            return PageResult::Photo(file);
+        }
     }
+    PageResult::NotFoundEmpty(())
 }
 
 /// Route handler called asynchronously to render a single photo inside the grid
 #[get("/<uid>/grid-item", rank = 2)]
-async fn get_grid_item(uid: UID, gallery: &State<Gallery>, config: &State<Config>) -> PageResult {
-    match gallery.get_from_uid(&uid).await {
+async fn get_grid_item(
+    uid: UID,
+    cookies: &CookieJar<'_>,
+    shared_paths: SharedPaths,
+    gallery: &State<Arc<Gallery>>,
+    config: &State<Config>,
+) -> PageResult {
+    match gallery
+        .get_from_uid_checked(&uid, cookies, &shared_paths)
+        .await
+    {
         Some(photo) => PageResult::Page(Template::render(
             "grid-item",
             context! {
                 config: &config.inner(),
-                photo: photo,
+                photo: &photo,
                 url_get_thumbnail: uri!(get_thumbnail(&uid)),
                 url_get_large: uri!(get_large(&uid)),
                 url_get_photo: uri!(get_photo(&uid)),
-                url_download_photo:uri!(download_photo(&uid)),
+                url_download_photo: uri!(download_photo(&uid, None as Option<DownloadVariant>)),
+                url_download_raw: url_download_raw(&photo),
+                url_get_video: url_get_video(&photo),
+                url_get_metadata: uri!(get_metadata(&uid)),
             },
         )),
         None => page_404(config),
@@ -286,23 +606,60 @@ async fn get_grid_item(uid: UID, gallery: &State<Gallery>, config: &State<Config
 
 /// Route handler that returns the thumbnail version of the requested UID
 #[get("/<uid>/thumbnail", rank = 3)]
-async fn get_thumbnail(uid: UID, gallery: &State<Gallery>, config: &State<Config>) -> PageResult {
-    get_resized(&uid, photos::ResizedType::Thumbnail, gallery, config).await
+async fn get_thumbnail(
+    uid: UID,
+    cookies: &CookieJar<'_>,
+    shared_paths: SharedPaths,
+    gallery: &State<Arc<Gallery>>,
+    config: &State<Config>,
+) -> PageResult {
+    get_resized(
+        &uid,
+        photos::ResizedType::Thumbnail,
+        cookies,
+        &shared_paths,
+        gallery,
+        config,
+    )
+    .await
 }
 
 /// Route handler that returns the large resized version of the requested UID
 #[get("/<uid>/large", rank = 4)]
-async fn get_large(uid: UID, gallery: &State<Gallery>, config: &State<Config>) -> PageResult {
-    get_resized(&uid, photos::ResizedType::Large, gallery, config).await
+async fn get_large(
+    uid: UID,
+    cookies: &CookieJar<'_>,
+    shared_paths: SharedPaths,
+    gallery: &State<Arc<Gallery>>,
+    config: &State<Config>,
+) -> PageResult {
+    get_resized(
+        &uid,
+        photos::ResizedType::Large,
+        cookies,
+        &shared_paths,
+        gallery,
+        config,
+    )
+    .await
 }
 
 /// Returns the resized version of the requested UID for the given prefix
 async fn get_resized(
     uid: &UID,
     resized_type: photos::ResizedType,
+    cookies: &CookieJar<'_>,
+    shared_paths: &SharedPaths,
     gallery: &Gallery,
     config: &Config,
 ) -> PageResult {
+    if gallery
+        .get_from_uid_checked(uid, cookies, shared_paths)
+        .await
+        .is_none()
+    {
+        return page_404(config);
+    }
     match gallery
         .get_resized_from_uid(uid, resized_type, config)
         .await
@@ -324,6 +681,13 @@ async fn get_resized(
         }
         Ok(None) => page_404(config),
         Err(error) => {
+            // Poster frames can't be extracted from videos when ffmpeg is not available : fall back
+            // to a generic image
+            if let Some(MediaType::Video) = gallery.get_from_uid(uid).await.map(|p| p.media_type) {
+                if let Ok(file) = NamedFile::open(VIDEO_PLACEHOLDER_FILE).await {
+                    return PageResult::Photo(file);
+                }
+            }
             eprintln!("Error : unable to return a resized photo for UID #{uid} : {error}");
             PageResult::Err(())
         }
@@ -332,16 +696,37 @@ async fn get_resized(
 
 /// Route handler that returns the photo file for the requested UID
 #[get("/<uid>", rank = 6)]
-async fn get_photo(uid: UID, gallery: &State<Gallery>, config: &State<Config>) -> PageResult {
-    match gallery.get_from_uid(&uid).await {
+async fn get_photo(
+    uid: UID,
+    cookies: &CookieJar<'_>,
+    shared_paths: SharedPaths,
+    gallery: &State<Arc<Gallery>>,
+    config: &State<Config>,
+) -> PageResult {
+    match gallery
+        .get_from_uid_checked(&uid, cookies, &shared_paths)
+        .await
+    {
         Some(photo) => {
+            // Get a copy of the file without the metadata that must not be disclosed, if necessary
+            let path = match photo
+                .stripped_file(&photo.full_path, &config.CACHE_DIR)
+                .await
+            {
+                Ok(path) => path,
+                Err(error) => {
+                    eprintln!("Error : unable to remove metadata from UID #{uid} : {error}");
+                    return PageResult::Err(());
+                }
+            };
+
             // Try to open the file
-            match NamedFile::open(&photo.full_path).await {
+            match NamedFile::open(&path).await {
                 Ok(file) => PageResult::Photo(file),
                 Err(error) => {
                     eprintln!(
                         "Error : unable to read file \"{}\" : {}",
-                        photo.full_path.display(),
+                        path.display(),
                         error
                     );
                     PageResult::Err(())
@@ -352,18 +737,126 @@ async fn get_photo(uid: UID, gallery: &State<Gallery>, config: &State<Config>) -
     }
 }
 
-/// Route handler that returns the photo file for the requested UID as a download
-#[get("/<uid>/download", rank = 5)]
-async fn download_photo(uid: UID, gallery: &State<Gallery>, config: &State<Config>) -> PageResult {
-    match gallery.get_from_uid(&uid).await {
+/// Route handler that streams the video file for the requested UID. Range requests are supported,
+/// which allows browsers to seek inside the video without downloading it entirely.
+#[get("/<uid>/video", rank = 7)]
+async fn get_video(
+    uid: UID,
+    range: ByteRange,
+    cookies: &CookieJar<'_>,
+    shared_paths: SharedPaths,
+    gallery: &State<Arc<Gallery>>,
+    config: &State<Config>,
+) -> PageResult {
+    match gallery
+        .get_from_uid_checked(&uid, cookies, &shared_paths)
+        .await
+    {
+        Some(photo) if photo.media_type == MediaType::Video => {
+            // Try to open the file
+            match RangedFile::open(&photo.full_path, &range).await {
+                Ok(file) => PageResult::Video(file),
+                Err(error) => {
+                    eprintln!(
+                        "Error : unable to read file \"{}\" : {}",
+                        photo.full_path.display(),
+                        error
+                    );
+                    PageResult::Err(())
+                }
+            }
+        }
+        _ => page_404(config),
+    }
+}
+
+/// Route handler that returns every EXIF field and XMP property of the requested UID as JSON, except
+/// for the ones removed by PRIVACY_STRIP_METADATA
+#[get("/<uid>/metadata", rank = 8)]
+async fn get_metadata(
+    uid: UID,
+    cookies: &CookieJar<'_>,
+    shared_paths: SharedPaths,
+    gallery: &State<Arc<Gallery>>,
+    config: &State<Config>,
+    mut db_conn: Connection<DB>,
+) -> PageResult {
+    match gallery
+        .get_from_uid_checked(&uid, cookies, &shared_paths)
+        .await
+    {
+        Some(photo) if config.SHOW_METADATA => {
+            match db::get_all_metadata(&mut db_conn, &uid).await {
+                Ok(mut metadata) => {
+                    metadata
+                        .retain(|name, _| !privacy::is_stripped_field(name, &photo.strip_metadata));
+                    PageResult::Json(json::to_value(metadata).unwrap_or_default())
+                }
+                Err(error) => {
+                    eprintln!("Error : unable to read the metadata of UID #{uid} : {error}");
+                    PageResult::Err(())
+                }
+            }
+        }
+        _ => page_404(config),
+    }
+}
+
+/// Route handler that returns the photo file for the requested UID as a download. The `variant` parameter
+/// allows to choose between the original file, a JPEG version, or the camera RAW file of the photo.
+#[get("/<uid>/download?<variant>", rank = 5)]
+async fn download_photo(
+    uid: UID,
+    variant: Option<DownloadVariant>,
+    cookies: &CookieJar<'_>,
+    shared_paths: SharedPaths,
+    gallery: &State<Arc<Gallery>>,
+    config: &State<Config>,
+) -> PageResult {
+    match gallery
+        .get_from_uid_checked(&uid, cookies, &shared_paths)
+        .await
+    {
         Some(photo) => {
+            // Find the file corresponding to the requested variant
+            let variant = variant.unwrap_or_default();
+            let path = match variant {
+                DownloadVariant::Original => Some(photo.full_path.clone()),
+                DownloadVariant::Jpeg => match photo.jpeg_file(&config.CACHE_DIR).await {
+                    Ok(path) => path,
+                    Err(error) => {
+                        eprintln!(
+                            "Error : unable to extract a JPEG version of UID #{uid} : {error}"
+                        );
+                        return PageResult::Err(());
+                    }
+                },
+                DownloadVariant::Raw => photo.raw_file(),
+            };
+            let Some(mut path) = path else {
+                // This variant is not available for this photo
+                return page_404(config);
+            };
+
+            // The JPEG version is already free of the metadata that must not be disclosed, remove them
+            // from a copy of the other files if necessary
+            if variant != DownloadVariant::Jpeg {
+                path = match photo.stripped_file(&path, &config.CACHE_DIR).await {
+                    Ok(path) => path,
+                    Err(error) => {
+                        eprintln!("Error : unable to remove metadata from UID #{uid} : {error}");
+                        return PageResult::Err(());
+                    }
+                };
+            }
+
             // Try to open the file
-            match DownloadedNamedFile::open(&photo.full_path, &photo.uid, config).await {
+            match DownloadedNamedFile::open(&path, &photo.uid, config).await {
                 Ok(file) => PageResult::PhotoDownload(file),
                 Err(error) => {
                     eprintln!(
                         "Error : unable to read file \"{}\" : {}",
-                        photo.full_path.display(),
+                        path.display(),
                         error
                     );
                     PageResult::Err(())
@@ -374,16 +867,286 @@ async fn download_photo(uid: UID, gallery: &State<Gallery>, config: &State<Confi
     }
 }
 
-/// Route handler that reloads the photos from the filesystem and sync them with the database
-/// TODO : add a cooldown timer to prevent DOS attempts through this computation-heavy endpoint
+/// Return the URL to download the camera RAW file of the given photo, if it is paired with one,
+/// or an empty string otherwise
+fn url_download_raw(photo: &Photo) -> String {
+    match photo.raw_file() {
+        Some(raw_file) if raw_file != photo.full_path => {
+            uri!(download_photo(&photo.uid, Some(DownloadVariant::Raw))).to_string()
+        }
+        _ => String::new(),
+    }
+}
+
+/// Return the URL to stream the given photo if it is a video, or an empty string otherwise
+fn url_get_video(photo: &Photo) -> String {
+    match photo.media_type {
+        MediaType::Video => uri!(get_video(&photo.uid)).to_string(),
+        MediaType::Photo => String::new(),
+    }
+}
+
+/// Route handler that starts reloading the photos from the filesystem and syncing them with the
+/// database in the background, and returns the status of the reload. Requests received while a
+/// reload is already running are merged into it, and the ones received during the RELOAD_COOLDOWN
+/// that follows it are refused.
 #[get("/.reload", rank = 1)]
-async fn reload(
-    gallery: &State<Gallery>,
+async fn start_reload(
+    admin: Result<Admin, AdminError>,
+    gallery: &State<Arc<Gallery>>,
+    config: &State<Config>,
+    db: &DB,
+) -> PageResult {
+    if let Err(error) = admin {
+        return admin_error(error);
+    }
+    let start = gallery.start_reload(config, &db.0);
+    let status = json::to_value(gallery.reload_status()).unwrap_or_default();
+    match start {
+        ReloadStart::Started => PageResult::Accepted(status),
+        ReloadStart::AlreadyRunning => PageResult::Json(status),
+        ReloadStart::CoolingDown(retry_after) => {
+            PageResult::TooManyRequests(TooManyRequests::new(status, retry_after))
+        }
+    }
+}
+
+/// Route handler that returns the status of the current or last reload
+#[get("/.reload/status", rank = 1)]
+async fn get_reload_status(
+    admin: Result<Admin, AdminError>,
+    gallery: &State<Arc<Gallery>>,
+) -> PageResult {
+    if let Err(error) = admin {
+        return admin_error(error);
+    }
+    PageResult::Json(json::to_value(gallery.reload_status()).unwrap_or_default())
+}
+
+/// Route handler that streams the status of the current reload as server-sent events, every time it
+/// is updated, until the reload is finished
+#[get("/.reload/events", rank = 1)]
+async fn get_reload_events(
+    admin: Result<Admin, AdminError>,
+    gallery: &State<Arc<Gallery>>,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![], PageResult> {
+    if let Err(error) = admin {
+        return Err(admin_error(error));
+    }
+    let mut receiver = gallery.subscribe_reload_status();
+    Ok(EventStream! {
+        loop {
+            let status = receiver.borrow_and_update().clone();
+            yield Event::json(&status).event("status");
+            if !status.is_running() {
+                break;
+            }
+            select! {
+                result = receiver.changed() => if result.is_err() {
+                    break;
+                },
+                _ = &mut shutdown => break,
+            }
+        }
+    })
+}
+
+/// Return the response to a request refused by the `Admin` request guard
+fn admin_error(error: AdminError) -> PageResult {
+    match error {
+        AdminError::TooManyAttempts(retry_after) => {
+            PageResult::TooManyAttempts(TooManyRequests::new(error.to_string(), retry_after))
+        }
+        _ => PageResult::Unauthorized(error.to_string()),
+    }
+}
+
+/// Route handler that opens the share link with the given token, passed as the `share` parameter of the
+/// URL of the shared path, then redirects to this path
+#[get("/<_path..>?<share>", rank = 14)]
+async fn open_share_link(
+    _path: PathBuf,
+    share: &str,
+    share_links: &State<ShareLinks>,
+    cookies: &CookieJar<'_>,
+    db_conn: Connection<DB>,
+    config: &State<Config>,
+) -> PageResult {
+    redeem_share_link(share, share_links, cookies, db_conn, config).await
+}
+
+/// Route handler that opens the share link with the given token, then redirects to the shared path
+#[get("/.s/<token>", rank = 1)]
+async fn open_short_share_link(
+    token: &str,
+    share_links: &State<ShareLinks>,
+    cookies: &CookieJar<'_>,
+    db_conn: Connection<DB>,
     config: &State<Config>,
+) -> PageResult {
+    redeem_share_link(token, share_links, cookies, db_conn, config).await
+}
+
+/// Open the share link with the given token and redirect to the shared path, or return a 404 page if
+/// it is unknown, expired, revoked or has been used too many times
+async fn redeem_share_link(
+    token: &str,
+    share_links: &ShareLinks,
+    cookies: &CookieJar<'_>,
     mut db_conn: Connection<DB>,
-) -> Result<Redirect, ()> {
-    gallery.reload(config, &mut db_conn).await.map_err(|_| ())?;
-    Ok(Redirect::to(uri!(get_gallery(PathBuf::new()))))
+    config: &Config,
+) -> PageResult {
+    match share_links.redeem(&mut db_conn, token, cookies).await {
+        Ok(Some(path)) => {
+            PageResult::Redirect(Redirect::to(uri!(get_gallery(PathBuf::from(path)))))
+        }
+        Ok(None) => page_404(config),
+        Err(error) => {
+            eprintln!("Error : unable to open share link : {error}");
+            PageResult::Err(())
+        }
+    }
+}
+
+/// Form sent to create a new share link
+#[derive(FromForm)]
+struct NewShareLink {
+    /// Path of the gallery or name of the collection to share
+    path: String,
+    /// Number of days after which the link expires
+    days: Option<u32>,
+    /// Maximum number of times the link can be opened
+    max_uses: Option<u32>,
+}
+
+/// Route handler that creates a new share link, and returns it as JSON
+#[post("/.admin/shares", data = "<form>", rank = 1)]
+async fn create_share_link(
+    admin: Result<Admin, AdminError>,
+    form: Form<NewShareLink>,
+    gallery: &State<Arc<Gallery>>,
+    share_links: &State<ShareLinks>,
+    mut db_conn: Connection<DB>,
+) -> PageResult {
+    if let Err(error) = admin {
+        return admin_error(error);
+    }
+    let path = form.path.trim_matches('/');
+    if !gallery.path_exists(Path::new(path)).await {
+        return PageResult::NotFoundEmpty(());
+    }
+    let days = form.days.unwrap_or(share::DEFAULT_EXPIRY_DAYS);
+    if !(1..=share::MAX_EXPIRY_DAYS).contains(&days) {
+        return PageResult::BadRequest(format!(
+            "days must be between 1 and {}",
+            share::MAX_EXPIRY_DAYS
+        ));
+    }
+    if form.max_uses == Some(0) {
+        return PageResult::BadRequest("max_uses must be at least 1".to_string());
+    }
+    match share_links
+        .create(&mut db_conn, path, days, form.max_uses)
+        .await
+    {
+        Ok(share_link) => PageResult::Created(share_link_json(&share_link)),
+        Err(error) => {
+            eprintln!("Error : unable to create share link : {error}");
+            PageResult::Err(())
+        }
+    }
+}
+
+/// Route handler that returns every share link as JSON
+#[get("/.admin/shares", rank = 1)]
+async fn get_share_links(
+    admin: Result<Admin, AdminError>,
+    share_links: &State<ShareLinks>,
+) -> PageResult {
+    if let Err(error) = admin {
+        return admin_error(error);
+    }
+    PageResult::Json(Value::Array(
+        share_links
+            .list()
+            .await
+            .iter()
+            .map(share_link_json)
+            .collect(),
+    ))
+}
+
+/// Route handler that revokes the share link with the given token, and returns it as JSON
+#[delete("/.admin/shares/<token>", rank = 1)]
+async fn revoke_share_link(
+    admin: Result<Admin, AdminError>,
+    token: &str,
+    share_links: &State<ShareLinks>,
+    mut db_conn: Connection<DB>,
+) -> PageResult {
+    if let Err(error) = admin {
+        return admin_error(error);
+    }
+    match share_links.revoke(&mut db_conn, token).await {
+        Ok(Some(share_link)) => PageResult::Json(share_link_json(&share_link)),
+        Ok(None) => PageResult::NotFoundEmpty(()),
+        Err(error) => {
+            eprintln!("Error : unable to revoke share link : {error}");
+            PageResult::Err(())
+        }
+    }
+}
+
+/// Serialize a share link into JSON, along with its short URL
+fn share_link_json(share_link: &share::ShareLink) -> Value {
+    let mut value = json::to_value(share_link).unwrap_or_default();
+    if let Value::Object(fields) = &mut value {
+        fields.insert(
+            "url".to_string(),
+            Value::String(uri!(open_short_share_link(&share_link.token)).to_string()),
+        );
+    }
+    value
+}
+
+/// Form sent to open an admin session
+#[derive(FromForm)]
+struct AdminLogin {
+    token: String,
+}
+
+/// Route handler that opens an admin session if the provided token matches the ADMIN_TOKEN, so that
+/// the admin routes can then be accessed from a browser
+#[post("/.admin/login", data = "<form>", rank = 1)]
+async fn admin_login(
+    form: Form<AdminLogin>,
+    cookies: &CookieJar<'_>,
+    config: &State<Config>,
+    attempts: &State<PasswordAttempts>,
+    client_ip: Option<IpAddr>,
+) -> PageResult {
+    match admin::login(&form.token, cookies, config, attempts, client_ip).await {
+        Ok(()) => PageResult::Redirect(Redirect::to(uri!(get_gallery(PathBuf::new())))),
+        Err(error) => admin_error(error),
+    }
+}
+
+/// Route handler that closes the admin session, if any
+#[post("/.admin/logout", rank = 1)]
+async fn admin_logout(cookies: &CookieJar<'_>) -> PageResult {
+    admin::logout(cookies);
+    PageResult::Redirect(Redirect::to(uri!(get_gallery(PathBuf::new()))))
+}
+
+/// Return the response to a request refused by `check_password`
+fn password_error(error: PasswordError) -> PageResult {
+    match error {
+        PasswordError::TooManyAttempts(_, retry_after) => {
+            PageResult::TooManyAttempts(TooManyRequests::new(error.message(), retry_after))
+        }
+        _ => PageResult::PasswordRequired(error.message()),
+    }
 }
 
 /// Render the 404 page
@@ -402,13 +1165,26 @@ fn page_404(config: &Config) -> PageResult {
 pub enum PageResult {
     Page(Template),
     Photo(NamedFile),
+    Video(RangedFile),
     PhotoDownload(DownloadedNamedFile),
+    Json(Value),
+    Redirect(Redirect),
+    #[response(status = 201)]
+    Created(Value),
+    #[response(status = 202)]
+    Accepted(Value),
+    #[response(status = 400)]
+    BadRequest(String),
     #[response(status = 404)]
     NotFound(Template),
     #[response(status = 404)]
     NotFoundEmpty(()),
     #[response(status = 401)]
     PasswordRequired(String),
+    #[response(status = 401)]
+    Unauthorized(String),
+    TooManyRequests(TooManyRequests<Value>),
+    TooManyAttempts(TooManyRequests<String>),
     #[response(status = 500)]
     Err(()),
 }
@@ -425,19 +1201,43 @@ impl DownloadedNamedFile {
     where
         P: AsRef<Path>,
     {
+        // Keep the extension of the original file
+        let extension = path
+            .as_ref()
+            .extension()
+            .map(|ext| format!(".{}", ext.to_string_lossy().to_lowercase()))
+            .unwrap_or_default();
         NamedFile::open(path).await.map(|file| Self {
             inner: file,
             content_disposition: Header::new(
                 rocket::http::hyper::header::CONTENT_DISPOSITION.as_str(),
                 format!(
-                    "attachment; filename=\"{}{}.jpg\"",
-                    &config.DOWNLOAD_PREFIX, uid
+                    "attachment; filename=\"{}{}{}\"",
+                    &config.DOWNLOAD_PREFIX, uid, extension
                 ),
             ),
         })
     }
 }
 
+/// A "429 Too Many Requests" response, with a Retry-After header telling the client how many seconds
+/// to wait before trying again
+#[derive(Responder)]
+#[response(status = 429)]
+pub struct TooManyRequests<R> {
+    inner: R,
+    retry_after: Header<'static>,
+}
+
+impl<R> TooManyRequests<R> {
+    pub fn new(inner: R, retry_after: u64) -> Self {
+        Self {
+            inner,
+            retry_after: Header::new("Retry-After", retry_after.to_string()),
+        }
+    }
+}
+
 /// General type used to standardize errors across the crate
 #[derive(Debug)]
 pub enum Error {
@@ -446,10 +1246,14 @@ pub enum Error {
     UIDParserError(String),
     FileError(io::Error, PathBuf),
     TomlParserError(toml::de::Error),
+    InvalidConfigError(String),
     DatabaseError(sqlx::Error),
     ImageError(image::ImageError, PathBuf),
     WebpEncoderError(String, PathBuf),
     EXIFParserError(exif::Error, PathBuf),
+    RawPreviewError(String, PathBuf),
+    MetadataParserError(String, PathBuf),
+    VideoToolError(String, PathBuf),
     OtherError(String),
 }
 
@@ -465,6 +1269,7 @@ impl Display for Error {
                 write!(f, "file error for \"{}\" : {}", path.display(), error)
             }
             Error::TomlParserError(error) => write!(f, "TOML parser error : {error}"),
+            Error::InvalidConfigError(error) => write!(f, "invalid config : {error}"),
             Error::DatabaseError(error) => write!(f, "database error : {error}"),
             Error::ImageError(error, path) => {
                 write!(f, "image error for \"{}\" : {}", path.display(), error)
@@ -481,6 +1286,21 @@ impl Display for Error {
                 path.display(),
                 error
             ),
+            Error::RawPreviewError(error, path) => write!(
+                f,
+                "RAW preview error for \"{}\" : {}",
+                path.display(),
+                error
+            ),
+            Error::MetadataParserError(error, path) => write!(
+                f,
+                "metadata parser error for \"{}\" : {}",
+                path.display(),
+                error
+            ),
+            Error::VideoToolError(error, path) => {
+                write!(f, "video tool error for \"{}\" : {}", path.display(), error)
+            }
             Error::OtherError(error) => write!(f, "other error : {error}"),
         }
     }
@@ -17,6 +17,7 @@ pub struct NavData {
     current_open: String,
     path_current: PathBuf,
     url_path_current: String,
+    url_path_open: String,
     parent: String,
     path_parent: PathBuf,
     url_path_parent: String,
@@ -221,6 +222,7 @@ impl NavData {
             current_open,
             path_current: path_current.clone(),
             url_path_current: uri!(crate::get_gallery(PathBuf::from(&path_current))).to_string(),
+            url_path_open: uri!(crate::get_gallery(path)).to_string(),
             parent,
             path_parent: path_parent.clone(),
             url_path_parent: uri!(crate::get_gallery(PathBuf::from(&path_parent))).to_string(),
@@ -1,34 +1,61 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
 use base64::{prelude::BASE64_STANDARD, Engine};
+use rand::RngCore;
 use rocket::{
-    http::Status,
+    http::{Cookie, CookieJar, Status},
     request::{self, FromRequest},
+    tokio::task,
 };
-use std::{collections::HashMap, fmt::Display};
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, fmt::Display, net::IpAddr};
+use subtle::ConstantTimeEq;
 
+/// Passwords required by some paths, indexed by path
 pub type Passwords = HashMap<String, String>;
 
+/// Prefixes of the bcrypt hashes, depending on the version of the algorithm
+const BCRYPT_PREFIXES: [&str; 4] = ["$2a$", "$2b$", "$2x$", "$2y$"];
+
 /// Custom type used as a request guard which represents a password optionally
-/// sent by the client with an Authorization header. This request guard never
-/// forwards : it succeeds even if no header is provided (in which case it simply
-/// stores None), and fails if the provided header is not a valid base64-encoded
-/// UTF8 string.
-pub struct OptionalPassword(Option<String>);
+/// sent by the client with an Authorization header, along with the IP address of
+/// the client. This request guard never forwards : it succeeds even if no header
+/// is provided (in which case it simply stores None), and fails if the provided
+/// header is not a valid base64-encoded UTF8 string.
+pub struct OptionalPassword {
+    password: Option<String>,
+    client_ip: Option<IpAddr>,
+}
 
 impl OptionalPassword {
     pub fn none() -> Self {
-        Self(None)
+        Self {
+            password: None,
+            client_ip: None,
+        }
     }
 
     /// Return a new OptionalPassword from the given base64-encoded string
-    fn from_base64(encoded: String) -> Result<Self, PasswordDecodeError> {
+    fn from_base64(
+        encoded: String,
+        client_ip: Option<IpAddr>,
+    ) -> Result<Self, PasswordDecodeError> {
         let decoded_buffer = BASE64_STANDARD.decode(encoded)?;
         let password = String::from_utf8(decoded_buffer)?;
-        Ok(Self(Some(password)))
+        Ok(Self {
+            password: Some(password),
+            client_ip,
+        })
     }
 
     /// Return the internal optional string
     pub fn as_string(&self) -> &Option<String> {
-        &self.0
+        &self.password
+    }
+
+    /// Return the IP address of the client that sent the password, if known
+    pub fn client_ip(&self) -> Option<IpAddr> {
+        self.client_ip
     }
 }
 
@@ -44,14 +71,14 @@ impl<'r> FromRequest<'r> for OptionalPassword {
             .get_one(rocket::http::hyper::header::AUTHORIZATION.as_str())
             .map(|v| v.to_string())
         {
-            Some(header) => match OptionalPassword::from_base64(header) {
+            Some(header) => match OptionalPassword::from_base64(header, request.client_ip()) {
                 Ok(password) => request::Outcome::Success(password),
                 Err(error) => {
                     eprintln!("Warning : a client sent an invalid Authorization header : {error}");
                     request::Outcome::Error((Status::BadGateway, error))
                 }
             },
-            None => request::Outcome::Success(OptionalPassword(None)),
+            None => request::Outcome::Success(OptionalPassword::none()),
         }
     }
 }
@@ -97,6 +124,8 @@ impl Display for PasswordDecodeError {
 pub enum PasswordError {
     Required(String),
     Invalid(String),
+    /// Too many invalid passwords have been tried, the client has to wait this number of seconds
+    TooManyAttempts(String, u64),
 }
 
 impl PasswordError {
@@ -116,6 +145,13 @@ impl PasswordError {
                     format!("Invalid password for \"{path}\"")
                 }
             }
+            PasswordError::TooManyAttempts(path, retry_after) => {
+                if path.is_empty() {
+                    format!("Too many invalid passwords, try again in {retry_after} seconds")
+                } else {
+                    format!("Too many invalid passwords for \"{path}\", try again in {retry_after} seconds")
+                }
+            }
         }
     }
 }
@@ -123,3 +159,112 @@ impl PasswordError {
 pub fn cookie_name(path: &str) -> String {
     format!("niobium_pw_{path}")
 }
+
+/// Check if the given password matches the required one, as found in a config file, either in plain
+/// text or as an argon2 or bcrypt hash. The comparison is made in constant time. Hashes are slow to
+/// check on purpose, so they are checked on a blocking thread instead of stalling the async workers.
+pub async fn verify(password: &str, required_password: &str) -> bool {
+    let is_hashed = required_password.starts_with("$argon2")
+        || BCRYPT_PREFIXES
+            .iter()
+            .any(|prefix| required_password.starts_with(prefix));
+    if !is_hashed {
+        return verify_blocking(password, required_password);
+    }
+    let password = password.to_string();
+    let required_password = required_password.to_string();
+    task::spawn_blocking(move || verify_blocking(&password, &required_password))
+        .await
+        .unwrap_or_else(|error| {
+            eprintln!("Error : unable to check a password : {error}");
+            false
+        })
+}
+
+/// Blocking implementation of `verify()`
+fn verify_blocking(password: &str, required_password: &str) -> bool {
+    if required_password.starts_with("$argon2") {
+        match PasswordHash::new(required_password) {
+            Ok(hash) => Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok(),
+            Err(error) => {
+                eprintln!("Warning : invalid argon2 password hash in a config file : {error}");
+                false
+            }
+        }
+    } else if BCRYPT_PREFIXES
+        .iter()
+        .any(|prefix| required_password.starts_with(prefix))
+    {
+        bcrypt::verify(password, required_password).unwrap_or_else(|error| {
+            eprintln!("Warning : invalid bcrypt password hash in a config file : {error}");
+            false
+        })
+    } else {
+        password
+            .as_bytes()
+            .ct_eq(required_password.as_bytes())
+            .into()
+    }
+}
+
+/// Algorithms available to hash passwords
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Argon2id,
+    Bcrypt,
+}
+
+/// Hash the given password with the given algorithm, using a random salt and the default parameters,
+/// into a string that can be used in place of the password in a config file
+pub fn hash(password: &str, algorithm: HashAlgorithm) -> Result<String, String> {
+    match algorithm {
+        HashAlgorithm::Argon2id => {
+            let mut salt = [0u8; 16];
+            rand::thread_rng().fill_bytes(&mut salt);
+            let salt = SaltString::encode_b64(&salt).map_err(|e| e.to_string())?;
+            Argon2::default()
+                .hash_password(password.as_bytes(), &salt)
+                .map(|hash| hash.to_string())
+                .map_err(|e| e.to_string())
+        }
+        HashAlgorithm::Bcrypt => {
+            bcrypt::hash(password, bcrypt::DEFAULT_COST).map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// Return the grant stored in the session cookies to give access to the given path : a digest of the
+/// path and of its required password, so that the password itself is never stored in the cookies
+/// and the grant is revoked as soon as the password is changed. Private cookies are encrypted and
+/// signed with the app's secret key, so grants can't be forged by the client.
+pub fn grant(path: &str, required_password: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(path.as_bytes());
+    hasher.update([0]);
+    hasher.update(required_password.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Grant access to the given path in the session cookies
+pub fn add_to_session(cookies: &CookieJar<'_>, path: &str, required_password: &str) {
+    cookies.add_private(Cookie::new(
+        cookie_name(path),
+        grant(path, required_password),
+    ));
+}
+
+/// Check if access to the given path, protected by the given password, has been granted in the
+/// session cookies
+pub fn is_in_session(cookies: &CookieJar<'_>, path: &str, required_password: &str) -> bool {
+    cookies
+        .get_private(&cookie_name(path))
+        .is_some_and(|cookie| {
+            cookie
+                .value()
+                .as_bytes()
+                .ct_eq(grant(path, required_password).as_bytes())
+                .into()
+        })
+}
@@ -1,27 +1,36 @@
 use crate::collection::Collections;
 use crate::config::Config;
+use crate::filter::{self, Facets, PhotoFilter};
 use crate::password::{self, OptionalPassword, PasswordError, Passwords};
+use crate::privacy::{self, MetadataGroup};
+use crate::rate_limit::PasswordAttempts;
+use crate::reload::{ReloadPhase, ReloadProgress, ReloadStart, ReloadStatus};
+use crate::share::SharedPaths;
 use crate::uid::UID;
-use crate::{db, Error};
+use crate::xmp::{self, DescriptiveMetadata};
+use crate::{date_taken, db, file_hash, iptc, raw, video, Error};
 use base64::prelude::BASE64_STANDARD;
 use base64::Engine;
 use image::codecs::jpeg::JpegEncoder;
 use image::imageops::FilterType;
-use md5::{Digest, Md5};
+use image::metadata::Orientation;
+use image::{DynamicImage, Rgb, RgbImage};
+use rocket::form;
 use rocket::futures::StreamExt;
-use rocket::http::{Cookie, CookieJar};
+use rocket::http::uri::fmt::{Formatter, Query, UriDisplay};
+use rocket::http::{impl_from_uri_param_identity, CookieJar};
 use rocket::serde::Serialize;
 use rocket::tokio::fs;
 use rocket::tokio::fs::create_dir_all;
-use rocket::tokio::sync::{RwLock, RwLockReadGuard};
-use rocket::tokio::task::JoinSet;
+use rocket::tokio::sync::{watch, Mutex, RwLock, RwLockReadGuard};
+use rocket::tokio::task::{self, JoinSet};
 use rocket::tokio::time::Instant;
 use rocket::{fairing, tokio, Rocket};
-use rocket_db_pools::sqlx::SqliteConnection;
+use rocket_db_pools::sqlx::{self, SqliteConnection};
 use rocket_db_pools::Database;
 use serde::Deserialize;
-use std::cmp::min;
-use std::collections::HashMap;
+use std::cmp::{min, Ordering};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::future::Future;
 use std::io::{self, Write};
 use std::ops::{Deref, DerefMut};
@@ -30,16 +39,57 @@ use std::pin::Pin;
 use std::sync::Arc;
 use tokio_stream::wrappers::ReadDirStream;
 use toml::value::Table;
+use utoipa::ToSchema;
+
+/// Version of the set of fields read from the EXIF data of photos by `Photo::parse_exif()`. It must be
+/// incremented every time a new field is read, so that photos indexed by a previous version have their
+/// EXIF data read again at startup.
+const EXIF_VERSION: u32 = 3;
+
+/// Values of EXIF fields with an undefined type that are larger than this (in bytes) are binary data
+/// that is not worth storing in the full dump of the metadata of photos
+const MAX_UNDEFINED_EXIF_VALUE_SIZE: usize = 256;
+
+/// EXIF fields that describe the layout of the file rather than the photo, which are not stored in
+/// the full dump of the metadata of photos
+const EXIF_LAYOUT_TAGS: [exif::Tag; 9] = [
+    exif::Tag::StripOffsets,
+    exif::Tag::StripByteCounts,
+    exif::Tag::RowsPerStrip,
+    exif::Tag::JPEGInterchangeFormat,
+    exif::Tag::JPEGInterchangeFormatLength,
+    exif::Tag::ExifIFDPointer,
+    exif::Tag::GPSInfoIFDPointer,
+    exif::Tag::InteropIFDPointer,
+    exif::Tag::MakerNote,
+];
 
 /// Main struct representing a photo and its metadata
-#[derive(Default, Serialize, Clone, Debug)]
+#[derive(Default, Serialize, ToSchema, Clone, Debug)]
 pub struct Photo {
     pub id: u32,
     pub filename: String,
     pub path: PathBuf,
+    /// The location of the file on the server is never sent to clients
+    #[serde(skip_serializing)]
     pub full_path: PathBuf,
     pub uid: UID,
-    pub md5: String,
+    /// Hash of the content of the file, prefixed by the name of the algorithm used to compute it
+    /// (except for MD5). This is empty if it hasn't been computed.
+    /// This field and the other ones that track the state of the file on the server are internal,
+    /// and never sent to clients.
+    #[serde(skip_serializing)]
+    pub hash: String,
+    /// Size of the file, which is used with its modification time as a quick fingerprint
+    #[serde(skip_serializing)]
+    pub file_size: Option<i64>,
+    /// Last modification time of the file, as a Unix timestamp
+    #[serde(skip_serializing)]
+    pub file_mtime: Option<i64>,
+    /// Last modification time of the XMP sidecar file when the descriptive metadata were read, as
+    /// a Unix timestamp, or None if the photo has no sidecar
+    #[serde(skip_serializing)]
+    pub sidecar_mtime: Option<i64>,
     pub sort_order: u32,
     pub hidden: bool,
     pub metadata_parsed: bool,
@@ -49,47 +99,112 @@ pub struct Photo {
     pub title: String,
     pub place: String,
     pub date_taken: String,
+    /// Unix timestamp of the moment the photo was taken, read from its EXIF data, or from its
+    /// filename or modification time if it has none. When the time zone is unknown, the local
+    /// time is stored as if it was UTC.
+    pub taken_at: Option<i64>,
+    /// Offset of the time zone in which the photo was taken from UTC, in minutes, if known
+    pub taken_at_offset: Option<i32>,
     pub camera_model: String,
     pub lens_model: String,
     pub focal_length: String,
     pub aperture: String,
     pub exposure_time: String,
     pub sensitivity: String,
+    pub alternate_files: Vec<String>,
+    pub media_type: MediaType,
+    pub duration: f64,
+    pub description: String,
+    pub rating: i32,
+    pub keywords: Vec<String>,
+    pub orientation: u32,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub altitude: Option<f64>,
+    #[serde(skip_serializing)]
+    pub exif_version: u32,
+    /// Groups of metadata removed from the files served for this photo
+    #[serde(skip_serializing)]
+    pub strip_metadata: Vec<MetadataGroup>,
+    /// Every EXIF field and XMP property read from the file, indexed by their name. This is only
+    /// filled when the metadata of the photo are parsed, in order to be saved in the database.
+    #[serde(skip)]
+    pub all_metadata: BTreeMap<String, String>,
 }
 
 impl Photo {
     /// Try to open the photo file to extract its metadata.
     /// If this has already been done according to the `metadata_parsed` field, this is a no-op.
     #[allow(clippy::identity_op)]
-    pub async fn parse_metadata(&mut self, read_exif: bool) -> Result<(), Error> {
+    pub async fn parse_metadata(
+        &mut self,
+        read_exif: bool,
+        settings: &DecoderSettings,
+    ) -> Result<(), Error> {
         if self.metadata_parsed {
             // Metadata already parsed, nothing to do
             return Ok(());
         }
 
-        // Load the image
-        println!("Parsing metadata for photo {}...", self.full_path.display());
-        let img = image::ImageReader::open(&self.full_path)
-            .map_err(|e| Error::FileError(e, self.full_path.clone()))?
-            .decode()
-            .map_err(|e| Error::ImageError(e, self.full_path.clone()))?;
+        // Photos without an EXIF orientation tag are displayed as they are stored
+        self.orientation = 1;
+
+        // For videos, read the dimensions and duration of the clip
+        if self.media_type == MediaType::Video {
+            println!("Parsing metadata for video {}...", self.full_path.display());
+            match video::probe(&settings.ffprobe_path, &self.full_path).await {
+                Ok(info) => {
+                    self.width = info.width;
+                    self.height = info.height;
+                    self.duration = info.duration;
+                }
+                Err(error) => eprintln!("Warning : unable to read video metadata : {error}"),
+            }
+        } else {
+            println!("Parsing metadata for photo {}...", self.full_path.display());
+        }
 
-        // Image dimensions
-        self.width = img.width();
-        self.height = img.height();
+        // Read the title, description, place and keywords
+        self.parse_descriptive_metadata().await;
 
-        // Compute the photo's average color
-        let img_rgb8 = match img {
-            image::DynamicImage::ImageRgb8(pixels) => pixels,
-            _ => {
-                eprintln!(
-                    "Warning : converting \"{}\" from {:?} to RGB8, this is not efficient",
-                    self.full_path.display(),
-                    img.color()
-                );
-                img.into_rgb8()
+        // Parse EXIF metadata, including the orientation which must be known before decoding the image
+        if read_exif && self.media_type == MediaType::Photo {
+            if let Err(Error::EXIFParserError(error, _)) = self.parse_exif() {
+                match error {
+                    exif::Error::NotFound(_) => (), // Ignore
+                    _ => eprintln!(
+                        "Warning : unable to parse EXIF data from \"{}\" : {}",
+                        &self.full_path.display(),
+                        error
+                    ),
+                }
+            }
+        }
+        self.exif_version = EXIF_VERSION;
+
+        // Photos without an EXIF date (and videos) are dated from their filename or file
+        self.parse_date_taken_fallback();
+
+        // Load the image, rotated according to its orientation
+        let img = match self.decode_image(settings).await {
+            Ok(img) => img,
+            Err(error) if self.media_type == MediaType::Video => {
+                // A video without a poster frame can still be played : keep the metadata found so far
+                eprintln!("Warning : unable to extract a poster frame : {error}");
+                self.metadata_parsed = true;
+                return Ok(());
             }
+            Err(error) => return Err(error),
         };
+
+        // Image dimensions
+        if self.width == 0 || self.height == 0 {
+            self.width = img.width();
+            self.height = img.height();
+        }
+
+        // Compute the photo's average color
+        let img_rgb8 = flatten_image(img, settings.background);
         let pixels = img_rgb8.as_flat_samples().samples;
         let mut average_r: u64 = 0;
         let mut average_g: u64 = 0;
@@ -112,20 +227,6 @@ impl Photo {
             average_b / darken_factor
         );
 
-        // Parse EXIF metadata
-        if read_exif {
-            if let Err(Error::EXIFParserError(error, _)) = self.parse_exif() {
-                match error {
-                    exif::Error::NotFound(_) => (), // Ignore
-                    _ => eprintln!(
-                        "Warning : unable to parse EXIF data from \"{}\" : {}",
-                        &self.full_path.display(),
-                        error
-                    ),
-                }
-            }
-        }
-
         self.metadata_parsed = true;
         Ok(())
     }
@@ -145,21 +246,22 @@ impl Photo {
         }
 
         // Read the EXIF data from the file
-        let exif_file = std::fs::File::open(&self.full_path)
-            .map_err(|e| Error::FileError(e, self.full_path.clone()))?;
-        let mut buf_reader = std::io::BufReader::new(&exif_file);
-        let exif_reader = exif::Reader::new();
-        let exif = exif_reader
-            .read_from_container(&mut buf_reader)
-            .map_err(|e| Error::EXIFParserError(e, self.full_path.clone()))?;
+        let exif = self.read_exif()?;
 
         // Add every relevant available fields to the photo object
+        if let Some(orientation) = read_orientation(&exif) {
+            self.orientation = orientation;
+        }
         if let Some(field) = exif.get_field(exif::Tag::DateTimeDigitized, exif::In::PRIMARY) {
             self.date_taken = remove_quotes(format!("{}", field.display_value()));
         }
         if let Some(field) = exif.get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY) {
             self.date_taken = remove_quotes(format!("{}", field.display_value()));
         }
+        if let Some(date_taken) = date_taken::from_exif(&exif) {
+            self.taken_at = Some(date_taken.timestamp);
+            self.taken_at_offset = date_taken.offset;
+        }
         if let Some(field) = exif.get_field(exif::Tag::Model, exif::In::PRIMARY) {
             self.camera_model = remove_quotes(format!("{}", field.display_value()));
         }
@@ -178,7 +280,147 @@ impl Photo {
         if let Some(field) = exif.get_field(exif::Tag::PhotographicSensitivity, exif::In::PRIMARY) {
             self.sensitivity = remove_quotes(format!("{}", field.display_value()));
         }
+        if let Some((latitude, longitude)) = read_gps_position(&exif) {
+            self.latitude = Some(latitude);
+            self.longitude = Some(longitude);
+            self.altitude = read_gps_altitude(&exif);
+        }
+
+        // Keep a copy of every other known field of the main image, except for binary data and the
+        // maker notes which are proprietary
+        for field in exif.fields() {
+            if field.ifd_num != exif::In::PRIMARY
+                || EXIF_LAYOUT_TAGS.contains(&field.tag)
+                || field.tag.description().is_none()
+            {
+                continue;
+            }
+            if let exif::Value::Undefined(data, _) = &field.value {
+                if data.len() > MAX_UNDEFINED_EXIF_VALUE_SIZE {
+                    continue;
+                }
+            }
+            let value = remove_quotes(field.display_value().with_unit(&exif).to_string());
+            if !value.trim().is_empty() {
+                self.all_metadata
+                    .entry(field.tag.to_string())
+                    .or_insert(value);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// If the date the photo was taken hasn't been found in its EXIF data, try to read it from its
+    /// filename, and otherwise use the modification time of its file
+    fn parse_date_taken_fallback(&mut self) {
+        if self.taken_at.is_some() {
+            return;
+        }
+        if let Some(date_taken) = date_taken::from_filename(&self.filename)
+            .or_else(|| date_taken::from_mtime(&self.full_path))
+        {
+            self.taken_at = Some(date_taken.timestamp);
+            self.taken_at_offset = date_taken.offset;
+        }
+    }
+
+    /// Read the descriptive metadata of this photo from its XMP sidecar file, if any, and from the XMP
+    /// packet and IPTC block embedded in the file. When a field is found in several of these sources,
+    /// the first one in this order takes precedence.
+    async fn parse_descriptive_metadata(&mut self) {
+        let mut metadata = DescriptiveMetadata::default();
+        self.sidecar_mtime = None;
+        if let Some((sidecar, mtime)) = xmp::find_sidecar(&self.full_path).await {
+            self.sidecar_mtime = Some(mtime);
+            match xmp::read_sidecar(&sidecar).await {
+                Ok(sidecar_metadata) => metadata.merge(sidecar_metadata),
+                Err(error) => eprintln!("Warning : unable to read XMP sidecar : {error}"),
+            }
+        }
 
+        // The files of videos are usually too large to be searched for an embedded packet
+        if self.media_type == MediaType::Photo {
+            match xmp::read_header(&self.full_path) {
+                Ok(data) => {
+                    match xmp::read_embedded(&data, &self.full_path) {
+                        Ok(Some(embedded_metadata)) => metadata.merge(embedded_metadata),
+                        Ok(None) => (),
+                        Err(error) => eprintln!("Warning : unable to read embedded XMP : {error}"),
+                    }
+                    if let Some(iptc_metadata) = iptc::parse_jpeg(&data) {
+                        metadata.merge(iptc_metadata);
+                    }
+                }
+                Err(error) => eprintln!("Warning : unable to read metadata : {error}"),
+            }
+        }
+
+        self.apply_descriptive_metadata(metadata);
+    }
+
+    /// Read the descriptive metadata of this photo again if its XMP sidecar file has been added,
+    /// edited or removed since the photo was indexed, and return true if this is the case
+    pub async fn reload_sidecar(&mut self) -> bool {
+        let mtime = xmp::find_sidecar(&self.full_path)
+            .await
+            .map(|(_, mtime)| mtime);
+        if mtime == self.sidecar_mtime {
+            return false;
+        }
+        self.parse_descriptive_metadata().await;
+        true
+    }
+
+    /// Replace the descriptive fields of this photo with the values found in `metadata`
+    fn apply_descriptive_metadata(&mut self, metadata: DescriptiveMetadata) {
+        self.place = metadata.place();
+        self.title = metadata.title;
+        self.description = metadata.description;
+        self.rating = metadata.rating.unwrap_or(0);
+        self.keywords = metadata.keywords;
+        for (name, value) in metadata.properties {
+            self.all_metadata.entry(name).or_insert(value);
+        }
+    }
+
+    /// Open the photo file and read its EXIF data
+    fn read_exif(&self) -> Result<exif::Exif, Error> {
+        let exif_file = std::fs::File::open(&self.full_path)
+            .map_err(|e| Error::FileError(e, self.full_path.clone()))?;
+        let mut buf_reader = std::io::BufReader::new(&exif_file);
+        let exif_reader = exif::Reader::new();
+        exif_reader
+            .read_from_container(&mut buf_reader)
+            .map_err(|e| Error::EXIFParserError(e, self.full_path.clone()))
+    }
+
+    /// Check if the EXIF orientation of this photo is a rotation by 90 degrees, in which case
+    /// the width and height of the stored image must be swapped to get the displayed dimensions
+    fn orientation_swaps_dimensions(&self) -> bool {
+        (5..=8).contains(&self.orientation)
+    }
+
+    /// Remove the resized versions of this photo from the cache folder, if they exist
+    async fn remove_resized(&self, cache_dir: &str) -> Result<(), Error> {
+        for resized_type in [ResizedType::Thumbnail, ResizedType::Large] {
+            for file_extension in ["jpg", "webp"] {
+                let mut path = PathBuf::from(cache_dir);
+                path.push(&self.path);
+                path.push(format!(
+                    "{}_{}.{}",
+                    resized_type.prefix(),
+                    &self.uid,
+                    file_extension
+                ));
+                match fs::remove_file(&path).await {
+                    Err(error) if error.kind() != io::ErrorKind::NotFound => {
+                        return Err(Error::FileError(error, path))
+                    }
+                    _ => (),
+                }
+            }
+        }
         Ok(())
     }
 
@@ -197,6 +439,7 @@ impl Photo {
             config.CACHE_DIR.clone(),
             max_size,
             quality,
+            &DecoderSettings::from_config(config),
         )
         .await
     }
@@ -208,6 +451,7 @@ impl Photo {
         cache_dir: String,
         max_size: usize,
         quality: usize,
+        settings: &DecoderSettings,
     ) -> Result<PathBuf, Error> {
         // Extention according to the configured image format
         let file_extension = match image_format {
@@ -252,17 +496,16 @@ impl Photo {
         }
 
         // Load the image
-        let img = image::ImageReader::open(file_path)
-            .map_err(|e| Error::FileError(e, file_path.clone()))?
-            .decode()
-            .map_err(|e| {
-                eprintln!(
-                    "Error : unable to decode photo at \"{}\" : {}",
-                    file_path.display(),
-                    e
-                );
-                Error::ImageError(e, file_path.clone())
-            })?;
+        let img = self.decode_image(settings).await.inspect_err(|e| {
+            eprintln!(
+                "Error : unable to decode photo at \"{}\" : {}",
+                file_path.display(),
+                e
+            );
+        })?;
+
+        // Remove the alpha channel and convert to 8-bit RGB, which is what both encoders expect
+        let img = DynamicImage::ImageRgb8(flatten_image(img, settings.background));
 
         // Resize this image
         let img_resized = img.resize(max_size as u32, max_size as u32, FilterType::CatmullRom);
@@ -273,7 +516,8 @@ impl Photo {
             80
         });
 
-        // Create an encoder and write the image to the output file.
+        // Create an encoder and write the image to the output file. The encoders don't write any
+        // metadata, so resized photos never carry the GPS position or serial numbers of the original.
         // Note that this uses the standard fs API, as opposed to tokio's async API, because the encoder is not compatible
         // with the async equivalent of Writer.
         let file = std::fs::File::create(&resized_file_path)
@@ -318,6 +562,207 @@ impl Photo {
         Ok(resized_file_path)
     }
 
+    /// Open and decode the photo file, and rotate it according to its EXIF orientation. For camera
+    /// RAW files, the JPEG preview embedded inside the file is decoded instead of the RAW data
+    /// itself, and for videos, a poster frame is extracted from the clip.
+    async fn decode_image(&self, settings: &DecoderSettings) -> Result<DynamicImage, Error> {
+        let mut img = self.decode_image_unrotated(settings).await?;
+        if let Some(orientation) = Orientation::from_exif(self.orientation as u8) {
+            img.apply_orientation(orientation);
+        }
+        Ok(img)
+    }
+
+    /// Open and decode the photo file, without applying its EXIF orientation
+    async fn decode_image_unrotated(
+        &self,
+        settings: &DecoderSettings,
+    ) -> Result<DynamicImage, Error> {
+        if self.media_type == MediaType::Video {
+            let frame =
+                video::extract_poster_frame(&settings.ffmpeg_path, &self.full_path, self.duration)
+                    .await?;
+            image::load_from_memory_with_format(&frame, image::ImageFormat::Png)
+                .map_err(|e| Error::ImageError(e, self.full_path.clone()))
+        } else if raw::is_raw_file(&self.full_path) {
+            let preview = raw::extract_preview(&self.full_path)?;
+            image::load_from_memory_with_format(&preview, image::ImageFormat::Jpeg)
+                .map_err(|e| Error::ImageError(e, self.full_path.clone()))
+        } else {
+            image::ImageReader::open(&self.full_path)
+                .map_err(|e| Error::FileError(e, self.full_path.clone()))?
+                .decode()
+                .map_err(|e| Error::ImageError(e, self.full_path.clone()))
+        }
+    }
+
+    /// Return the full path of the camera RAW file of this photo, which is either the photo file
+    /// itself or one of its alternate files, if any
+    pub fn raw_file(&self) -> Option<PathBuf> {
+        if raw::is_raw_file(&self.full_path) {
+            return Some(self.full_path.clone());
+        }
+        self.alternate_files
+            .iter()
+            .map(|filename| self.full_path.with_file_name(filename))
+            .find(|path| raw::is_raw_file(path))
+    }
+
+    /// Return the full path of a JPEG version of this photo, which is either the photo file itself
+    /// or, for camera RAW files, its embedded preview extracted into the cache folder
+    pub async fn jpeg_file(&self, cache_dir: &str) -> Result<Option<PathBuf>, Error> {
+        if raw::is_raw_file(&self.full_path) {
+            return self.create_raw_preview(cache_dir).await.map(Some);
+        }
+        let is_jpeg = self
+            .full_path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .map(|ext| ext == "jpg" || ext == "jpeg")
+            .unwrap_or(false);
+        match is_jpeg {
+            true => self
+                .stripped_file(&self.full_path, cache_dir)
+                .await
+                .map(Some),
+            false => Ok(None),
+        }
+    }
+
+    /// Return the full path of a version of the given file (either the photo file itself or one of
+    /// its alternate files) that can be sent to users : if some metadata must be removed according
+    /// to PRIVACY_STRIP_METADATA, this is a copy of the file without them in the cache folder,
+    /// created if necessary. Otherwise, this is the file itself.
+    pub async fn stripped_file(&self, path: &Path, cache_dir: &str) -> Result<PathBuf, Error> {
+        if self.strip_metadata.is_empty() || self.media_type == MediaType::Video {
+            return Ok(path.to_path_buf());
+        }
+
+        // The name of the copy depends on the groups of metadata removed, so that it is created
+        // again if the setting changes
+        let mut groups = self
+            .strip_metadata
+            .iter()
+            .map(|group| group.as_str())
+            .collect::<Vec<&str>>();
+        groups.sort();
+        groups.dedup();
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let mut stripped_file_path = PathBuf::from(cache_dir);
+        stripped_file_path.push(&self.path);
+        stripped_file_path.push(format!(
+            "stripped_{}_{}.{}",
+            &self.uid,
+            groups.join("-"),
+            extension
+        ));
+
+        // Reuse the existing copy, unless the file has been modified since it was created
+        let modified = |path: &Path| {
+            let path = path.to_path_buf();
+            async move {
+                fs::metadata(path)
+                    .await
+                    .and_then(|metadata| metadata.modified())
+            }
+        };
+        if let (Ok(stripped_modified), Ok(modified)) =
+            (modified(&stripped_file_path).await, modified(path).await)
+        {
+            if stripped_modified >= modified {
+                return Ok(stripped_file_path);
+            }
+        }
+
+        println!(
+            "Removing metadata ({}) from a copy of \"{}\" in the cache directory...",
+            groups.join(", "),
+            path.display()
+        );
+        if let Some(dir_path) = stripped_file_path.parent() {
+            create_dir_all(dir_path)
+                .await
+                .map_err(|e| Error::FileError(e, dir_path.to_path_buf()))?;
+        }
+        let mut data = fs::read(path)
+            .await
+            .map_err(|e| Error::FileError(e, path.to_path_buf()))?;
+        let strip_metadata = self.strip_metadata.clone();
+        let data = task::spawn_blocking(move || {
+            privacy::strip(&mut data, &strip_metadata);
+            data
+        })
+        .await
+        .map_err(|e| Error::OtherError(format!("unable to remove metadata : {e}")))?;
+
+        // Write to a temporary file first, so that a concurrent request never reads an incomplete copy
+        let temp_file_path =
+            stripped_file_path.with_extension(format!("{}.{}", extension, rand::random::<u32>()));
+        fs::write(&temp_file_path, data)
+            .await
+            .map_err(|e| Error::FileError(e, temp_file_path.clone()))?;
+        fs::rename(&temp_file_path, &stripped_file_path)
+            .await
+            .map_err(|e| Error::FileError(e, stripped_file_path.clone()))?;
+        Ok(stripped_file_path)
+    }
+
+    /// Extract the JPEG preview embedded in this camera RAW photo into the cache folder, if it
+    /// hasn't already been done, and return its path
+    async fn create_raw_preview(&self, cache_dir: &str) -> Result<PathBuf, Error> {
+        let mut preview_file_path = PathBuf::from(cache_dir);
+        preview_file_path.push(&self.path);
+        preview_file_path.push(format!("preview_{}.jpg", &self.uid));
+        if fs::try_exists(&preview_file_path).await.unwrap_or(false) {
+            return Ok(preview_file_path);
+        }
+        if let Some(dir_path) = preview_file_path.parent() {
+            create_dir_all(dir_path)
+                .await
+                .map_err(|e| Error::FileError(e, dir_path.to_path_buf()))?;
+        }
+
+        // Reading the RAW file and walking its structure is blocking work, which is kept away from
+        // the async workers. The preview is a derivative of the photo which never needs to carry
+        // these metadata.
+        let full_path = self.full_path.clone();
+        let preview = task::spawn_blocking(move || {
+            let mut preview = raw::extract_preview(&full_path)?;
+            privacy::strip(&mut preview, &MetadataGroup::ALL);
+            Ok::<Vec<u8>, Error>(preview)
+        })
+        .await
+        .map_err(|e| Error::OtherError(format!("unable to extract a RAW preview : {e}")))??;
+
+        // Write to a temporary file first, so that a concurrent request or a crash never leaves an
+        // incomplete preview in the cache
+        let temp_file_path =
+            preview_file_path.with_extension(format!("jpg.{}", rand::random::<u32>()));
+        fs::write(&temp_file_path, preview)
+            .await
+            .map_err(|e| Error::FileError(e, temp_file_path.clone()))?;
+        fs::rename(&temp_file_path, &preview_file_path)
+            .await
+            .map_err(|e| Error::FileError(e, preview_file_path.clone()))?;
+        Ok(preview_file_path)
+    }
+
+    /// Size and last modification time of the file, if known
+    pub fn fingerprint(&self) -> Option<(i64, i64)> {
+        self.file_size.zip(self.file_mtime)
+    }
+
+    /// Read the size and last modification time of the file
+    pub fn read_fingerprint(&mut self) -> Result<(), Error> {
+        let (file_size, file_mtime) = file_hash::fingerprint(&self.full_path)?;
+        self.file_size = Some(file_size);
+        self.file_mtime = Some(file_mtime);
+        Ok(())
+    }
+
     /// Return the file name of this photo prepended with its path inside the photos directory
     pub fn path_with_filename(&self) -> PathBuf {
         let mut path = self.path.clone();
@@ -347,6 +792,15 @@ impl CachedPhoto {
             passwords,
         }
     }
+
+    /// Check if every password required for this photo to be displayed is in the given list
+    fn is_granted(&self, provided_passwords: &Passwords) -> bool {
+        self.passwords
+            .iter()
+            .all(|(required_password_path, required_password)| {
+                provided_passwords.get(required_password_path) == Some(required_password)
+            })
+    }
 }
 
 impl Deref for CachedPhoto {
@@ -360,7 +814,7 @@ impl Deref for CachedPhoto {
 pub type GalleryContent = HashMap<String, Vec<CachedPhoto>>;
 
 /// Thread-safe struct that holds a list of photos and allows them to be accessed efficiently once loaded
-/// This is supposed to be managed by Rocket
+/// This is supposed to be managed by Rocket, behind an Arc so that it can be shared with the watcher
 pub struct Gallery {
     gallery: RwLock<GalleryContent>,
     photos: RwLock<HashMap<UID, CachedPhoto>>,
@@ -369,6 +823,8 @@ pub struct Gallery {
     passwords: RwLock<HashMap<String, String>>,
     counts: RwLock<HashMap<String, HashMap<Vec<String>, usize>>>,
     subdirs_configs: RwLock<HashMap<String, Config>>,
+    loading: Mutex<()>,
+    progress: ReloadProgress,
 }
 
 impl Gallery {
@@ -382,6 +838,8 @@ impl Gallery {
             passwords: RwLock::new(HashMap::new()),
             counts: RwLock::new(HashMap::new()),
             subdirs_configs: RwLock::new(HashMap::new()),
+            loading: Mutex::new(()),
+            progress: ReloadProgress::new(),
         }
     }
 
@@ -390,28 +848,17 @@ impl Gallery {
         self.photos.read().await.len()
     }
 
-    /// Remove every photo from the gallery
-    pub async fn clear(&self) {
-        let mut gallery_lock = self.gallery.write().await;
-        let mut photos_lock = self.photos.write().await;
-        let mut collections_lock = self.collections.write().await;
-        let mut passwords_lock = self.passwords.write().await;
-        gallery_lock.clear();
-        photos_lock.clear();
-        collections_lock.clear();
-        passwords_lock.clear();
-    }
-
-    /// Reload the gallery. This will `clear()` then `load()`.
+    /// Reload the gallery. The previous content keeps being served until the new one is fully loaded.
     pub async fn reload(
         &self,
         config: &Config,
         db_conn: &mut SqliteConnection,
     ) -> Result<(), Error> {
-        self.clear().await;
         println!("Reloading photos...");
         let now = Instant::now();
-        match self.load(config, db_conn).await {
+        let result = self.load(config, db_conn).await;
+        self.progress.finish(&result, self.len().await);
+        match result {
             Ok(_) => {
                 println!(
                     "Loaded {} photos successfully in {}ms",
@@ -427,6 +874,56 @@ impl Gallery {
         }
     }
 
+    /// Start reloading the gallery in a background task, unless a reload is already running or the
+    /// previous one finished less than RELOAD_COOLDOWN ago
+    pub fn start_reload(self: &Arc<Self>, config: &Config, db: &sqlx::SqlitePool) -> ReloadStart {
+        let start = self.progress.try_start(config.RELOAD_COOLDOWN);
+        if start != ReloadStart::Started {
+            return start;
+        }
+        let gallery = Arc::clone(self);
+        let config = config.clone();
+        let db = db.clone();
+        tokio::spawn(async move {
+            let reload = tokio::spawn({
+                let gallery = Arc::clone(&gallery);
+                async move {
+                    match db.acquire().await {
+                        Ok(mut db_conn) => {
+                            gallery.reload(&config, &mut db_conn).await.ok();
+                        }
+                        Err(error) => {
+                            eprintln!(
+                                "Error : unable to acquire a connection to the database : {error}"
+                            );
+                            gallery
+                                .progress
+                                .finish(&Err(error.into()), gallery.len().await);
+                        }
+                    }
+                }
+            });
+
+            // Make sure that the load isn't reported as running forever if the task panics
+            if let Err(error) = reload.await {
+                eprintln!("Error : the reload of the gallery has been interrupted : {error}");
+                let error = Error::OtherError(format!("the reload has been interrupted : {error}"));
+                gallery.progress.finish(&Err(error), gallery.len().await);
+            }
+        });
+        start
+    }
+
+    /// Return the status of the current or last reload
+    pub fn reload_status(&self) -> ReloadStatus {
+        self.progress.status()
+    }
+
+    /// Return a receiver notified every time the status of the current reload is updated
+    pub fn subscribe_reload_status(&self) -> watch::Receiver<ReloadStatus> {
+        self.progress.subscribe()
+    }
+
     /// Insert an empty array at the given path if it doesn't already exist in the gallery
     pub async fn insert_path(&self, path: &Path) {
         // If this path is not already in the hashmap, insert an empty vec at this key
@@ -571,35 +1068,142 @@ impl Gallery {
         }
     }
 
+    /// Return the photos displayed in the given path that match the given query and are granted by
+    /// the provided passwords, paginated like `read()`, or None if the path doesn't exist
+    pub async fn read_matching(
+        &self,
+        path: &Path,
+        query: &PhotoQuery,
+        start: Option<usize>,
+        count: Option<usize>,
+        provided_passwords: &Passwords,
+    ) -> Option<MatchingPhotos> {
+        // Get the photos from the collection if this path is inside one, or the main gallery otherwise.
+        // Subdir passwords are not supported for collections, so their photos don't require any.
+        let collections_read_lock = self.collections.read().await;
+        let gallery_read_lock = self.gallery.read().await;
+        let (collection, _, path_str) = collections_read_lock.find(path);
+        let photos = match collection {
+            Some(collection) => collection.photos.get(&path_str),
+            None => gallery_read_lock.get(&path_str),
+        }?;
+
+        // Keep the order in which the photos are displayed in the grid
+        let found = photos
+            .iter()
+            .filter(|cp| cp.is_granted(provided_passwords) && query.matches(&cp.photo))
+            .collect::<Vec<_>>();
+
+        // Compute pagination, limiting the maximum number of results to 100
+        let total = found.len();
+        let start = start.filter(|&start| start < total).unwrap_or(0);
+        let count = count.unwrap_or(total).min(100);
+        Some(MatchingPhotos {
+            start,
+            total,
+            photos: found
+                .iter()
+                .skip(start)
+                .take(count)
+                .map(|cp| Arc::clone(&cp.photo))
+                .collect(),
+        })
+    }
+
+    /// Count the values of the metadata of the photos displayed in the given path and granted by the
+    /// provided passwords, or return None if the path doesn't exist
+    pub async fn facets(&self, path: &Path, provided_passwords: &Passwords) -> Option<Facets> {
+        let collections_read_lock = self.collections.read().await;
+        let gallery_read_lock = self.gallery.read().await;
+        let (collection, _, path_str) = collections_read_lock.find(path);
+        let photos = match collection {
+            Some(collection) => collection.photos.get(&path_str),
+            None => gallery_read_lock.get(&path_str),
+        }?;
+        Some(Facets::from_photos(
+            photos
+                .iter()
+                .filter(|cp| cp.is_granted(provided_passwords))
+                .map(|cp| cp.photo.as_ref()),
+        ))
+    }
+
     /// Return a copy of a single photo from the cache, based on its UID
     pub async fn get_from_uid(&self, uid: &UID) -> Option<Photo> {
         Some(Photo::clone(&self.photos.read().await.get(uid)?.photo))
     }
 
+    /// Return a copy of a single photo from the cache based on its UID, if the current session grants
+    /// access to it : either every password required by the folders containing it is stored in the
+    /// session cookies or granted by a share link, or it is part of a collection that can be accessed.
+    /// Photos that can't be accessed are reported as not found, in order not to disclose their existence.
+    pub async fn get_from_uid_checked(
+        &self,
+        uid: &UID,
+        cookies: &CookieJar<'_>,
+        shared_paths: &SharedPaths,
+    ) -> Option<Photo> {
+        // The passwords stored along with the photos in the gallery only cover the folders in which
+        // they are displayed, check every parent folder instead
+        let (photo, granted) = {
+            let gallery_passwords = self.passwords.read().await;
+            let photo = self.get_from_uid(uid).await?;
+            let photo_path = photo.path.to_string_lossy();
+            let granted = photo.path.ancestors().all(|path| {
+                let path = path.to_string_lossy();
+                gallery_passwords
+                    .get(path.as_ref())
+                    .is_none_or(|required_password| {
+                        password::is_in_session(cookies, &path, required_password)
+                            || shared_paths.grants(&path, &photo_path)
+                    })
+            });
+            (photo, granted)
+        };
+        if granted {
+            return Some(photo);
+        }
+
+        // Subdir passwords are not required for the photos displayed in collections, only the
+        // password of the collection itself
+        let collections = self.collections.read().await;
+        let in_collection = collections.deref().into_iter().any(|collection| {
+            collection.contains(uid)
+                && collection
+                    .password
+                    .as_ref()
+                    .is_none_or(|required_password| {
+                        password::is_in_session(cookies, &collection.name, required_password)
+                            || shared_paths.grants(&collection.name, &collection.name)
+                    })
+        });
+        in_collection.then_some(photo)
+    }
+
     /// Check if the current session state stored in private cookies as well as any password provided
     /// with the current request grants access to the given path. If a new valid password has been
-    /// provided in the current request, add it to the session cookie.
-    /// Returns Ok with the list of all passwords in the user's session either if no password is required
+    /// provided in the current request, grant access to its path in the session cookie.
+    /// Returns Ok with the list of all passwords granted in the user's session either if no password is required
     /// or if access is granted, or Err with the kind of error if the password is invalid.
     pub async fn check_password(
         &self,
         path: &Path,
         cookies: &CookieJar<'_>,
         request_password: &OptionalPassword,
+        attempts: &PasswordAttempts,
+        shared_paths: &SharedPaths,
     ) -> Result<Passwords, PasswordError> {
         // Get a lock on the gallery's password list
         let gallery_passwords = self.passwords.read().await;
 
-        // Compute the list of user-provided passwords
+        // Compute the list of passwords granted in the user's session, either directly or by a share link
+        let path_str = path.to_string_lossy();
         let mut user_passwords = Passwords::new();
         for (gallery_path, required_password) in gallery_passwords.deref() {
-            if let Some(provided_password) = cookies
-                .get_private(&password::cookie_name(gallery_path))
-                .map(|c| c.value().to_string())
+            if password::is_in_session(cookies, gallery_path, required_password)
+                || shared_paths.grants(gallery_path, &path_str)
             {
-                if &provided_password == required_password {
-                    user_passwords.insert(gallery_path.clone(), provided_password);
-                }
+                user_passwords.insert(gallery_path.clone(), required_password.clone());
             }
         }
 
@@ -646,18 +1250,28 @@ impl Gallery {
                 let required_password = required_passwords.get(required_password_path).unwrap();
 
                 // Check if a matching password was provided through the request guard (the Authorization header)
+                let client_ip = request_password.client_ip();
                 if let Some(user_provided_password) = request_password.as_string() {
-                    // Check if the password matches
-                    if user_provided_password == required_password {
-                        // It does : save it in the session cookies
-                        cookies.add_private(Cookie::new(
-                            password::cookie_name(required_password_path),
-                            user_provided_password.clone(),
-                        ));
-                        user_passwords.insert(
+                    // Refuse to check it if the client has tried too many invalid passwords
+                    if let Err(retry_after) = attempts.check(client_ip, required_password_path) {
+                        return Err(PasswordError::TooManyAttempts(
                             required_password_path.clone(),
-                            user_provided_password.clone(),
+                            retry_after,
+                        ));
+                    }
+
+                    // Check if the password matches
+                    if password::verify(user_provided_password, required_password).await {
+                        attempts.succeed(client_ip, required_password_path);
+
+                        // It does : grant access to this path in the session cookies
+                        password::add_to_session(
+                            cookies,
+                            required_password_path,
+                            required_password,
                         );
+                        user_passwords
+                            .insert(required_password_path.clone(), required_password.clone());
 
                         // Jump to the next required password in the list, if any
                         continue;
@@ -674,8 +1288,9 @@ impl Gallery {
                         // It doesn't match : return "invalid password"
                         return Err(PasswordError::Invalid(required_password_path.clone()));
                     }
-                } else if request_password.as_string().is_some() {
+                } else if let Some(user_provided_password) = request_password.as_string() {
                     // An invalid password was provided in the current request : return "invalid password"
+                    attempts.fail(client_ip, required_password_path, user_provided_password);
                     return Err(PasswordError::Invalid(required_password_path.clone()));
                 } else {
                     // No password found in the request or the session cookies : return "password required"
@@ -734,25 +1349,27 @@ impl Gallery {
 
             // Try to find a config file in this directory, append it to a copy of the current one (so it won't propagate to
             // sibling directories), and put it on the stack
-            let mut cfg = configs_stack.last().unwrap().1.clone();
-            cfg.remove("HIDDEN"); // These settings don't propagate from the parent
-            if rel_path != &PathBuf::new() {
-                cfg.remove("PASSWORD");
-            }
-            Config::update_with_subdir(full_path, &mut cfg);
+            let cfg = subdir_config(&configs_stack.last().unwrap().1, full_path, rel_path);
             configs_stack.push((rel_path.clone(), cfg));
             let subdir_config = &configs_stack.last().unwrap().1;
-            match Config::from_table(subdir_config.clone()) {
+            let (strip_metadata, sort_order) = match Config::from_table(subdir_config.clone()) {
                 Ok(config) => {
+                    let strip_metadata = config.PRIVACY_STRIP_METADATA.clone();
+                    let sort_order = config.sort_order();
                     let mut subdirs_configs_lock = self.subdirs_configs.write().await;
                     subdirs_configs_lock.insert(rel_path_str.clone(), config);
+                    (strip_metadata, sort_order)
                 }
-                Err(error) => eprintln!(
-                    "Warning : unable to read config in {} : {}",
-                    rel_path.display(),
-                    error
-                ),
-            }
+                Err(error) => {
+                    eprintln!(
+                        "Warning : unable to read config in {} : {}",
+                        rel_path.display(),
+                        error
+                    );
+                    // Don't take any risk with the privacy of the photos in this directory
+                    (MetadataGroup::ALL.to_vec(), default_config.sort_order())
+                }
+            };
 
             // If this directory is password-protected, add it to the list
             let password = match subdir_config.get("PASSWORD") {
@@ -776,6 +1393,7 @@ impl Gallery {
 
             // List the files inside this path in the photos directory
             let mut filenames_in_fs: Vec<String> = Vec::new();
+            let mut alternate_files_in_fs: HashMap<String, Vec<String>> = HashMap::new();
             if photos_to_insert.is_some() || photos_to_remove.is_some() {
                 let dir = fs::read_dir(full_path)
                     .await
@@ -788,8 +1406,7 @@ impl Gallery {
                             let filename_lowercase = filename.to_lowercase();
                             if file_type.is_file()
                                 && !filename_lowercase.starts_with('.')
-                                && (filename_lowercase.ends_with(".jpg")
-                                    || filename_lowercase.ends_with(".jpeg"))
+                                && main_config.is_indexed_file(&filename_lowercase)
                             {
                                 filenames_in_fs.push(filename);
                             }
@@ -797,20 +1414,68 @@ impl Gallery {
                     }
                 }
                 filenames_in_fs.sort_by(|a, b| natord::compare_ignore_case(a, b));
+
+                // Attach camera RAW files to their sibling image, if any
+                if main_config.PAIR_RAW_FILES {
+                    (filenames_in_fs, alternate_files_in_fs) = pair_raw_files(filenames_in_fs);
+                }
             }
 
             // Get the list of photos saved in the database for this path exactly
-            let sort_columns = String::from(
-                subdir_config
-                    .get("SORT_ORDER")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or(&default_config.SORT_ORDER),
-            )
-            .split(',')
-            .map(|s| String::from(s.trim()))
-            .collect::<Vec<String>>();
-            let photos_in_db =
-                db::get_photos_in_path(db_conn, rel_path, &sort_columns, main_config).await?;
+            let mut photos_in_db =
+                db::get_photos_in_path(db_conn, rel_path, &sort_order, main_config).await?;
+
+            // Update the alternate files of the photos already known in the database if they have changed
+            if photos_to_insert.is_some() {
+                for photo in photos_in_db.iter_mut() {
+                    if !filenames_in_fs.contains(&photo.filename) {
+                        continue;
+                    }
+                    let alternate_files = alternate_files_in_fs
+                        .remove(&photo.filename)
+                        .unwrap_or_default();
+                    if alternate_files != photo.alternate_files {
+                        println!(
+                            "Updating alternate files of \"{}\" : {}",
+                            photo.path_with_filename().display(),
+                            alternate_files
+                                .iter()
+                                .map(|filename| format!("\"{filename}\""))
+                                .collect::<Vec<String>>()
+                                .join(", ")
+                        );
+                        photo.alternate_files = alternate_files;
+                        db::update_alternate_files(db_conn, photo).await?;
+                    }
+                }
+            }
+
+            // Save the fingerprint of the photos indexed by a previous version, which don't have one yet
+            if photos_to_insert.is_some() {
+                for photo in photos_in_db.iter_mut() {
+                    if photo.fingerprint().is_some() || !filenames_in_fs.contains(&photo.filename) {
+                        continue;
+                    }
+                    match photo.read_fingerprint() {
+                        Ok(_) => db::update_fingerprint(db_conn, photo).await?,
+                        Err(error) => eprintln!("Warning : unable to read the size and modification time of \"{}\" : {}", photo.path_with_filename().display(), error),
+                    }
+                }
+            }
+
+            // Read the XMP sidecars of the photos already known in the database, which may have been
+            // edited since the photos were indexed
+            if photos_to_insert.is_some() {
+                for photo in photos_in_db.iter_mut() {
+                    if photo.reload_sidecar().await {
+                        println!(
+                            "Updating metadata of \"{}\" from its XMP sidecar",
+                            photo.path_with_filename().display()
+                        );
+                        db::update_descriptive_metadata(db_conn, photo).await?;
+                    }
+                }
+            }
 
             // Find photos in the filesystem that are not in the database yet
             if let Some(ref mut photos_to_insert) = photos_to_insert {
@@ -823,10 +1488,18 @@ impl Gallery {
                         let mut full_path = PathBuf::from(&main_config.PHOTOS_DIR);
                         full_path.push(rel_path);
                         full_path.push(filename);
+                        let media_type = match main_config.is_video_file(filename) {
+                            true => MediaType::Video,
+                            false => MediaType::Photo,
+                        };
                         photos_to_insert.push(Photo {
                             path: rel_path.clone(),
                             filename: filename.clone(),
                             full_path,
+                            media_type,
+                            alternate_files: alternate_files_in_fs
+                                .remove(filename)
+                                .unwrap_or_default(),
                             ..Default::default()
                         });
                     }
@@ -860,7 +1533,7 @@ impl Gallery {
                         if let Ok(file_type) = entry.file_type().await {
                             if let Ok(filename) = entry.file_name().into_string() {
                                 let filename_lowercase = filename.to_lowercase();
-                                for prefix in ["thumbnail_", "large_"] {
+                                for prefix in ["thumbnail_", "large_", "preview_"] {
                                     // Check if this is a jpeg file with a known prefix
                                     if file_type.is_file()
                                         && filename_lowercase.starts_with(prefix)
@@ -920,6 +1593,17 @@ impl Gallery {
                 }
             }
 
+            // Remember which metadata must be removed from these photos before sending them, and forget
+            // their position if it must not be disclosed
+            for photo in photos_in_db.iter_mut() {
+                photo.strip_metadata = strip_metadata.clone();
+                if strip_metadata.contains(&MetadataGroup::GPS) {
+                    photo.latitude = None;
+                    photo.longitude = None;
+                    photo.altitude = None;
+                }
+            }
+
             // Add these photos recursively to this path and its parent paths in the gallery as long as SHOW_PHOTOS_FROM_SUBDIRS is set and HIDDEN isn't
             if !photos_in_db.is_empty() {
                 let mut is_parent = false; // False for the first iteration, then set to true for the parent paths
@@ -1040,6 +1724,9 @@ impl Gallery {
 
     /// Load all available photos in the photos folder, add them to the given gallery, and sync them with the database
     async fn load(&self, config: &Config, db_conn: &mut SqliteConnection) -> Result<(), Error> {
+        // Make sure the photos are not synchronized concurrently by the watcher
+        let _loading_lock = self.loading.lock().await;
+
         // Make sure the main directories (photos and cache) exist, and if not, try to create them
         check_config_dir(&PathBuf::from(&config.PHOTOS_DIR)).await
             .inspect_err(|e| {
@@ -1054,6 +1741,9 @@ impl Gallery {
                 }
             })?;
 
+        // Photos indexed by a previous version may be missing some of the EXIF fields
+        migrate_exif(config, db_conn).await?;
+
         // Keep these paths on hand
         let full_path = PathBuf::from(&config.PHOTOS_DIR);
         let rel_path = PathBuf::new();
@@ -1062,298 +1752,298 @@ impl Gallery {
         let default_config = Config::default();
 
         // Initialize the stack of configs with the main config
-        let subdir_config = config.as_value().unwrap_or_else(|_| Table::new());
-        let mut configs_stack: Vec<(PathBuf, Table)> = Vec::new();
-        configs_stack.push((PathBuf::from("[main]"), subdir_config));
-
-        // Get all existing UIDs from the database
-        let mut existing_uids = db::get_existing_uids(db_conn).await?;
+        let mut configs_stack = configs_stack(config, &rel_path);
 
         // Load the photos recursively, comparing the filesystem and the database.
         // If some differences are found, they will be returned in these Vec's.
         let mut photos_to_insert: Vec<Photo> = Vec::new();
         let mut photos_to_remove: Vec<Photo> = Vec::new();
+        // The photos are loaded into a new gallery, while this one keeps serving the previous content.
         let mut paths_found: Vec<PathBuf> = Vec::new();
-        self.load_rec(
-            &full_path,
-            &rel_path,
-            db_conn,
-            config,
-            &mut configs_stack,
-            &default_config,
-            &mut Some(&mut photos_to_insert),
-            &mut Some(&mut photos_to_remove),
-            &mut Some(&mut paths_found),
-        )
-        .await?;
+        let mut loaded = Gallery::new();
+        loaded
+            .load_rec(
+                &full_path,
+                &rel_path,
+                db_conn,
+                config,
+                &mut configs_stack,
+                &default_config,
+                &mut Some(&mut photos_to_insert),
+                &mut Some(&mut photos_to_remove),
+                &mut Some(&mut paths_found),
+            )
+            .await?;
 
         // Get the list of all known subdirs of the current path in the database, check if some have been removed,
         // and if so add their photos to the 'to_remove' list
         if config.INDEX_SUBDIRS {
-            let mut deleted_paths: Vec<PathBuf> = Vec::new();
-            let known_paths_in_db = db::get_all_paths(db_conn).await?;
-            for known_path in known_paths_in_db {
-                if !paths_found.contains(&known_path) {
-                    deleted_paths.push(known_path);
-                }
-            }
-            if !deleted_paths.is_empty() {
-                let photos_in_deleted_paths =
-                    db::get_photos_in_paths(db_conn, &deleted_paths, config).await?;
-                for photo in photos_in_deleted_paths {
-                    photos_to_remove.push(photo);
-                }
-            }
+            photos_to_remove.append(
+                &mut find_photos_in_deleted_paths(config, db_conn, &rel_path, &paths_found).await?,
+            );
         }
 
-        // Calculate the MD5 hashes of the new files in parallel background tasks
-        if !photos_to_insert.is_empty() {
-            let now = Instant::now();
-            let n = photos_to_insert.len();
-            let mut last_percent: usize = 0;
-            let mut tasks = JoinSet::new();
-            let mut results: Vec<(usize, String)> = Vec::new();
-            let mut offset = 0;
-            while results.len() < n {
-                // Create up to LOADING_WORKERS background tasks
-                let batch_size = min(n - offset, config.LOADING_WORKERS);
-                for (idx, photo) in photos_to_insert
-                    .iter()
-                    .enumerate()
-                    .skip(offset)
-                    .take(batch_size)
-                {
-                    let full_path = photo.full_path.clone();
-                    tasks.spawn(async move { (idx, calculate_file_md5(&full_path).await) });
-                }
-                offset += batch_size;
-
-                // Wait for these tasks to complete and add their results to the list
-                while let Some(result) = tasks.join_next().await {
-                    match result {
-                        Ok((i, Ok(md5))) => {
-                            results.push((i, md5));
-                            let percent: usize = (i + 1) * 100 / n;
-                            if percent > last_percent {
-                                print!("\rCalculating MD5 hashes of {n} new files... {percent}%");
-                                std::io::stdout().flush().ok();
-                                last_percent = percent;
-                            }
-                        }
-                        Ok((i, Err(error))) => eprintln!(
-                            "Error : unable to compute MD5 for \"{}\" : {}",
-                            photos_to_insert.get(i).unwrap().filename,
-                            error
-                        ),
-                        Err(error) => eprintln!(
-                            "Error : unable to join background task while computing MD5 : {error}"
-                        ),
-                    }
-                }
-            }
-            for (i, md5) in results {
-                photos_to_insert.get_mut(i).unwrap().md5 = md5;
-            }
-            println!("\nDone in {}ms", now.elapsed().as_millis());
+        // Apply the differences to the database
+        let need_to_reload = sync_database(
+            config,
+            db_conn,
+            photos_to_insert,
+            photos_to_remove,
+            &self.progress,
+        )
+        .await?;
+        self.progress.set_phase(ReloadPhase::Indexing, None);
+
+        // Reload if required, into yet another gallery so that the one that has just been loaded
+        // doesn't need to be cleared
+        if need_to_reload {
+            loaded = Gallery::new();
+            loaded
+                .load_rec(
+                    &full_path,
+                    &rel_path,
+                    db_conn,
+                    config,
+                    &mut configs_stack,
+                    &default_config,
+                    &mut None,
+                    &mut None,
+                    &mut None,
+                )
+                .await?;
         }
 
-        // Detect if some of the insert/remove are actually the same file that has been moved or renamed
-        let mut photos_to_move: Vec<(Photo, Photo)> = Vec::new();
-        if !&photos_to_insert.is_empty() && !photos_to_remove.is_empty() {
-            let mut duplicate_hashes: Vec<String> = Vec::new();
-            for new_photo in &photos_to_insert {
-                for old_photo in &photos_to_remove {
-                    if old_photo.md5 == new_photo.md5 {
-                        duplicate_hashes.push(old_photo.md5.clone());
-                        photos_to_move.push((old_photo.clone(), new_photo.clone()));
-                    }
-                }
-            }
-            photos_to_insert.retain(|photo| !duplicate_hashes.contains(&photo.md5));
-            photos_to_remove.retain(|photo| !duplicate_hashes.contains(&photo.md5));
-        }
-
-        // If there were some modifications to the photos we will need to reload the photos
-        let need_to_reload = !photos_to_insert.is_empty()
-            || !photos_to_remove.is_empty()
-            || !photos_to_move.is_empty();
-
-        // Apply detected modifications (photos added, moved, or deleted) to the database
-        if !photos_to_insert.is_empty() {
-            // Generate a new UID for each photo
-            for photo in photos_to_insert.iter_mut() {
-                photo.uid = UID::new(&existing_uids);
-                existing_uids.push(photo.uid.clone());
-            }
-
-            // Since we will handle the list from last to first (because of pop()) we need to reverse
-            // the array first to keep the original order
-            photos_to_insert.reverse();
-
-            // Parse the photos' metadata and, if set in the config, generate their thumbnails
-            let pre_generate_thumbnails = config.PRE_GENERATE_THUMBNAILS;
-            let thumbnail_max_size = ResizedType::Thumbnail.max_size(config);
-            let thumbnail_quality = ResizedType::Thumbnail.quality(config);
-            let large_size_max_size = ResizedType::Large.max_size(config);
-            let large_size_quality = ResizedType::Large.quality(config);
-            let mut photos_to_insert_in_db: Vec<Photo> = Vec::new();
-            while !photos_to_insert.is_empty() {
-                // Spawn background tasks to parallelize computation, up to the LOADING_WORKERS setting in the config
-                let mut counter = 0;
-                let mut tasks = Vec::with_capacity(config.LOADING_WORKERS);
-                while let Some(mut photo) = photos_to_insert.pop() {
-                    // Background task which takes ownership of the photo object
-                    let cache_dir = config.CACHE_DIR.clone();
-                    let image_format = config.RESIZED_IMAGE_FORMAT;
-                    tasks.push(tokio::spawn(async move {
-                        // Parse the metadata
-                        photo
-                            .parse_metadata(true)
-                            .await
-                            .map_err(|e| {
-                                eprintln!(
-                                    "Error : unable to open \"{}/{}\" : {}",
-                                    photo.path.to_string_lossy(),
-                                    photo.filename,
-                                    e
-                                );
-                                e
-                            })
-                            .ok(); // Ignore error after printing it
-
-                        // Generate thumbnails
-                        if pre_generate_thumbnails {
-                            photo
-                                .create_resized_from_params(
-                                    ResizedType::Thumbnail,
-                                    image_format,
-                                    cache_dir.clone(),
-                                    thumbnail_max_size,
-                                    thumbnail_quality,
-                                )
-                                .await
-                                .ok();
-                            photo
-                                .create_resized_from_params(
-                                    ResizedType::Large,
-                                    image_format,
-                                    cache_dir.clone(),
-                                    large_size_max_size,
-                                    large_size_quality,
-                                )
-                                .await
-                                .ok();
-                        }
+        // Update the counts of photos
+        loaded.update_counts().await;
 
-                        // Return ownership of the photo to the main thread
-                        photo
-                    }));
+        // Sort the photos into collections
+        println!("Indexing collections...");
+        loaded.load_collections(config).await;
 
-                    counter += 1;
-                    if counter >= config.LOADING_WORKERS {
-                        // We have enough workers for now
-                        break;
-                    }
-                }
+        // Replace the previous content of the gallery with the new one at once
+        self.replace_with(loaded).await;
 
-                // Wait for the background tasks to finish
-                for task in tasks.into_iter() {
-                    if let Ok(photo) = task.await {
-                        photos_to_insert_in_db.push(photo);
+        // Good job.
+        Ok(())
+    }
+
+    /// Replace the whole content of this gallery with the content of the other one at once. The locks
+    /// are all held during the swap, in the same order as they are acquired by readers, so that they
+    /// see either the previous content or the new one, but never a mix of both.
+    async fn replace_with(&self, other: Gallery) {
+        let mut passwords_lock = self.passwords.write().await;
+        let mut collections_lock = self.collections.write().await;
+        let mut gallery_lock = self.gallery.write().await;
+        let mut counts_lock = self.counts.write().await;
+        let mut photos_lock = self.photos.write().await;
+        let mut subdirs_lock = self.subdirs.write().await;
+        let mut subdirs_configs_lock = self.subdirs_configs.write().await;
+        *passwords_lock = other.passwords.into_inner();
+        *collections_lock = other.collections.into_inner();
+        *gallery_lock = other.gallery.into_inner();
+        *counts_lock = other.counts.into_inner();
+        *photos_lock = other.photos.into_inner();
+        *subdirs_lock = other.subdirs.into_inner();
+        *subdirs_configs_lock = other.subdirs_configs.into_inner();
+    }
+
+    /// Synchronize the given directories of the photos folder (relative to PHOTOS_DIR) and their
+    /// subdirectories with the database, then update them in the gallery without reloading the other
+    /// directories. This is used by the watcher when some changes are detected in the photos folder.
+    pub async fn sync_dirs(
+        &self,
+        config: &Config,
+        db_conn: &mut SqliteConnection,
+        dirs: &[PathBuf],
+    ) -> Result<(), Error> {
+        let _loading_lock = self.loading.lock().await;
+
+        // Directories that have been removed, or that are not indexed, are synchronized from their
+        // closest parent directory that is loaded in the gallery
+        let dirs = {
+            let gallery_lock = self.gallery.read().await;
+            dirs.iter()
+                .map(|dir| {
+                    let mut dir = dir.as_path();
+                    while let Some(parent) = dir.parent() {
+                        let mut full_path = PathBuf::from(&config.PHOTOS_DIR);
+                        full_path.push(dir);
+                        if full_path.is_dir()
+                            && gallery_lock.contains_key(dir.to_string_lossy().as_ref())
+                        {
+                            break;
+                        }
+                        dir = parent;
                     }
-                }
-            }
+                    dir.to_path_buf()
+                })
+                .collect::<Vec<PathBuf>>()
+        };
 
-            // Log the list of photos to insert
-            println!(
-                "Inserting {} photo(s) into the database : {}",
-                photos_to_insert_in_db.len(),
-                photos_to_insert_in_db
+        // Only keep the topmost directories, since their subdirectories are synchronized recursively
+        let mut dirs = dirs
+            .iter()
+            .filter(|dir| {
+                !dirs
                     .iter()
-                    .map(|photo| format!("\"{}/{}\"", photo.path.to_string_lossy(), photo.filename))
-                    .collect::<Vec<String>>()
-                    .join(", ")
-            );
+                    .any(|other| other != *dir && dir.starts_with(other))
+            })
+            .cloned()
+            .collect::<Vec<PathBuf>>();
+        dirs.sort_unstable();
+        dirs.dedup();
+        let default_config = Config::default();
 
-            // Insert them into the database
-            db::insert_photos(db_conn, &photos_to_insert_in_db).await?;
+        // Compare the filesystem and the database in these directories, the same way as `load()` but
+        // without modifying the gallery
+        let mut photos_to_insert: Vec<Photo> = Vec::new();
+        let mut photos_to_remove: Vec<Photo> = Vec::new();
+        let mut paths_found: Vec<PathBuf> = Vec::new();
+        for dir in &dirs {
+            let mut full_path = PathBuf::from(&config.PHOTOS_DIR);
+            full_path.push(dir);
+            Gallery::new()
+                .load_rec(
+                    &full_path,
+                    dir,
+                    db_conn,
+                    config,
+                    &mut configs_stack(config, dir),
+                    &default_config,
+                    &mut Some(&mut photos_to_insert),
+                    &mut Some(&mut photos_to_remove),
+                    &mut Some(&mut paths_found),
+                )
+                .await?;
+            if config.INDEX_SUBDIRS {
+                photos_to_remove.append(
+                    &mut find_photos_in_deleted_paths(config, db_conn, dir, &paths_found).await?,
+                );
+            }
         }
-        if !photos_to_remove.is_empty() {
-            // Log the list of photos to remove
-            println!(
-                "Removing {} photo(s) from the database : {}",
-                photos_to_remove.len(),
-                photos_to_remove
-                    .iter()
-                    .map(|photo| format!("\"{}/{}\"", photo.path.to_string_lossy(), photo.filename))
-                    .collect::<Vec<String>>()
-                    .join(", ")
-            );
 
-            // Remove them from the database
-            db::remove_photos(db_conn, &photos_to_remove).await?;
-        }
-        if !photos_to_move.is_empty() {
-            // Log the list of photos to rename/move
-            println!(
-                "Renaming/moving {} photo(s) in the database : {}",
-                photos_to_move.len(),
-                photos_to_move
-                    .iter()
-                    .map(|pair| format!(
-                        "\"{}/{}\" -> \"{}/{}\"",
-                        pair.0.path.to_string_lossy(),
-                        pair.0.filename,
-                        pair.1.path.to_string_lossy(),
-                        pair.1.filename
-                    ))
-                    .collect::<Vec<String>>()
-                    .join(", ")
-            );
+        // Apply the differences to the database
+        // The progress of the synchronizations made by the watcher is not reported
+        sync_database(
+            config,
+            db_conn,
+            photos_to_insert,
+            photos_to_remove,
+            &ReloadProgress::new(),
+        )
+        .await?;
 
-            // Update the database
-            db::move_photos(db_conn, &photos_to_move).await?;
+        // Load the new content of these directories into a separate gallery, even if no photos have
+        // been added or removed since their sidecars may have been updated, and merge it into this one
+        let updated = Gallery::new();
+        for dir in &dirs {
+            let mut full_path = PathBuf::from(&config.PHOTOS_DIR);
+            full_path.push(dir);
+            updated
+                .load_rec(
+                    &full_path,
+                    dir,
+                    db_conn,
+                    config,
+                    &mut configs_stack(config, dir),
+                    &default_config,
+                    &mut None,
+                    &mut None,
+                    &mut None,
+                )
+                .await?;
         }
+        // The collections are filled with the updated photos during the replacement
+        let collections = Collections::try_read_from(config.COLLECTIONS_FILE.clone());
+        self.replace_dirs(updated, &dirs, collections).await;
 
-        // Reload if required
-        if need_to_reload {
-            self.clear().await;
-            self.load_rec(
-                &full_path,
-                &rel_path,
-                db_conn,
-                config,
-                &mut configs_stack,
-                &default_config,
-                &mut None,
-                &mut None,
-                &mut None,
-            )
-            .await?;
-        }
+        Ok(())
+    }
 
-        // Update the counts of photos
-        self.update_counts().await;
+    /// Replace the content of the given directories and their subdirectories with the content of
+    /// the `updated` gallery, which has been loaded with these directories only. The photos of these
+    /// directories are also replaced in their parent paths, at the same position as they would have
+    /// been loaded, so that readers never see an incomplete gallery. The given collections, if any,
+    /// are then filled with the new content of the gallery and replace the current ones.
+    async fn replace_dirs(
+        &self,
+        updated: Gallery,
+        dirs: &[PathBuf],
+        collections: Option<Collections>,
+    ) {
+        let is_updated = |path: &Path| dirs.iter().any(|dir| path.starts_with(dir));
+
+        // Hold every lock during the update, in the same order as `replace_with()`, so that readers
+        // never see a partially updated gallery
+        let mut passwords_lock = self.passwords.write().await;
+        let mut collections_lock = self.collections.write().await;
+        let mut gallery_lock = self.gallery.write().await;
+        let mut counts_lock = self.counts.write().await;
+        let mut photos_lock = self.photos.write().await;
+        let mut subdirs_lock = self.subdirs.write().await;
+        let mut subdirs_configs_lock = self.subdirs_configs.write().await;
+
+        // Replace the passwords, configs and subdirectories of the updated directories
+        passwords_lock.retain(|path, _| !is_updated(Path::new(path)));
+        passwords_lock.extend(updated.passwords.into_inner());
+        subdirs_configs_lock.retain(|path, _| !is_updated(Path::new(path)));
+        subdirs_configs_lock.extend(updated.subdirs_configs.into_inner());
+        subdirs_lock.retain(|path, _| !is_updated(Path::new(path)));
+        subdirs_lock.extend(updated.subdirs.into_inner());
+
+        // Replace their photos
+        photos_lock.retain(|_, photo| !is_updated(&photo.path));
+        photos_lock.extend(updated.photos.into_inner());
+
+        // Remove the updated directories, and their photos from the parent paths
+        let mut modified_paths: Vec<String> = Vec::new();
+        gallery_lock.retain(|path, _| !is_updated(Path::new(path)));
+        for (path, photos) in gallery_lock.iter_mut() {
+            if photos.iter().any(|photo| is_updated(&photo.path)) {
+                photos.retain(|photo| !is_updated(&photo.path));
+                modified_paths.push(path.clone());
+            }
+        }
 
-        // Apply REVERSE_SORT_ORDER to the gallery
-        {
-            let mut gallery_lock = self.gallery.write().await;
-            let subdirs_configs_lock = self.subdirs_configs.read().await;
-            for (path, config) in subdirs_configs_lock.deref() {
-                if config.REVERSE_SORT_ORDER {
-                    if let Some(ref mut photos) = gallery_lock.get_mut(path) {
-                        photos.reverse();
+        // Insert the new content, keeping the photos in the parent paths sorted by directory
+        for (path, updated_photos) in updated.gallery.into_inner() {
+            match gallery_lock.get_mut(&path) {
+                Some(photos) => {
+                    for photo in updated_photos {
+                        let index = photos
+                            .iter()
+                            .position(|other| {
+                                compare_paths(&other.path, &photo.path) == Ordering::Greater
+                            })
+                            .unwrap_or(photos.len());
+                        photos.insert(index, photo);
                     }
                 }
+                None => {
+                    gallery_lock.insert(path.clone(), updated_photos);
+                }
             }
+            modified_paths.push(path);
         }
 
-        // Sort the photos into collections
-        println!("Indexing collections...");
-        self.load_collections(config).await;
+        // Update the counts of the modified paths
+        counts_lock.retain(|path, _| gallery_lock.contains_key(path));
+        for path in modified_paths {
+            if let Some(photos) = gallery_lock.get(&path) {
+                counts_lock.insert(path, count_photos(photos));
+            }
+        }
 
-        // Good job.
-        Ok(())
+        // Fill the collections with the updated gallery and register their passwords
+        if let Some(mut collections) = collections {
+            collections.fill(gallery_lock.deref());
+            for collection in &collections {
+                if let Some(password) = &collection.password {
+                    passwords_lock.insert(collection.name.clone(), password.clone());
+                }
+            }
+            collections_lock.replace_with(collections);
+        }
     }
 
     /// Load the list of collections from the dedicated config file and fill them with the photos from the gallery
@@ -1393,45 +2083,7 @@ impl Gallery {
 
         // Process each path in the gallery
         for (path, photos) in gallery_lock.deref() {
-            // Aggregate of the number of photos found which require a certain list of passwords
-            let mut counts_in_path: HashMap<Vec<String>, usize> = HashMap::new();
-
-            // Process each photo in this path
-            for photo in photos {
-                // List of passwords required to access this photo
-                let mut photo_passwords = photo
-                    .passwords
-                    .iter()
-                    .map(|(p, _)| p)
-                    .collect::<Vec<&String>>();
-                photo_passwords.sort_unstable();
-
-                // Try to find this list of passwords in the current hashmap
-                let mut key_found = None;
-                for key in counts_in_path.keys() {
-                    let key_pw = key.iter().collect::<Vec<&String>>();
-                    if key_pw == photo_passwords {
-                        key_found = Some(key.clone());
-                        break;
-                    }
-                }
-
-                // If this list of passwords already exists, increment its count
-                if let Some(key_found) = key_found {
-                    *counts_in_path.get_mut(&key_found).unwrap() += 1;
-                } else {
-                    // Otherwise, insert it with an initial value of 1
-                    let mut new_key = photo_passwords
-                        .iter()
-                        .map(|&s| s.clone())
-                        .collect::<Vec<String>>();
-                    new_key.sort_unstable();
-                    counts_in_path.insert(new_key, 1);
-                }
-            }
-
-            // Add the counts for this path to the main hashmap
-            counts_lock.insert(path.clone(), counts_in_path);
+            counts_lock.insert(path.clone(), count_photos(photos));
         }
     }
 
@@ -1503,6 +2155,69 @@ impl PhotosGuard<'_> {
     }
 }
 
+/// A full-text search and some metadata filters requested on the photos of a path, by the grid
+/// and by the API
+pub struct PhotoQuery {
+    /// UIDs of the photos matching the search, if any
+    results: Option<HashSet<UID>>,
+    filter: PhotoFilter,
+}
+
+/// Kinds of error when preparing a PhotoQuery
+pub enum PhotoQueryError {
+    /// A filter has an invalid value, described by this message
+    InvalidFilters(String),
+    /// The full-text search failed, which has already been logged
+    SearchFailed,
+}
+
+impl PhotoQuery {
+    /// Check the filters read from the query parameters and run the search in the full-text
+    /// index, or return None if neither a search nor a filter is set, in which case the photos
+    /// should be read with `Gallery::read()`
+    pub async fn new(
+        db_conn: &mut SqliteConnection,
+        search: Option<&str>,
+        filter: Result<PhotoFilter, form::Errors<'_>>,
+    ) -> Result<Option<Self>, PhotoQueryError> {
+        let filter = filter.map_err(|errors| {
+            PhotoQueryError::InvalidFilters(format!(
+                "Invalid filters : {}",
+                filter::describe_errors(&errors)
+            ))
+        })?;
+        let results = match search.map(str::trim).filter(|s| !s.is_empty()) {
+            Some(search) => match db::search_photos(db_conn, search).await {
+                Ok(results) => Some(results),
+                Err(error) => {
+                    eprintln!("Error : unable to search for \"{search}\" : {error}");
+                    return Err(PhotoQueryError::SearchFailed);
+                }
+            },
+            None => None,
+        };
+        if results.is_none() && filter.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Self { results, filter }))
+    }
+
+    /// Check if the given photo matches both the search and the filters
+    fn matches(&self, photo: &Photo) -> bool {
+        self.results
+            .as_ref()
+            .is_none_or(|results| results.contains(&photo.uid))
+            && self.filter.matches(photo)
+    }
+}
+
+/// Page of the photos of a path that match a query, obtained with `Gallery::read_matching()`
+pub struct MatchingPhotos {
+    pub start: usize,
+    pub total: usize,
+    pub photos: Vec<Arc<Photo>>,
+}
+
 /// RAII read lock on the gallery, obtained with `Gallery::read()`. Every access on the gallery's photos
 /// must pass through an instance of this lock. Concurrent reads are allowed, which means access will
 /// be immediately granted as long as the gallery is not reloading.
@@ -1566,19 +2281,9 @@ impl<'a> Iterator for GalleryReadIterator<'a> {
             if let Some(photo) = photos.get(self.lock.start + self.index) {
                 self.index += 1;
 
-                // Check if this photo requires some passwords
-                for (required_password_path, required_password) in &photo.passwords {
-                    if let Some(provided_password) =
-                        self.lock.provided_passwords.get(required_password_path)
-                    {
-                        if required_password != provided_password {
-                            // This required password is invalid in the user's session
-                            continue 'find_a_photo;
-                        }
-                    } else {
-                        // This required password is not found in the user's session
-                        continue 'find_a_photo;
-                    }
+                // Check if this photo requires some passwords that are missing or invalid in the user's session
+                if !photo.is_granted(&self.lock.provided_passwords) {
+                    continue 'find_a_photo;
                 }
 
                 self.counter += 1;
@@ -1593,6 +2298,444 @@ impl<'a> Iterator for GalleryReadIterator<'a> {
     }
 }
 
+/// Return the config of the given directory of the photos folder, based on the config of its parent
+/// directory and the local config file it may contain
+fn subdir_config(parent_config: &Table, full_path: &PathBuf, rel_path: &Path) -> Table {
+    let mut cfg = parent_config.clone();
+    cfg.remove("HIDDEN"); // These settings don't propagate from the parent
+    if rel_path != Path::new("") {
+        cfg.remove("PASSWORD");
+    }
+    Config::update_with_subdir(full_path, &mut cfg);
+    cfg
+}
+
+/// Initialize the stack of configs used by `Gallery::load_rec()` to load the given directory of the
+/// photos folder : the main config followed by the configs of every parent directory
+fn configs_stack(config: &Config, rel_path: &Path) -> Vec<(PathBuf, Table)> {
+    let main_config = config.as_value().unwrap_or_else(|_| Table::new());
+    let mut configs_stack: Vec<(PathBuf, Table)> = vec![(PathBuf::from("[main]"), main_config)];
+    if let Some(parent) = rel_path.parent() {
+        let mut full_path = PathBuf::from(&config.PHOTOS_DIR);
+        let mut path = PathBuf::new();
+        let cfg = subdir_config(&configs_stack.last().unwrap().1, &full_path, &path);
+        configs_stack.push((path.clone(), cfg));
+        for component in parent.components() {
+            full_path.push(component);
+            path.push(component);
+            let cfg = subdir_config(&configs_stack.last().unwrap().1, &full_path, &path);
+            configs_stack.push((path.clone(), cfg));
+        }
+    }
+    configs_stack
+}
+
+/// Return the photos of the database that are in a subdirectory of the given path (or in this path
+/// itself) which has not been found in the filesystem
+async fn find_photos_in_deleted_paths(
+    config: &Config,
+    db_conn: &mut SqliteConnection,
+    rel_path: &Path,
+    paths_found: &[PathBuf],
+) -> Result<Vec<Photo>, Error> {
+    let deleted_paths = db::get_all_paths(db_conn)
+        .await?
+        .into_iter()
+        .filter(|path| path.starts_with(rel_path) && !paths_found.contains(path))
+        .collect::<Vec<PathBuf>>();
+    if deleted_paths.is_empty() {
+        return Ok(Vec::new());
+    }
+    db::get_photos_in_paths(db_conn, &deleted_paths, config).await
+}
+
+/// Aggregate the number of photos in the given list based on the exact combination of passwords
+/// they require
+fn count_photos(photos: &[CachedPhoto]) -> HashMap<Vec<String>, usize> {
+    // Aggregate of the number of photos found which require a certain list of passwords
+    let mut counts_in_path: HashMap<Vec<String>, usize> = HashMap::new();
+
+    // Process each photo in this path
+    for photo in photos {
+        // List of passwords required to access this photo
+        let mut photo_passwords = photo
+            .passwords
+            .iter()
+            .map(|(p, _)| p)
+            .collect::<Vec<&String>>();
+        photo_passwords.sort_unstable();
+
+        // Try to find this list of passwords in the current hashmap
+        let mut key_found = None;
+        for key in counts_in_path.keys() {
+            let key_pw = key.iter().collect::<Vec<&String>>();
+            if key_pw == photo_passwords {
+                key_found = Some(key.clone());
+                break;
+            }
+        }
+
+        // If this list of passwords already exists, increment its count
+        if let Some(key_found) = key_found {
+            *counts_in_path.get_mut(&key_found).unwrap() += 1;
+        } else {
+            // Otherwise, insert it with an initial value of 1
+            let mut new_key = photo_passwords
+                .iter()
+                .map(|&s| s.clone())
+                .collect::<Vec<String>>();
+            new_key.sort_unstable();
+            counts_in_path.insert(new_key, 1);
+        }
+    }
+
+    counts_in_path
+}
+
+/// Compare two paths of the gallery in the order in which their photos are loaded : a directory comes
+/// before its subdirectories, which are sorted by name
+fn compare_paths(a: &Path, b: &Path) -> Ordering {
+    let mut a_components = a.components();
+    let mut b_components = b.components();
+    loop {
+        match (a_components.next(), b_components.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_component), Some(b_component)) => {
+                match natord::compare_ignore_case(
+                    &a_component.as_os_str().to_string_lossy(),
+                    &b_component.as_os_str().to_string_lossy(),
+                ) {
+                    Ordering::Equal => (),
+                    ordering => return ordering,
+                }
+            }
+        }
+    }
+}
+
+/// Apply the differences found between the filesystem and the database : compute the hashes of the
+/// new files, detect the ones that have actually been moved or renamed, then insert, remove and move
+/// the photos in the database. Return whether the database has been modified.
+async fn sync_database(
+    config: &Config,
+    db_conn: &mut SqliteConnection,
+    mut photos_to_insert: Vec<Photo>,
+    mut photos_to_remove: Vec<Photo>,
+    progress: &ReloadProgress,
+) -> Result<bool, Error> {
+    // Read the fingerprints of the new files, which is much quicker than hashing their content
+    for photo in photos_to_insert.iter_mut() {
+        if let Err(error) = photo.read_fingerprint() {
+            eprintln!(
+                "Warning : unable to read the size and modification time of \"{}/{}\" : {}",
+                photo.path.to_string_lossy(),
+                photo.filename,
+                error
+            );
+        }
+    }
+
+    // Only hash the new files that can't be told apart from the removed ones by their fingerprint,
+    // unless every file must be hashed according to the config. Photos indexed by a previous version
+    // may have no fingerprint, in which case they can only be compared by hash. Since their hash may
+    // have been computed with another algorithm than the current one (MD5 for the oldest ones), the
+    // new files are also hashed with every algorithm used by the removed photos, in order to compare
+    // them with the same algorithm.
+    let mut algorithms = vec![config.HASH_ALGORITHM];
+    for photo in &photos_to_remove {
+        let algorithm = file_hash::algorithm_of(&photo.hash);
+        if !photo.hash.is_empty() && !algorithms.contains(&algorithm) {
+            algorithms.push(algorithm);
+        }
+    }
+    let removed_without_fingerprint = photos_to_remove
+        .iter()
+        .any(|photo| photo.fingerprint().is_none());
+    let photos_to_hash = photos_to_insert
+        .iter()
+        .enumerate()
+        .filter(|(_, photo)| {
+            if config.ALWAYS_HASH_FILES || removed_without_fingerprint {
+                return true;
+            }
+            let Some(fingerprint) = photo.fingerprint() else {
+                return !photos_to_remove.is_empty();
+            };
+            let count_matching = |photos: &Vec<Photo>| {
+                photos
+                    .iter()
+                    .filter(|other| other.fingerprint() == Some(fingerprint))
+                    .count()
+            };
+            let removed_matching = count_matching(&photos_to_remove);
+            removed_matching > 1 || (removed_matching == 1 && count_matching(&photos_to_insert) > 1)
+        })
+        .map(|(idx, _)| idx)
+        .collect::<Vec<usize>>();
+
+    // Calculate the hashes of these files in parallel background tasks. The hash computed with the
+    // current algorithm is saved in the photo, and every hash is kept for the comparisons below.
+    let mut new_hashes: Vec<Vec<String>> = vec![Vec::new(); photos_to_insert.len()];
+    if !photos_to_hash.is_empty() {
+        let now = Instant::now();
+        let n = photos_to_hash.len();
+        progress.set_phase(ReloadPhase::Hashing, Some(n));
+        let mut last_percent: usize = 0;
+        let mut tasks = JoinSet::new();
+        let mut results: Vec<(usize, Vec<String>)> = Vec::new();
+        let mut offset = 0;
+        let mut done = 0;
+        while done < n {
+            // Create up to LOADING_WORKERS background tasks
+            let batch_size = min(n - offset, config.LOADING_WORKERS);
+            for &idx in photos_to_hash.iter().skip(offset).take(batch_size) {
+                let full_path = photos_to_insert[idx].full_path.clone();
+                let algorithms = algorithms.clone();
+                tasks.spawn(async move {
+                    (idx, file_hash::calculate_all(&full_path, &algorithms).await)
+                });
+            }
+            offset += batch_size;
+
+            // Wait for these tasks to complete and add their results to the list
+            while let Some(result) = tasks.join_next().await {
+                done += 1;
+                progress.set_processed(done);
+                match result {
+                    Ok((i, Ok(hashes))) => {
+                        results.push((i, hashes));
+                        let percent: usize = done * 100 / n;
+                        if percent > last_percent {
+                            print!("\rCalculating hashes of {n} new files... {percent}%");
+                            std::io::stdout().flush().ok();
+                            last_percent = percent;
+                        }
+                    }
+                    Ok((i, Err(error))) => eprintln!(
+                        "Error : unable to compute hash for \"{}\" : {}",
+                        photos_to_insert.get(i).unwrap().filename,
+                        error
+                    ),
+                    Err(error) => eprintln!(
+                        "Error : unable to join background task while computing hash : {error}"
+                    ),
+                }
+            }
+        }
+        for (i, hashes) in results {
+            photos_to_insert.get_mut(i).unwrap().hash = hashes[0].clone();
+            new_hashes[i] = hashes;
+        }
+        println!("\nDone in {}ms", now.elapsed().as_millis());
+    }
+
+    // Detect if some of the insert/remove are actually the same file that has been moved or renamed.
+    // Files are compared by hash when it is known for both of them, otherwise by fingerprint as long
+    // as a single removed photo and a single new file share it.
+    let mut photos_to_move: Vec<(Photo, Photo)> = Vec::new();
+    if !&photos_to_insert.is_empty() && !photos_to_remove.is_empty() {
+        let mut moved_files: Vec<PathBuf> = Vec::new();
+        for (new_photo, new_hashes) in photos_to_insert.iter().zip(&new_hashes) {
+            let is_not_moved = |old_photo: &&Photo| {
+                !photos_to_move
+                    .iter()
+                    .any(|(moved_photo, _)| moved_photo.uid == old_photo.uid)
+            };
+            let same_hash = photos_to_remove
+                .iter()
+                .filter(is_not_moved)
+                .find(|old_photo| {
+                    new_hashes
+                        .iter()
+                        .any(|new_hash| file_hash::is_same(&old_photo.hash, new_hash))
+                });
+            let same_fingerprint = || {
+                let fingerprint = new_photo.fingerprint()?;
+                let mut matching = photos_to_remove
+                    .iter()
+                    .filter(|old_photo| old_photo.fingerprint() == Some(fingerprint));
+                let old_photo = matching.next()?;
+                let is_unique = matching.next().is_none()
+                    && photos_to_insert
+                        .iter()
+                        .filter(|other| other.fingerprint() == Some(fingerprint))
+                        .count()
+                        == 1;
+                let is_hash_comparable = new_hashes
+                    .iter()
+                    .any(|new_hash| file_hash::is_comparable(&old_photo.hash, new_hash));
+                (is_unique && !is_hash_comparable).then_some(old_photo)
+            };
+            if let Some(old_photo) = same_hash.or_else(same_fingerprint) {
+                photos_to_move.push((old_photo.clone(), new_photo.clone()));
+                moved_files.push(new_photo.full_path.clone());
+            }
+        }
+        photos_to_insert.retain(|photo| !moved_files.contains(&photo.full_path));
+        photos_to_remove.retain(|photo| {
+            !photos_to_move
+                .iter()
+                .any(|(moved_photo, _)| moved_photo.uid == photo.uid)
+        });
+    }
+
+    // If there were some modifications to the photos we will need to reload the photos
+    let need_to_reload =
+        !photos_to_insert.is_empty() || !photos_to_remove.is_empty() || !photos_to_move.is_empty();
+
+    // Apply detected modifications (photos added, moved, or deleted) to the database
+    if !photos_to_insert.is_empty() {
+        // Get all existing UIDs from the database
+        let mut existing_uids = db::get_existing_uids(db_conn).await?;
+
+        // Generate a new UID for each photo
+        for photo in photos_to_insert.iter_mut() {
+            photo.uid = UID::new(&existing_uids);
+            existing_uids.push(photo.uid.clone());
+        }
+
+        // Since we will handle the list from last to first (because of pop()) we need to reverse
+        // the array first to keep the original order
+        photos_to_insert.reverse();
+
+        // Parse the photos' metadata and, if set in the config, generate their thumbnails
+        progress.set_phase(ReloadPhase::Parsing, Some(photos_to_insert.len()));
+        let pre_generate_thumbnails = config.PRE_GENERATE_THUMBNAILS;
+        let thumbnail_max_size = ResizedType::Thumbnail.max_size(config);
+        let thumbnail_quality = ResizedType::Thumbnail.quality(config);
+        let large_size_max_size = ResizedType::Large.max_size(config);
+        let large_size_quality = ResizedType::Large.quality(config);
+        let mut photos_to_insert_in_db: Vec<Photo> = Vec::new();
+        while !photos_to_insert.is_empty() {
+            // Spawn background tasks to parallelize computation, up to the LOADING_WORKERS setting in the config
+            let mut counter = 0;
+            let mut tasks = Vec::with_capacity(config.LOADING_WORKERS);
+            while let Some(mut photo) = photos_to_insert.pop() {
+                // Background task which takes ownership of the photo object
+                let cache_dir = config.CACHE_DIR.clone();
+                let image_format = config.RESIZED_IMAGE_FORMAT;
+                let decoder_settings = DecoderSettings::from_config(config);
+                tasks.push(tokio::spawn(async move {
+                    // Parse the metadata
+                    photo
+                        .parse_metadata(true, &decoder_settings)
+                        .await
+                        .map_err(|e| {
+                            eprintln!(
+                                "Error : unable to open \"{}/{}\" : {}",
+                                photo.path.to_string_lossy(),
+                                photo.filename,
+                                e
+                            );
+                            e
+                        })
+                        .ok(); // Ignore error after printing it
+
+                    // Generate thumbnails
+                    if pre_generate_thumbnails {
+                        photo
+                            .create_resized_from_params(
+                                ResizedType::Thumbnail,
+                                image_format,
+                                cache_dir.clone(),
+                                thumbnail_max_size,
+                                thumbnail_quality,
+                                &decoder_settings,
+                            )
+                            .await
+                            .ok();
+                        photo
+                            .create_resized_from_params(
+                                ResizedType::Large,
+                                image_format,
+                                cache_dir.clone(),
+                                large_size_max_size,
+                                large_size_quality,
+                                &decoder_settings,
+                            )
+                            .await
+                            .ok();
+                    }
+
+                    // Return ownership of the photo to the main thread
+                    photo
+                }));
+
+                counter += 1;
+                if counter >= config.LOADING_WORKERS {
+                    // We have enough workers for now
+                    break;
+                }
+            }
+
+            // Wait for the background tasks to finish
+            for task in tasks.into_iter() {
+                if let Ok(photo) = task.await {
+                    photos_to_insert_in_db.push(photo);
+                }
+            }
+            progress.set_processed(photos_to_insert_in_db.len());
+        }
+
+        // Log the list of photos to insert
+        println!(
+            "Inserting {} photo(s) into the database : {}",
+            photos_to_insert_in_db.len(),
+            photos_to_insert_in_db
+                .iter()
+                .map(|photo| format!("\"{}/{}\"", photo.path.to_string_lossy(), photo.filename))
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+
+        // Insert them into the database
+        progress.set_phase(ReloadPhase::Saving, None);
+        db::insert_photos(db_conn, &photos_to_insert_in_db).await?;
+    } else if need_to_reload {
+        progress.set_phase(ReloadPhase::Saving, None);
+    }
+    if !photos_to_remove.is_empty() {
+        // Log the list of photos to remove
+        println!(
+            "Removing {} photo(s) from the database : {}",
+            photos_to_remove.len(),
+            photos_to_remove
+                .iter()
+                .map(|photo| format!("\"{}/{}\"", photo.path.to_string_lossy(), photo.filename))
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+
+        // Remove them from the database
+        db::remove_photos(db_conn, &photos_to_remove).await?;
+    }
+    if !photos_to_move.is_empty() {
+        // Log the list of photos to rename/move
+        println!(
+            "Renaming/moving {} photo(s) in the database : {}",
+            photos_to_move.len(),
+            photos_to_move
+                .iter()
+                .map(|pair| format!(
+                    "\"{}/{}\" -> \"{}/{}\"",
+                    pair.0.path.to_string_lossy(),
+                    pair.0.filename,
+                    pair.1.path.to_string_lossy(),
+                    pair.1.filename
+                ))
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+
+        // Update the database
+        db::move_photos(db_conn, &photos_to_move).await?;
+    }
+
+    Ok(need_to_reload)
+}
+
 /// Fairing callback used to load/sync the photos with the database at startup
 pub async fn init(rocket: Rocket<rocket::Build>) -> fairing::Result {
     // Make sure the database has been initialized (fairings have been attached in the correct order)
@@ -1603,12 +2746,15 @@ pub async fn init(rocket: Rocket<rocket::Build>) -> fairing::Result {
                     .state::<Config>()
                     .expect("Error : unable to obtain the config");
                 let gallery = rocket
-                    .state::<Gallery>()
+                    .state::<Arc<Gallery>>()
                     .expect("Error : unable to obtain the gallery");
 
                 println!("Loading photos...");
                 let now = Instant::now();
-                match gallery.load(config, &mut db_conn).await {
+                gallery.progress.try_start(0);
+                let result = gallery.load(config, &mut db_conn).await;
+                gallery.progress.finish(&result, gallery.len().await);
+                match result {
                     Ok(_) => {
                         println!(
                             "Loaded {} photos successfully in {}ms",
@@ -1845,13 +2991,225 @@ async fn list_subdirs_in_cache(path: &PathBuf, config: &Config) -> Result<Vec<St
     Ok(subdirs)
 }
 
-/// Calculate and return the MD5 hash of the file located at the given path
-async fn calculate_file_md5(path: &PathBuf) -> Result<String, Error> {
-    let file_content = fs::read(path)
-        .await
-        .map_err(|e| Error::FileError(e, path.clone()))?;
-    let hash = Md5::digest(file_content);
-    Ok(format!("{hash:x}"))
+/// Group camera RAW files with the image that has the same name, if any (for instance `IMG_0001.CR2`
+/// and `IMG_0001.JPG`), so that they are displayed as a single photo. Returns the list of filenames
+/// that must be indexed as photos, in the same order as the given list, and the list of RAW files
+/// attached to some of them.
+fn pair_raw_files(filenames: Vec<String>) -> (Vec<String>, HashMap<String, Vec<String>>) {
+    fn stem(filename: &str) -> String {
+        Path::new(filename)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    }
+
+    // Find the first image that is not a RAW file for each name
+    let mut images_by_stem: HashMap<String, &String> = HashMap::new();
+    for filename in &filenames {
+        if !raw::is_raw_file(Path::new(filename)) {
+            images_by_stem.entry(stem(filename)).or_insert(filename);
+        }
+    }
+
+    // Attach RAW files to their sibling
+    let mut photos: Vec<String> = Vec::new();
+    let mut alternate_files: HashMap<String, Vec<String>> = HashMap::new();
+    for filename in &filenames {
+        if raw::is_raw_file(Path::new(filename)) {
+            if let Some(&image) = images_by_stem.get(&stem(filename)) {
+                alternate_files
+                    .entry(image.clone())
+                    .or_default()
+                    .push(filename.clone());
+                continue;
+            }
+        }
+        photos.push(filename.clone());
+    }
+
+    (photos, alternate_files)
+}
+
+/// Convert the given image to 8-bit RGB. If the image has an alpha channel, its pixels are blended
+/// onto the given background color instead of simply discarding the transparency information.
+fn flatten_image(img: DynamicImage, background: Rgb<u8>) -> RgbImage {
+    match img {
+        DynamicImage::ImageRgb8(pixels) => pixels,
+        img if img.color().has_alpha() => {
+            let img_rgba8 = img.into_rgba8();
+            let mut img_rgb8 = RgbImage::new(img_rgba8.width(), img_rgba8.height());
+            for (src, dst) in img_rgba8.pixels().zip(img_rgb8.pixels_mut()) {
+                let alpha = src[3] as u32;
+                for c in 0..3 {
+                    dst[c] = ((src[c] as u32 * alpha + background[c] as u32 * (255 - alpha) + 127)
+                        / 255) as u8;
+                }
+            }
+            img_rgb8
+        }
+        img => img.into_rgb8(),
+    }
+}
+
+/// Read the orientation tag of the given EXIF data, as a value between 1 and 8
+fn read_orientation(exif: &exif::Exif) -> Option<u32> {
+    exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+        .filter(|orientation| (1..=8).contains(orientation))
+}
+
+/// Read the GPS position of the given EXIF data, as a (latitude, longitude) pair in decimal degrees
+fn read_gps_position(exif: &exif::Exif) -> Option<(f64, f64)> {
+    let latitude = read_gps_coordinate(
+        exif,
+        exif::Tag::GPSLatitude,
+        exif::Tag::GPSLatitudeRef,
+        b'S',
+    )?;
+    let longitude = read_gps_coordinate(
+        exif,
+        exif::Tag::GPSLongitude,
+        exif::Tag::GPSLongitudeRef,
+        b'W',
+    )?;
+    if latitude.abs() > 90.0 || longitude.abs() > 180.0 {
+        return None;
+    }
+    if latitude == 0.0 && longitude == 0.0 {
+        // Written by some cameras when they didn't get a fix
+        return None;
+    }
+    Some((latitude, longitude))
+}
+
+/// Read a GPS coordinate stored as degrees, minutes and seconds, and convert it to decimal degrees.
+/// The coordinate is negative if its reference (hemisphere) is `negative_ref`.
+fn read_gps_coordinate(
+    exif: &exif::Exif,
+    tag: exif::Tag,
+    ref_tag: exif::Tag,
+    negative_ref: u8,
+) -> Option<f64> {
+    let exif::Value::Rational(values) = &exif.get_field(tag, exif::In::PRIMARY)?.value else {
+        return None;
+    };
+    let value = values
+        .iter()
+        .zip([1.0, 60.0, 3600.0])
+        .map(|(value, divisor)| value.to_f64() / divisor)
+        .sum::<f64>();
+    if values.is_empty() || !value.is_finite() {
+        return None;
+    }
+    let reference = exif
+        .get_field(ref_tag, exif::In::PRIMARY)
+        .and_then(|field| match &field.value {
+            exif::Value::Ascii(strings) => strings.first().and_then(|s| s.first()).copied(),
+            _ => None,
+        });
+    match reference {
+        Some(reference) if reference.eq_ignore_ascii_case(&negative_ref) => Some(-value),
+        _ => Some(value),
+    }
+}
+
+/// Read the GPS altitude of the given EXIF data, in meters relative to the sea level
+fn read_gps_altitude(exif: &exif::Exif) -> Option<f64> {
+    let exif::Value::Rational(values) = &exif
+        .get_field(exif::Tag::GPSAltitude, exif::In::PRIMARY)?
+        .value
+    else {
+        return None;
+    };
+    let altitude = values.first()?.to_f64();
+    if !altitude.is_finite() {
+        return None;
+    }
+    let below_sea_level = exif
+        .get_field(exif::Tag::GPSAltitudeRef, exif::In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+        == Some(1);
+    Some(if below_sea_level { -altitude } else { altitude })
+}
+
+/// Read again the EXIF data of the photos that have been indexed by a previous version which didn't
+/// know every field that is currently supported. For the photos whose orientation was ignored, also
+/// fix their dimensions and remove their resized versions from the cache so that they will be
+/// generated again with the correct orientation.
+async fn migrate_exif(config: &Config, db_conn: &mut SqliteConnection) -> Result<(), Error> {
+    let photos = db::get_photos_with_outdated_exif(db_conn, EXIF_VERSION, config).await?;
+    if photos.is_empty() {
+        return Ok(());
+    }
+    println!(
+        "Reading the EXIF data of {} photo(s) indexed by a previous version...",
+        photos.len()
+    );
+    let now = Instant::now();
+
+    // Read the files in background tasks, up to LOADING_WORKERS at a time, and update the database
+    // as their results come in
+    let mut photos = photos.into_iter();
+    let mut tasks = JoinSet::new();
+    loop {
+        for photo in photos.by_ref().take(config.LOADING_WORKERS.max(1)) {
+            tasks.spawn(migrate_photo_exif(photo, config.CACHE_DIR.clone()));
+        }
+        if tasks.is_empty() {
+            break;
+        }
+        while let Some(result) = tasks.join_next().await {
+            match result {
+                Ok(photo) => {
+                    db::update_exif_metadata(db_conn, &photo).await?;
+                    db::update_all_metadata(db_conn, &photo).await?;
+                }
+                Err(error) => eprintln!(
+                    "Error : unable to join background task while reading EXIF data : {error}"
+                ),
+            }
+        }
+    }
+    println!("Done in {}ms", now.elapsed().as_millis());
+    Ok(())
+}
+
+/// Read again the EXIF data of the given photo for `migrate_exif()`, and return it updated
+async fn migrate_photo_exif(mut photo: Photo, cache_dir: String) -> Photo {
+    let orientation_known = photo.orientation != 0;
+    photo.parse_descriptive_metadata().await;
+    if photo.media_type == MediaType::Photo {
+        if let Err(Error::EXIFParserError(error, _)) = photo.parse_exif() {
+            match error {
+                exif::Error::NotFound(_) => (), // Ignore
+                _ => eprintln!(
+                    "Warning : unable to parse EXIF data from \"{}\" : {}",
+                    &photo.full_path.display(),
+                    error
+                ),
+            }
+        }
+    }
+    photo.parse_date_taken_fallback();
+    if !orientation_known {
+        if photo.orientation == 0 {
+            photo.orientation = 1;
+        }
+        if photo.orientation != 1 {
+            if photo.orientation_swaps_dimensions() {
+                (photo.width, photo.height) = (photo.height, photo.width);
+            }
+            photo
+                .remove_resized(&cache_dir)
+                .await
+                .inspect_err(|error| {
+                    eprintln!("Warning : unable to remove an obsolete resized photo : {error}")
+                })
+                .ok();
+        }
+    }
+    photo.exif_version = EXIF_VERSION;
+    photo
 }
 
 /// Kinds of resized versions of photos generated in the cache folder
@@ -1886,6 +3244,88 @@ impl ResizedType {
     }
 }
 
+/// Kinds of media files that can be indexed in the gallery
+#[derive(Serialize, ToSchema, Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaType {
+    /// A still image, including camera RAW files
+    #[default]
+    Photo,
+
+    /// A video clip
+    Video,
+}
+
+impl MediaType {
+    /// Name of this media type, as stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MediaType::Photo => "photo",
+            MediaType::Video => "video",
+        }
+    }
+}
+
+impl From<&str> for MediaType {
+    fn from(value: &str) -> Self {
+        match value {
+            "video" => MediaType::Video,
+            _ => MediaType::Photo,
+        }
+    }
+}
+
+/// Settings extracted from the config that are required to decode photos and videos, which can be
+/// moved into background tasks
+#[derive(Clone, Debug)]
+pub struct DecoderSettings {
+    /// See `Config::transparency_background()`
+    pub background: Rgb<u8>,
+
+    /// See `Config::FFMPEG_PATH`
+    pub ffmpeg_path: String,
+
+    /// See `Config::FFPROBE_PATH`
+    pub ffprobe_path: String,
+}
+
+impl DecoderSettings {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            background: config.transparency_background(),
+            ffmpeg_path: config.FFMPEG_PATH.clone(),
+            ffprobe_path: config.FFPROBE_PATH.clone(),
+        }
+    }
+}
+
+/// Versions of a photo that can be downloaded
+#[derive(FromFormField, Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum DownloadVariant {
+    /// The file this photo has been indexed from
+    #[default]
+    Original,
+
+    /// A JPEG version of the photo : either the file itself, or the preview embedded in a RAW file
+    Jpeg,
+
+    /// The camera RAW file of the photo, either the file itself or an alternate file
+    Raw,
+}
+
+/// Format a DownloadVariant to be used as a URI's query parameter
+impl UriDisplay<Query> for DownloadVariant {
+    fn fmt(&self, f: &mut Formatter<'_, Query>) -> std::fmt::Result {
+        f.write_raw(match self {
+            DownloadVariant::Original => "original",
+            DownloadVariant::Jpeg => "jpeg",
+            DownloadVariant::Raw => "raw",
+        })
+    }
+}
+
+impl_from_uri_param_identity!([Query] DownloadVariant);
+
 /// Available image formats for cache files
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, Default)]
 #[allow(clippy::upper_case_acronyms)]
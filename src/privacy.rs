@@ -0,0 +1,524 @@
+use crate::tiff::{self, TiffReader, MAX_IFDS, TAG_SUB_IFDS};
+use crate::xmp::find_bytes;
+use regex::bytes::Regex;
+use rocket::serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+use utoipa::ToSchema;
+
+/// Signature of the EXIF blocks embedded in JPEG (APP1 segment), WEBP and HEIF files
+const EXIF_SIGNATURE: &[u8] = b"Exif\0\0";
+
+/// Signature of PNG files
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+// TIFF tags that point to other IFDs
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+
+// TIFF tags that contain serial numbers or other identifying data
+const TAG_IMAGE_UNIQUE_ID: u16 = 0xa420;
+const TAG_CAMERA_OWNER_NAME: u16 = 0xa430;
+const TAG_BODY_SERIAL_NUMBER: u16 = 0xa431;
+const TAG_LENS_SERIAL_NUMBER: u16 = 0xa435;
+const TAG_DNG_CAMERA_SERIAL_NUMBER: u16 = 0xc62f;
+
+// TIFF tags that contain proprietary data
+const TAG_MAKER_NOTE: u16 = 0x927c;
+const TAG_DNG_PRIVATE_DATA: u16 = 0xc634;
+
+/// Names of the EXIF fields and XMP properties that contain serial numbers or other identifying data
+const SERIAL_NUMBER_FIELDS: [&str; 7] = [
+    "SerialNumber",
+    "LensSerialNumber",
+    "OwnerName",
+    "BodySerialNumber",
+    "CameraOwnerName",
+    "ImageUniqueID",
+    "CameraSerialNumber",
+];
+
+/// Names of the EXIF fields that contain proprietary data
+const MAKER_NOTES_FIELDS: [&str; 2] = ["MakerNote", "DNGPrivateData"];
+
+/// XMP properties that contain GPS data
+static XMP_GPS_REGEX: LazyLock<Regex> = LazyLock::new(|| xmp_property_regex("GPS[A-Za-z]*"));
+
+/// XMP properties that contain serial numbers or other identifying data
+static XMP_SERIAL_NUMBERS_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| xmp_property_regex(&SERIAL_NUMBER_FIELDS.join("|")));
+
+/// Groups of metadata that can be removed from the files sent to users
+#[derive(Debug, Serialize, Deserialize, ToSchema, Copy, Clone, PartialEq)]
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+pub enum MetadataGroup {
+    /// Position, altitude, direction and timestamp recorded by the GPS receiver
+    GPS,
+    /// Serial numbers of the camera body and lens, name of the owner, and unique ID of the image
+    SERIAL_NUMBERS,
+    /// Proprietary data written by the manufacturer of the camera, which usually include its
+    /// serial number
+    MAKER_NOTES,
+}
+
+impl MetadataGroup {
+    /// Every group of metadata
+    pub const ALL: [MetadataGroup; 3] = [
+        MetadataGroup::GPS,
+        MetadataGroup::SERIAL_NUMBERS,
+        MetadataGroup::MAKER_NOTES,
+    ];
+
+    /// Short name of this group, used in the names of cache files
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetadataGroup::GPS => "gps",
+            MetadataGroup::SERIAL_NUMBERS => "serial",
+            MetadataGroup::MAKER_NOTES => "makernotes",
+        }
+    }
+}
+
+/// Kind of the data described by an IFD
+#[derive(Copy, Clone, PartialEq)]
+enum IfdKind {
+    Image,
+    Exif,
+    Gps,
+}
+
+/// Remove the given groups of metadata from the content of an image file, and return true if
+/// something has been removed. Metadata are blanked out (overwritten with zeros, or with spaces in
+/// XMP packets) instead of being removed from the file, which keeps every offset valid and works
+/// the same way for every TIFF-based format, including camera RAW files.
+pub fn strip(data: &mut [u8], groups: &[MetadataGroup]) -> bool {
+    if groups.is_empty() {
+        return false;
+    }
+    let mut modified = false;
+
+    // TIFF and RAW files are entirely based on a TIFF structure
+    if tiff::is_tiff_header(data) {
+        modified |= strip_tiff(&mut data[..], groups);
+    }
+
+    // Other formats embed one or several EXIF blocks (including the JPEG previews inside RAW files)
+    let mut offset = 0;
+    while let Some(position) = find_bytes(&data[offset..], EXIF_SIGNATURE) {
+        let start = offset + position + EXIF_SIGNATURE.len();
+        if tiff::is_tiff_header(&data[start..]) {
+            modified |= strip_tiff(&mut data[start..], groups);
+        }
+        offset = start;
+    }
+
+    // PNG and WEBP files may also store EXIF blocks without the signature
+    for start in find_png_chunks(data, b"eXIf")
+        .into_iter()
+        .chain(find_riff_chunks(data, b"EXIF"))
+    {
+        if tiff::is_tiff_header(&data[start..]) {
+            modified |= strip_tiff(&mut data[start..], groups);
+        }
+    }
+
+    // XMP packets repeat some of the EXIF fields
+    modified |= strip_xmp(data, groups);
+
+    // The checksums of the PNG chunks that have been modified must be updated
+    if modified && data.starts_with(PNG_SIGNATURE) {
+        update_png_checksums(data);
+    }
+
+    modified
+}
+
+/// Check if the EXIF field or XMP property with the given name (such as "GPSLatitude" or
+/// "aux:SerialNumber") belongs to one of the given groups of metadata
+pub fn is_stripped_field(name: &str, groups: &[MetadataGroup]) -> bool {
+    // XMP properties are prefixed by their namespace
+    let name = name.rsplit(':').next().unwrap_or(name);
+    groups.iter().any(|group| match group {
+        MetadataGroup::GPS => name.starts_with("GPS"),
+        MetadataGroup::SERIAL_NUMBERS => SERIAL_NUMBER_FIELDS.contains(&name),
+        MetadataGroup::MAKER_NOTES => MAKER_NOTES_FIELDS.contains(&name),
+    })
+}
+
+/// Blank out the given groups of metadata inside the TIFF structure that starts at the beginning of
+/// `data`. Every offset inside this structure is relative to its beginning.
+fn strip_tiff(data: &mut [u8], groups: &[MetadataGroup]) -> bool {
+    // Find the ranges to blank out first, while the data is borrowed by the reader. Errors are
+    // ignored, as they only mean that a part of the structure can't be read.
+    let mut ranges: Vec<Range<u64>> = Vec::new();
+    let Ok(mut reader) = TiffReader::new(Cursor::new(&data[..]), PathBuf::new()) else {
+        return false;
+    };
+    let little_endian = reader.little_endian;
+    let mut ifds_to_visit: Vec<(u64, IfdKind)> = vec![(reader.first_ifd_offset, IfdKind::Image)];
+    let mut visited_ifds: Vec<u64> = Vec::new();
+    while let Some((offset, kind)) = ifds_to_visit.pop() {
+        if offset == 0 || visited_ifds.contains(&offset) || visited_ifds.len() >= MAX_IFDS {
+            continue;
+        }
+        visited_ifds.push(offset);
+        let Ok(ifd) = reader.read_ifd(offset) else {
+            continue;
+        };
+
+        for entry in &ifd.entries {
+            let strip_value = match (kind, entry.tag) {
+                (IfdKind::Image, TAG_EXIF_IFD) => {
+                    ifds_to_visit.push((entry.value_offset(little_endian), IfdKind::Exif));
+                    false
+                }
+                (IfdKind::Image, TAG_GPS_IFD) => {
+                    ifds_to_visit.push((entry.value_offset(little_endian), IfdKind::Gps));
+                    false
+                }
+                (IfdKind::Image, TAG_SUB_IFDS) => {
+                    for ifd in reader.read_values(entry).unwrap_or_default() {
+                        ifds_to_visit.push((ifd, IfdKind::Image));
+                    }
+                    false
+                }
+                (IfdKind::Image, TAG_DNG_CAMERA_SERIAL_NUMBER)
+                | (
+                    IfdKind::Exif,
+                    TAG_IMAGE_UNIQUE_ID
+                    | TAG_CAMERA_OWNER_NAME
+                    | TAG_BODY_SERIAL_NUMBER
+                    | TAG_LENS_SERIAL_NUMBER,
+                ) => groups.contains(&MetadataGroup::SERIAL_NUMBERS),
+                (IfdKind::Image, TAG_DNG_PRIVATE_DATA) | (IfdKind::Exif, TAG_MAKER_NOTE) => {
+                    groups.contains(&MetadataGroup::MAKER_NOTES)
+                }
+                (IfdKind::Gps, _) => groups.contains(&MetadataGroup::GPS),
+                _ => false,
+            };
+            if strip_value {
+                ranges.push(entry.value_range(little_endian));
+            }
+        }
+
+        match kind {
+            // Follow the chain of IFDs of the images
+            IfdKind::Image => ifds_to_visit.push((ifd.next_offset, IfdKind::Image)),
+            // Empty the GPS IFD entirely : its number of entries is set to zero, which is followed
+            // by a null offset to the next IFD
+            IfdKind::Gps if groups.contains(&MetadataGroup::GPS) && !ifd.entries.is_empty() => {
+                ranges.push(ifd.offset..ifd.entries_end());
+            }
+            _ => (),
+        }
+    }
+
+    let mut modified = false;
+    for range in ranges {
+        let start = range.start.min(data.len() as u64) as usize;
+        let end = range.end.min(data.len() as u64) as usize;
+        if start < end {
+            data[start..end].fill(0);
+            modified = true;
+        }
+    }
+    modified
+}
+
+/// Blank out the properties corresponding to the given groups of metadata in every XMP packet
+/// found in `data`, by replacing them with spaces
+fn strip_xmp(data: &mut [u8], groups: &[MetadataGroup]) -> bool {
+    let mut regexes: Vec<&Regex> = Vec::new();
+    if groups.contains(&MetadataGroup::GPS) {
+        regexes.push(&XMP_GPS_REGEX);
+    }
+    if groups.contains(&MetadataGroup::SERIAL_NUMBERS) {
+        regexes.push(&XMP_SERIAL_NUMBERS_REGEX);
+    }
+    if regexes.is_empty() {
+        return false;
+    }
+
+    let mut modified = false;
+    let mut offset = 0;
+    while let Some(start) = find_bytes(&data[offset..], b"<x:xmpmeta").map(|p| offset + p) {
+        let Some(end) = find_bytes(&data[start..], b"</x:xmpmeta>").map(|p| start + p) else {
+            break;
+        };
+        let ranges = regexes
+            .iter()
+            .flat_map(|regex| regex.find_iter(&data[start..end]))
+            .map(|m| start + m.start()..start + m.end())
+            .collect::<Vec<_>>();
+        for range in ranges {
+            data[range].fill(b' ');
+            modified = true;
+        }
+        offset = end;
+    }
+    modified
+}
+
+/// Build a regex that matches the XMP properties with the given names (an alternation of patterns).
+/// Properties are either written as attributes or as elements with a simple value.
+fn xmp_property_regex(names: &str) -> Regex {
+    Regex::new(&format!(
+        r#"\s[A-Za-z][\w.-]*:(?:{names})\s*=\s*(?:"[^"]*"|'[^']*')|<[A-Za-z][\w.-]*:(?:{names})\b[^>]*?(?:/>|>[^<]*</[A-Za-z][\w.-]*:(?:{names})\s*>)"#
+    ))
+    .unwrap()
+}
+
+/// Return the offsets of the data of the chunks of the given type in a PNG file
+fn find_png_chunks(data: &[u8], chunk_type: &[u8]) -> Vec<usize> {
+    let mut chunks = Vec::new();
+    if !data.starts_with(PNG_SIGNATURE) {
+        return chunks;
+    }
+    let mut offset = PNG_SIGNATURE.len();
+    while offset + 8 <= data.len() {
+        let length = u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+        if &data[offset + 4..offset + 8] == chunk_type {
+            chunks.push(offset + 8);
+        }
+        offset += 12 + length;
+    }
+    chunks
+}
+
+/// Recalculate the checksum of every chunk of a PNG file
+fn update_png_checksums(data: &mut [u8]) {
+    let mut offset = PNG_SIGNATURE.len();
+    while offset + 8 <= data.len() {
+        let length = u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+        let crc_offset = offset + 8 + length;
+        if crc_offset + 4 > data.len() {
+            break;
+        }
+        let crc = crc32fast::hash(&data[offset + 4..crc_offset]);
+        data[crc_offset..crc_offset + 4].copy_from_slice(&crc.to_be_bytes());
+        offset = crc_offset + 4;
+    }
+}
+
+/// Return the offsets of the data of the chunks of the given type in a RIFF file, such as WEBP
+fn find_riff_chunks(data: &[u8], chunk_type: &[u8]) -> Vec<usize> {
+    let mut chunks = Vec::new();
+    if !data.starts_with(b"RIFF") || data.get(8..12) != Some(b"WEBP") {
+        return chunks;
+    }
+    let mut offset = 12;
+    while offset + 8 <= data.len() {
+        let length = u32::from_le_bytes(data[offset + 4..offset + 8].try_into().unwrap()) as usize;
+        if &data[offset..offset + 4] == chunk_type {
+            chunks.push(offset + 8);
+        }
+        // Chunks are padded to an even size
+        offset += 8 + length + length % 2;
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageEncoder, RgbImage};
+    use std::io::Cursor;
+
+    const SERIAL_NUMBER: &[u8] = b"SN12345678";
+    const XMP_GPS_VALUE: &[u8] = b"48,51.5N";
+
+    /// Build a little-endian TIFF structure with a camera make in IFD0, a body serial number in the
+    /// EXIF IFD and a latitude in the GPS IFD
+    fn tiff_fixture() -> Vec<u8> {
+        fn entry(tiff: &mut Vec<u8>, tag: u16, field_type: u16, count: u32, value: u32) {
+            tiff.extend(tag.to_le_bytes());
+            tiff.extend(field_type.to_le_bytes());
+            tiff.extend(count.to_le_bytes());
+            tiff.extend(value.to_le_bytes());
+        }
+        let make = b"FUJIFILM\0";
+        let mut serial = SERIAL_NUMBER.to_vec();
+        serial.push(0);
+
+        // Header (8 bytes), IFD0 (42 bytes), EXIF IFD (18 bytes), GPS IFD (30 bytes), then values
+        let (ifd0, exif_ifd, gps_ifd) = (8u32, 50, 68);
+        let make_offset = 98u32;
+        let serial_offset = make_offset + make.len() as u32;
+        let latitude_offset = serial_offset + serial.len() as u32;
+
+        let mut tiff = b"II*\0".to_vec();
+        tiff.extend(ifd0.to_le_bytes());
+        tiff.extend(3u16.to_le_bytes());
+        entry(&mut tiff, 0x010f, 2, make.len() as u32, make_offset);
+        entry(&mut tiff, TAG_EXIF_IFD, 4, 1, exif_ifd);
+        entry(&mut tiff, TAG_GPS_IFD, 4, 1, gps_ifd);
+        tiff.extend(0u32.to_le_bytes());
+        tiff.extend(1u16.to_le_bytes());
+        entry(
+            &mut tiff,
+            TAG_BODY_SERIAL_NUMBER,
+            2,
+            serial.len() as u32,
+            serial_offset,
+        );
+        tiff.extend(0u32.to_le_bytes());
+        tiff.extend(2u16.to_le_bytes());
+        entry(&mut tiff, 0x0001, 2, 2, u32::from_le_bytes(*b"N\0\0\0"));
+        entry(&mut tiff, 0x0002, 5, 3, latitude_offset);
+        tiff.extend(0u32.to_le_bytes());
+        assert_eq!(tiff.len() as u32, make_offset);
+        tiff.extend(make);
+        tiff.extend(&serial);
+        for value in [48u32, 1, 51, 1, 30, 1] {
+            tiff.extend(value.to_le_bytes());
+        }
+        tiff
+    }
+
+    /// An XMP packet with GPS data, stored both as an attribute and as an element, and a serial number
+    fn xmp_fixture() -> Vec<u8> {
+        format!(
+            r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"><rdf:Description xmlns:exif="http://ns.adobe.com/exif/1.0/" xmlns:aux="http://ns.adobe.com/exif/1.0/aux/" xmlns:tiff="http://ns.adobe.com/tiff/1.0/" tiff:Make="FUJIFILM" aux:SerialNumber="{}" exif:GPSLatitude="{}"><exif:GPSLongitude>2,21.0E</exif:GPSLongitude></rdf:Description></rdf:RDF></x:xmpmeta>"#,
+            String::from_utf8_lossy(SERIAL_NUMBER),
+            String::from_utf8_lossy(XMP_GPS_VALUE),
+        )
+        .into_bytes()
+    }
+
+    fn image_fixture() -> RgbImage {
+        RgbImage::from_fn(8, 8, |x, y| image::Rgb([x as u8 * 30, y as u8 * 30, 128]))
+    }
+
+    fn jpeg_fixture() -> Vec<u8> {
+        let mut jpeg = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new(&mut jpeg)
+            .write_image(&image_fixture(), 8, 8, image::ExtendedColorType::Rgb8)
+            .unwrap();
+        let mut app1_exif = EXIF_SIGNATURE.to_vec();
+        app1_exif.extend(tiff_fixture());
+        let mut app1_xmp = b"http://ns.adobe.com/xap/1.0/\0".to_vec();
+        app1_xmp.extend(xmp_fixture());
+
+        let mut data = jpeg[..2].to_vec();
+        for segment in [app1_exif, app1_xmp] {
+            data.extend([0xff, 0xe1]);
+            data.extend((segment.len() as u16 + 2).to_be_bytes());
+            data.extend(segment);
+        }
+        data.extend(&jpeg[2..]);
+        data
+    }
+
+    fn png_fixture() -> Vec<u8> {
+        let mut png = Vec::new();
+        image::codecs::png::PngEncoder::new(&mut png)
+            .write_image(&image_fixture(), 8, 8, image::ExtendedColorType::Rgb8)
+            .unwrap();
+        let mut itxt = b"XML:com.adobe.xmp\0\0\0\0\0".to_vec();
+        itxt.extend(xmp_fixture());
+
+        // Insert the chunks right before IEND, which is the last chunk of the file
+        let (content, iend) = png.split_at(png.len() - 12);
+        let mut data = content.to_vec();
+        for (chunk_type, chunk_data) in [(b"eXIf", tiff_fixture()), (b"iTXt", itxt)] {
+            data.extend((chunk_data.len() as u32).to_be_bytes());
+            let start = data.len();
+            data.extend(chunk_type);
+            data.extend(chunk_data);
+            let crc = crc32fast::hash(&data[start..]);
+            data.extend(crc.to_be_bytes());
+        }
+        data.extend(iend);
+        data
+    }
+
+    fn webp_fixture() -> Vec<u8> {
+        let mut data = Vec::new();
+        image::codecs::webp::WebPEncoder::new_lossless(&mut data)
+            .write_image(&image_fixture(), 8, 8, image::ExtendedColorType::Rgb8)
+            .unwrap();
+        for (chunk_type, mut chunk_data) in [(b"EXIF", tiff_fixture()), (b"XMP ", xmp_fixture())] {
+            data.extend(chunk_type);
+            data.extend((chunk_data.len() as u32).to_le_bytes());
+            if chunk_data.len() % 2 == 1 {
+                chunk_data.push(0);
+            }
+            data.extend(chunk_data);
+        }
+        let riff_size = data.len() as u32 - 8;
+        data[4..8].copy_from_slice(&riff_size.to_le_bytes());
+        data
+    }
+
+    /// Strip every group of metadata from the given file, and check that the GPS data and serial
+    /// numbers are gone while the image and its other metadata are still readable
+    fn check_strip(mut data: Vec<u8>) {
+        assert!(find_bytes(&data, SERIAL_NUMBER).is_some());
+        assert!(find_bytes(&data, XMP_GPS_VALUE).is_some());
+
+        assert!(strip(&mut data, &MetadataGroup::ALL));
+
+        assert!(find_bytes(&data, SERIAL_NUMBER).is_none());
+        assert!(find_bytes(&data, XMP_GPS_VALUE).is_none());
+        assert!(find_bytes(&data, b"2,21.0E").is_none());
+        assert!(find_bytes(&data, b"tiff:Make=\"FUJIFILM\"").is_some());
+
+        let exif = exif::Reader::new()
+            .read_from_container(&mut Cursor::new(&data))
+            .unwrap();
+        assert!(exif
+            .get_field(exif::Tag::GPSLatitude, exif::In::PRIMARY)
+            .is_none());
+        let make = exif.get_field(exif::Tag::Make, exif::In::PRIMARY).unwrap();
+        assert_eq!(make.display_value().to_string(), "\"FUJIFILM\"");
+
+        let img = image::load_from_memory(&data).unwrap();
+        assert_eq!((img.width(), img.height()), (8, 8));
+    }
+
+    #[test]
+    fn strip_jpeg() {
+        check_strip(jpeg_fixture());
+    }
+
+    #[test]
+    fn strip_png() {
+        check_strip(png_fixture());
+
+        // Decoders ignore invalid checksums in ancillary chunks, so they must be checked separately
+        let mut data = png_fixture();
+        strip(&mut data, &MetadataGroup::ALL);
+        let mut offset = PNG_SIGNATURE.len();
+        while offset + 12 <= data.len() {
+            let length = u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+            let crc_offset = offset + 8 + length;
+            let crc = u32::from_be_bytes(data[crc_offset..crc_offset + 4].try_into().unwrap());
+            assert_eq!(crc, crc32fast::hash(&data[offset + 4..crc_offset]));
+            offset = crc_offset + 4;
+        }
+        assert_eq!(offset, data.len());
+    }
+
+    #[test]
+    fn strip_webp() {
+        check_strip(webp_fixture());
+    }
+
+    #[test]
+    fn strip_nothing_without_groups() {
+        let original = jpeg_fixture();
+        let mut data = original.clone();
+        assert!(!strip(&mut data, &[]));
+        assert_eq!(data, original);
+    }
+
+    #[test]
+    fn strip_gps_only() {
+        let mut data = jpeg_fixture();
+        assert!(strip(&mut data, &[MetadataGroup::GPS]));
+        assert!(find_bytes(&data, XMP_GPS_VALUE).is_none());
+        assert!(find_bytes(&data, SERIAL_NUMBER).is_some());
+    }
+}
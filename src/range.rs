@@ -0,0 +1,276 @@
+use rocket::fs::NamedFile;
+use rocket::http::{ContentType, Header, Status};
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::{self, Responder, Response};
+use rocket::tokio::fs::File;
+use rocket::tokio::io::{AsyncReadExt, AsyncSeekExt};
+use std::io::{self, Cursor, SeekFrom};
+use std::path::Path;
+
+/// Maximum number of bytes returned by a single partial response. Clients requesting an open-ended
+/// range (such as `bytes=0-`, which is what browsers do when they start playing a video) will receive
+/// the file in chunks of this size.
+const MAX_CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Custom type used as a request guard which represents the byte range optionally requested by the
+/// client with a Range header, as a (start, end) pair where either bound may be missing. This request
+/// guard never fails : a missing, malformed or multi-range header simply results in None, which means
+/// that the whole file should be returned.
+pub struct ByteRange(Option<(Option<u64>, Option<u64>)>);
+
+impl ByteRange {
+    /// Parse the value of a Range header, such as `bytes=0-1023`, `bytes=1024-` or `bytes=-512`
+    fn parse(header: &str) -> Option<(Option<u64>, Option<u64>)> {
+        let range = header.trim().strip_prefix("bytes=")?;
+        if range.contains(',') {
+            // Multiple ranges are not supported
+            return None;
+        }
+        let (start, end) = range.split_once('-')?;
+        let start = match start.trim() {
+            "" => None,
+            start => Some(start.parse().ok()?),
+        };
+        let end = match end.trim() {
+            "" => None,
+            end => Some(end.parse().ok()?),
+        };
+        match (start, end) {
+            (None, None) => None,
+            (Some(start), Some(end)) if start > end => None,
+            range => Some(range),
+        }
+    }
+
+    /// Resolve this range against a file of the given length, and return the inclusive bounds of
+    /// the bytes to send, or None if the range is not satisfiable
+    fn resolve(&self, length: u64) -> Option<(u64, u64)> {
+        let (start, end) = match self.0? {
+            (Some(start), Some(end)) => (start, end.min(length.saturating_sub(1))),
+            (Some(start), None) => (start, length.saturating_sub(1)),
+            (None, Some(suffix)) => (length.saturating_sub(suffix), length.saturating_sub(1)),
+            (None, None) => return None,
+        };
+        if length == 0 || start >= length {
+            return None;
+        }
+        Some((start, end.min(start + MAX_CHUNK_SIZE - 1)))
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ByteRange {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        request::Outcome::Success(ByteRange(
+            request
+                .headers()
+                .get_one(rocket::http::hyper::header::RANGE.as_str())
+                .and_then(ByteRange::parse),
+        ))
+    }
+}
+
+/// A file returned either entirely, or partially according to the Range requested by the client
+pub enum RangedFile {
+    /// No range has been requested, return the whole file
+    Full(NamedFile),
+
+    /// Return the given chunk of the file, which starts at `start` in a file of `length` bytes
+    Partial {
+        data: Vec<u8>,
+        start: u64,
+        length: u64,
+        content_type: Option<ContentType>,
+    },
+
+    /// The requested range is outside of the file, which has the given length
+    Unsatisfiable(u64),
+}
+
+impl RangedFile {
+    /// Open the file at the given path and read the requested range, if any
+    pub async fn open<P>(path: P, range: &ByteRange) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        if range.0.is_none() {
+            return NamedFile::open(path).await.map(RangedFile::Full);
+        }
+
+        let mut file = File::open(&path).await?;
+        let length = file.metadata().await?.len();
+        let Some((start, end)) = range.resolve(length) else {
+            return Ok(RangedFile::Unsatisfiable(length));
+        };
+        let mut data = vec![0u8; (end - start + 1) as usize];
+        file.seek(SeekFrom::Start(start)).await?;
+        file.read_exact(&mut data).await?;
+        let content_type = path
+            .as_ref()
+            .extension()
+            .and_then(|ext| ContentType::from_extension(&ext.to_string_lossy()));
+        Ok(RangedFile::Partial {
+            data,
+            start,
+            length,
+            content_type,
+        })
+    }
+}
+
+impl<'r> Responder<'r, 'static> for RangedFile {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let accept_ranges =
+            Header::new(rocket::http::hyper::header::ACCEPT_RANGES.as_str(), "bytes");
+        match self {
+            RangedFile::Full(file) => Response::build_from(file.respond_to(request)?)
+                .header(accept_ranges)
+                .ok(),
+            RangedFile::Partial {
+                data,
+                start,
+                length,
+                content_type,
+            } => {
+                let end = start + data.len() as u64 - 1;
+                let mut response = Response::build();
+                response
+                    .status(Status::PartialContent)
+                    .header(accept_ranges)
+                    .header(Header::new(
+                        rocket::http::hyper::header::CONTENT_RANGE.as_str(),
+                        format!("bytes {start}-{end}/{length}"),
+                    ))
+                    .sized_body(data.len(), Cursor::new(data));
+                if let Some(content_type) = content_type {
+                    response.header(content_type);
+                }
+                response.ok()
+            }
+            RangedFile::Unsatisfiable(length) => Response::build()
+                .status(Status::RangeNotSatisfiable)
+                .header(accept_ranges)
+                .header(Header::new(
+                    rocket::http::hyper::header::CONTENT_RANGE.as_str(),
+                    format!("bytes */{length}"),
+                ))
+                .ok(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: u64 = 1024 * 1024;
+
+    fn resolve(header: &str, length: u64) -> Option<(u64, u64)> {
+        ByteRange(ByteRange::parse(header)).resolve(length)
+    }
+
+    #[test]
+    fn parse_ranges() {
+        assert_eq!(
+            ByteRange::parse("bytes=0-1023"),
+            Some((Some(0), Some(1023)))
+        );
+        assert_eq!(ByteRange::parse("bytes=100-"), Some((Some(100), None)));
+        assert_eq!(ByteRange::parse("bytes=-500"), Some((None, Some(500))));
+        assert_eq!(
+            ByteRange::parse(" bytes= 5 - 10 "),
+            Some((Some(5), Some(10)))
+        );
+    }
+
+    #[test]
+    fn ignore_malformed_headers() {
+        for header in [
+            "",
+            "0-100",
+            "items=0-100",
+            "bytes=",
+            "bytes=-",
+            "bytes=100",
+            "bytes=a-b",
+            "bytes=10-5",
+            "bytes=-1-5",
+            "bytes=0-99999999999999999999",
+        ] {
+            assert_eq!(ByteRange::parse(header), None, "{header}");
+        }
+    }
+
+    #[test]
+    fn ignore_multiple_ranges() {
+        assert_eq!(ByteRange::parse("bytes=0-10,20-30"), None);
+        assert_eq!(ByteRange::parse("bytes=0-10, -5"), None);
+    }
+
+    #[test]
+    fn resolve_ranges() {
+        assert_eq!(resolve("bytes=0-99", 1000), Some((0, 99)));
+        assert_eq!(resolve("bytes=900-2000", 1000), Some((900, 999)));
+        assert_eq!(resolve("bytes=100-", 1000), Some((100, 999)));
+        assert_eq!(resolve("bytes=999-", 1000), Some((999, 999)));
+    }
+
+    #[test]
+    fn resolve_suffix_ranges() {
+        assert_eq!(resolve("bytes=-500", 1000), Some((500, 999)));
+        assert_eq!(resolve("bytes=-5000", 1000), Some((0, 999)));
+        assert_eq!(resolve("bytes=-0", 1000), None);
+    }
+
+    #[test]
+    fn reject_unsatisfiable_ranges() {
+        assert_eq!(resolve("bytes=1000-", 1000), None);
+        assert_eq!(resolve("bytes=2000-3000", 1000), None);
+        assert_eq!(resolve("bytes=0-", 0), None);
+        assert_eq!(resolve("bytes=-500", 0), None);
+        assert_eq!(ByteRange(None).resolve(1000), None);
+    }
+
+    #[test]
+    fn cap_chunk_size() {
+        assert_eq!(resolve("bytes=0-", 10 * MIB), Some((0, MAX_CHUNK_SIZE - 1)));
+        assert_eq!(
+            resolve("bytes=1000-", 10 * MIB),
+            Some((1000, 1000 + MAX_CHUNK_SIZE - 1))
+        );
+        assert_eq!(
+            resolve("bytes=0-9999999", 10 * MIB),
+            Some((0, MAX_CHUNK_SIZE - 1))
+        );
+        assert_eq!(
+            resolve("bytes=-6000000", 10 * MIB),
+            Some((10 * MIB - 6000000, 10 * MIB - 6000000 + MAX_CHUNK_SIZE - 1))
+        );
+        assert_eq!(
+            resolve("bytes=0-", MAX_CHUNK_SIZE),
+            Some((0, MAX_CHUNK_SIZE - 1))
+        );
+    }
+
+    #[rocket::async_test]
+    async fn open_unsatisfiable_range() {
+        let path = std::env::temp_dir().join(format!("niobium_range_{}", rand::random::<u32>()));
+        std::fs::write(&path, [0u8; 1000]).unwrap();
+        let full = RangedFile::open(&path, &ByteRange(None)).await.unwrap();
+        let beyond = RangedFile::open(&path, &ByteRange(ByteRange::parse("bytes=1000-")))
+            .await
+            .unwrap();
+        let partial = RangedFile::open(&path, &ByteRange(ByteRange::parse("bytes=-10")))
+            .await
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(full, RangedFile::Full(_)));
+        assert!(matches!(beyond, RangedFile::Unsatisfiable(1000)));
+        assert!(matches!(
+            partial,
+            RangedFile::Partial { ref data, start: 990, length: 1000, .. } if data.len() == 10
+        ));
+    }
+}
@@ -0,0 +1,376 @@
+use crate::config::Config;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Maximum delay for which a client can be locked out, however many attempts it has failed
+const MAX_LOCKOUT_DELAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Minimum interval between two scans of the counters to forget the stale ones
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Maximum number of entries kept in each map of counters, past which the counters with the oldest
+/// failure are forgotten, so that the memory used by an attacker rotating its addresses is bounded
+const MAX_TRACKED_ENTRIES: usize = 100_000;
+
+/// Failed password attempts counted under a given key
+struct Attempts {
+    failures: u32,
+    /// Digest of the last password tried, so that a client retrying the same wrong password (such as
+    /// the nav and the grid loaded in parallel with it) only counts as a single attempt
+    last_password: Vec<u8>,
+    last_failure: Instant,
+    locked_until: Option<Instant>,
+}
+
+impl Attempts {
+    /// Return the remaining lockout delay, if any
+    fn remaining(&self, now: Instant) -> Option<Duration> {
+        self.locked_until
+            .filter(|locked_until| *locked_until > now)
+            .map(|locked_until| locked_until - now)
+    }
+
+    /// Record a failed attempt, and return the delay for which the key is now locked out if it has
+    /// failed `max_attempts` of them. Past this limit, the delay doubles with every failed attempt
+    /// if `escalate` is set, otherwise the counter starts over once locked out.
+    /// Return None without counting anything if the password is the same as the previous one.
+    fn fail(
+        &mut self,
+        password: &[u8],
+        now: Instant,
+        max_attempts: u32,
+        lockout_delay: Duration,
+        escalate: bool,
+    ) -> Option<Option<Duration>> {
+        if self.failures > 0 && self.last_password == password {
+            return None;
+        }
+        self.failures += 1;
+        self.last_password = password.to_vec();
+        self.last_failure = now;
+        if max_attempts == 0 || self.failures < max_attempts {
+            return Some(None);
+        }
+        let delay = if escalate {
+            let exponent = (self.failures - max_attempts).min(31);
+            lockout_delay
+                .saturating_mul(1 << exponent)
+                .min(MAX_LOCKOUT_DELAY)
+        } else {
+            self.failures = 0;
+            lockout_delay
+        };
+        self.locked_until = Some(now + delay);
+        Some(Some(delay))
+    }
+
+    /// Check if these attempts can be forgotten
+    fn is_stale(&self, now: Instant) -> bool {
+        now - self.last_failure >= MAX_LOCKOUT_DELAY && self.remaining(now).is_none()
+    }
+}
+
+/// Return the counter for the given key, creating it if needed, while keeping the map under
+/// MAX_TRACKED_ENTRIES
+fn entry<K: Eq + Hash>(map: &mut HashMap<K, Attempts>, key: K, now: Instant) -> &mut Attempts {
+    if !map.contains_key(&key) && map.len() >= MAX_TRACKED_ENTRIES {
+        map.retain(|_, attempts| !attempts.is_stale(now));
+        if map.len() >= MAX_TRACKED_ENTRIES {
+            // Forget the tenth of the counters with the oldest failures at once, so that this scan
+            // only runs once in a while when an attacker keeps rotating its addresses
+            let mut last_failures = map
+                .values()
+                .map(|attempts| attempts.last_failure)
+                .collect::<Vec<_>>();
+            let (_, threshold, _) = last_failures.select_nth_unstable(MAX_TRACKED_ENTRIES / 10);
+            let threshold = *threshold;
+            map.retain(|_, attempts| attempts.last_failure > threshold);
+        }
+    }
+    map.entry(key).or_insert_with(|| Attempts {
+        failures: 0,
+        last_password: Vec::new(),
+        last_failure: now,
+        locked_until: None,
+    })
+}
+
+/// Counters of the failed password attempts
+struct Counters {
+    /// For each client IP address and each protected path
+    by_client: HashMap<(IpAddr, String), Attempts>,
+    /// For each client IP address, across all the protected paths
+    by_ip: HashMap<IpAddr, Attempts>,
+    /// For each protected path, across all the clients
+    by_path: HashMap<String, Attempts>,
+    last_prune: Instant,
+}
+
+/// Counters of the failed password attempts, used to lock out the clients which fail too many of
+/// them :
+/// - a client failing PASSWORD_MAX_ATTEMPTS for a given path is locked out of this path, for a
+///   delay which doubles with every further failed attempt ;
+/// - a client failing PASSWORD_MAX_ATTEMPTS_PER_IP across all the paths is locked out of every
+///   path, with the same doubling delay ;
+/// - a path for which PASSWORD_MAX_ATTEMPTS_PER_PATH attempts have failed, from any client, refuses
+///   every password for PASSWORD_LOCKOUT_DELAY, without doubling so that an attacker can't lock
+///   the legitimate users out of it for long.
+///
+/// Clients whose IP address is unknown are only counted for each path.
+pub struct PasswordAttempts {
+    max_attempts: u32,
+    max_attempts_per_ip: u32,
+    max_attempts_per_path: u32,
+    lockout_delay: Duration,
+    counters: Mutex<Counters>,
+}
+
+impl PasswordAttempts {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            max_attempts: config.PASSWORD_MAX_ATTEMPTS,
+            max_attempts_per_ip: config.PASSWORD_MAX_ATTEMPTS_PER_IP,
+            max_attempts_per_path: config.PASSWORD_MAX_ATTEMPTS_PER_PATH,
+            lockout_delay: Duration::from_secs(config.PASSWORD_LOCKOUT_DELAY),
+            counters: Mutex::new(Counters {
+                by_client: HashMap::new(),
+                by_ip: HashMap::new(),
+                by_path: HashMap::new(),
+                last_prune: Instant::now(),
+            }),
+        }
+    }
+
+    /// Check if the given client is allowed to try a password for the given path, or return the
+    /// number of seconds it has to wait before the next attempt
+    pub fn check(&self, ip: Option<IpAddr>, path: &str) -> Result<(), u64> {
+        let counters = self.counters.lock().unwrap();
+        let now = Instant::now();
+        let path = path.to_string();
+        let by_client = ip.and_then(|ip| counters.by_client.get(&(ip, path.clone())));
+        let by_ip = ip.and_then(|ip| counters.by_ip.get(&ip));
+        let by_path = counters.by_path.get(&path);
+        match [by_client, by_ip, by_path]
+            .into_iter()
+            .flatten()
+            .filter_map(|attempts| attempts.remaining(now))
+            .max()
+        {
+            Some(remaining) => Err(remaining.as_secs() + 1),
+            None => Ok(()),
+        }
+    }
+
+    /// Record a failed attempt of the given client for the given path, log it, and lock the client
+    /// or the path out if too many of them have failed
+    pub fn fail(&self, ip: Option<IpAddr>, path: &str, password: &str) {
+        let client = ip
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| "unknown address".to_string());
+        let mut counters = self.counters.lock().unwrap();
+        let counters = &mut *counters;
+
+        // Forget the counters that haven't failed any attempt for a while
+        let now = Instant::now();
+        if now - counters.last_prune >= PRUNE_INTERVAL {
+            counters
+                .by_client
+                .retain(|_, attempts| !attempts.is_stale(now));
+            counters.by_ip.retain(|_, attempts| !attempts.is_stale(now));
+            counters
+                .by_path
+                .retain(|_, attempts| !attempts.is_stale(now));
+            counters.last_prune = now;
+        }
+
+        let password = Sha256::digest(password.as_bytes()).to_vec();
+        let path_lockout = entry(&mut counters.by_path, path.to_string(), now).fail(
+            &password,
+            now,
+            self.max_attempts_per_path,
+            self.lockout_delay,
+            false,
+        );
+        let (client_lockout, ip_lockout) = match ip {
+            Some(ip) => (
+                entry(&mut counters.by_client, (ip, path.to_string()), now).fail(
+                    &password,
+                    now,
+                    self.max_attempts,
+                    self.lockout_delay,
+                    true,
+                ),
+                entry(&mut counters.by_ip, ip, now).fail(
+                    &password,
+                    now,
+                    self.max_attempts_per_ip,
+                    self.lockout_delay,
+                    true,
+                ),
+            ),
+            None => (None, None),
+        };
+
+        // Only log the attempts that have been counted, which are those with a new password
+        if client_lockout.is_some() || (ip.is_none() && path_lockout.is_some()) {
+            eprintln!("Warning : invalid password for \"{path}\" from {client}");
+        }
+        if let Some(Some(delay)) = client_lockout {
+            eprintln!(
+                "Warning : too many invalid passwords for \"{path}\" from {client}, locked out for {}s",
+                delay.as_secs()
+            );
+        }
+        if let Some(Some(delay)) = ip_lockout {
+            eprintln!(
+                "Warning : too many invalid passwords from {client}, locked out of every path for {}s",
+                delay.as_secs()
+            );
+        }
+        if let Some(Some(delay)) = path_lockout {
+            eprintln!(
+                "Warning : too many invalid passwords for \"{path}\", locked out for every client for {}s",
+                delay.as_secs()
+            );
+        }
+    }
+
+    /// Reset the counter of the given client for the given path after a successful attempt. The
+    /// counters across paths and across clients are kept, so that they can't be reset by a client
+    /// knowing one of the passwords.
+    pub fn succeed(&self, ip: Option<IpAddr>, path: &str) {
+        if let Some(ip) = ip {
+            self.counters
+                .lock()
+                .unwrap()
+                .by_client
+                .remove(&(ip, path.to_string()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn password_attempts(
+        max_attempts: u32,
+        max_attempts_per_ip: u32,
+        max_attempts_per_path: u32,
+    ) -> PasswordAttempts {
+        PasswordAttempts {
+            max_attempts,
+            max_attempts_per_ip,
+            max_attempts_per_path,
+            lockout_delay: Duration::from_secs(30),
+            counters: Mutex::new(Counters {
+                by_client: HashMap::new(),
+                by_ip: HashMap::new(),
+                by_path: HashMap::new(),
+                last_prune: Instant::now(),
+            }),
+        }
+    }
+
+    fn ip(n: u8) -> Option<IpAddr> {
+        Some(IpAddr::from([192, 0, 2, n]))
+    }
+
+    #[test]
+    fn lock_out_client_for_path() {
+        let attempts = password_attempts(3, 0, 0);
+        for password in ["a", "b"] {
+            attempts.fail(ip(1), "private", password);
+        }
+        assert!(attempts.check(ip(1), "private").is_ok());
+        attempts.fail(ip(1), "private", "c");
+        assert_eq!(attempts.check(ip(1), "private"), Err(30));
+        assert!(attempts.check(ip(1), "other").is_ok());
+        assert!(attempts.check(ip(2), "private").is_ok());
+    }
+
+    #[test]
+    fn count_same_password_once() {
+        let attempts = password_attempts(2, 0, 0);
+        for _ in 0..5 {
+            attempts.fail(ip(1), "private", "a");
+        }
+        assert!(attempts.check(ip(1), "private").is_ok());
+        attempts.fail(ip(1), "private", "b");
+        assert!(attempts.check(ip(1), "private").is_err());
+    }
+
+    #[test]
+    fn double_lockout_delay() {
+        let attempts = password_attempts(2, 0, 0);
+        for password in ["a", "b", "c", "d"] {
+            attempts.fail(ip(1), "private", password);
+        }
+        assert_eq!(attempts.check(ip(1), "private"), Err(120));
+    }
+
+    #[test]
+    fn lock_out_ip_across_paths() {
+        let attempts = password_attempts(5, 3, 0);
+        for (path, password) in [("a", "1"), ("b", "2"), ("c", "3")] {
+            attempts.fail(ip(1), path, password);
+        }
+        assert!(attempts.check(ip(1), "d").is_err());
+        assert!(attempts.check(ip(2), "a").is_ok());
+    }
+
+    #[test]
+    fn lock_out_path_across_clients() {
+        let attempts = password_attempts(5, 5, 3);
+        for n in 1..=3 {
+            attempts.fail(ip(n), "private", &n.to_string());
+        }
+        assert_eq!(attempts.check(ip(4), "private"), Err(30));
+        assert!(attempts.check(ip(4), "other").is_ok());
+
+        // The delay doesn't double for the path, the counter starts over instead
+        attempts.fail(ip(5), "private", "5");
+        assert_eq!(attempts.check(ip(4), "private"), Err(30));
+    }
+
+    #[test]
+    fn count_unknown_clients_for_path_only() {
+        let attempts = password_attempts(2, 2, 3);
+        for password in ["a", "b"] {
+            attempts.fail(None, "private", password);
+        }
+        assert!(attempts.check(None, "private").is_ok());
+        assert!(attempts.check(ip(1), "private").is_ok());
+        attempts.fail(None, "private", "c");
+        assert!(attempts.check(ip(1), "private").is_err());
+    }
+
+    #[test]
+    fn succeed_resets_client_only() {
+        let attempts = password_attempts(2, 3, 0);
+        for password in ["a", "b"] {
+            attempts.fail(ip(1), "private", password);
+        }
+        attempts.succeed(ip(1), "private");
+        assert!(attempts.check(ip(1), "private").is_ok());
+        attempts.fail(ip(1), "other", "c");
+        assert!(attempts.check(ip(1), "private").is_err());
+    }
+
+    #[test]
+    fn cap_tracked_entries() {
+        let now = Instant::now();
+        let mut map = HashMap::new();
+        for n in 0..MAX_TRACKED_ENTRIES {
+            entry(&mut map, n, now + Duration::from_millis(n as u64)).failures = 1;
+        }
+        entry(&mut map, MAX_TRACKED_ENTRIES, now);
+        assert!(map.len() < MAX_TRACKED_ENTRIES);
+        assert!(map.contains_key(&(MAX_TRACKED_ENTRIES - 1)));
+        assert!(!map.contains_key(&0));
+    }
+}
@@ -0,0 +1,312 @@
+use crate::tiff::{TiffReader, MAX_IFDS, TAG_SUB_IFDS};
+use crate::Error;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+
+/// Extensions of the camera RAW formats that can be indexed. All of these are based on the TIFF
+/// container and embed a full-size (or at least large) JPEG preview that is used to display them.
+pub const SUPPORTED_EXTENSIONS: [&str; 10] = [
+    "cr2", "nef", "nrw", "arw", "srf", "sr2", "dng", "orf", "rw2", "pef",
+];
+
+// TIFF tags used to locate the embedded previews
+const TAG_JPG_FROM_RAW: u16 = 0x002e;
+const TAG_COMPRESSION: u16 = 0x0103;
+const TAG_STRIP_OFFSETS: u16 = 0x0111;
+const TAG_STRIP_BYTE_COUNTS: u16 = 0x0117;
+const TAG_JPEG_INTERCHANGE_FORMAT: u16 = 0x0201;
+const TAG_JPEG_INTERCHANGE_FORMAT_LENGTH: u16 = 0x0202;
+
+/// Check if the given file is a camera RAW file, based on its extension
+pub fn is_raw_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .map(|ext| SUPPORTED_EXTENSIONS.contains(&ext.as_str()))
+        .unwrap_or(false)
+}
+
+/// Find the largest JPEG preview embedded inside the given RAW file and return its content
+pub fn extract_preview(path: &Path) -> Result<Vec<u8>, Error> {
+    let file = File::open(path).map_err(|e| Error::FileError(e, path.to_path_buf()))?;
+    read_preview(BufReader::new(file), path)
+}
+
+/// Find the largest JPEG preview embedded inside the TIFF-based structure of the given reader. The
+/// path of the file is only used in errors and warnings.
+fn read_preview<R: Read + Seek>(reader: R, path: &Path) -> Result<Vec<u8>, Error> {
+    let mut reader = TiffReader::new(reader, path.to_path_buf())?;
+
+    // Visit every IFD of the main chain and their sub-IFDs, looking for JPEG data
+    let mut candidates: Vec<(u64, u64)> = Vec::new();
+    let mut ifds_to_visit: Vec<u64> = vec![reader.first_ifd_offset];
+    let mut visited_ifds: Vec<u64> = Vec::new();
+    while let Some(offset) = ifds_to_visit.pop() {
+        if offset == 0 || visited_ifds.contains(&offset) || visited_ifds.len() >= MAX_IFDS {
+            continue;
+        }
+        visited_ifds.push(offset);
+        let ifd = match reader.read_ifd(offset) {
+            Ok(ifd) => ifd,
+            Err(error) => {
+                eprintln!(
+                    "Warning : unable to read an IFD in \"{}\" : {}",
+                    path.display(),
+                    error
+                );
+                continue;
+            }
+        };
+        ifds_to_visit.push(ifd.next_offset);
+        if let Some(entry) = ifd.get(TAG_SUB_IFDS) {
+            ifds_to_visit.append(&mut reader.read_values(entry).unwrap_or_default());
+        }
+
+        // Standard thumbnail/preview tags. A corrupt entry only skips this candidate, the
+        // other IFDs may still contain a valid preview.
+        let jpeg_offset = ifd.get(TAG_JPEG_INTERCHANGE_FORMAT);
+        let jpeg_length = ifd.get(TAG_JPEG_INTERCHANGE_FORMAT_LENGTH);
+        if let (Some(jpeg_offset), Some(jpeg_length)) = (jpeg_offset, jpeg_length) {
+            if let (Ok(jpeg_offset), Ok(jpeg_length)) = (
+                reader.read_value(jpeg_offset),
+                reader.read_value(jpeg_length),
+            ) {
+                candidates.push((jpeg_offset, jpeg_length));
+            }
+        }
+
+        // Single-strip JPEG-compressed images (used by Canon for their full-size preview)
+        let compression = match ifd.get(TAG_COMPRESSION) {
+            Some(entry) => reader.read_value(entry).unwrap_or(0),
+            None => 0,
+        };
+        let strip_offsets = ifd.get(TAG_STRIP_OFFSETS);
+        let strip_byte_counts = ifd.get(TAG_STRIP_BYTE_COUNTS);
+        if let (6 | 7, Some(strip_offsets), Some(strip_byte_counts)) =
+            (compression, strip_offsets, strip_byte_counts)
+        {
+            if strip_offsets.count == 1 && strip_byte_counts.count == 1 {
+                if let (Ok(strip_offset), Ok(strip_byte_count)) = (
+                    reader.read_value(strip_offsets),
+                    reader.read_value(strip_byte_counts),
+                ) {
+                    candidates.push((strip_offset, strip_byte_count));
+                }
+            }
+        }
+
+        // Panasonic's full-size preview, stored as an opaque blob in IFD0
+        if let Some(entry) = ifd.get(TAG_JPG_FROM_RAW) {
+            candidates.push((entry.value_offset(reader.little_endian), entry.count as u64));
+        }
+    }
+
+    // Keep the largest valid JPEG found
+    candidates.sort_by_key(|(_, length)| std::cmp::Reverse(*length));
+    for (offset, length) in candidates {
+        if length == 0 {
+            continue;
+        }
+        if let Ok(data) = reader.read_at(offset, length as usize) {
+            if data.starts_with(&[0xff, 0xd8]) {
+                return Ok(data);
+            }
+        }
+    }
+    Err(Error::RawPreviewError(
+        "no embedded JPEG preview found".to_string(),
+        path.to_path_buf(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const TYPE_SHORT: u16 = 3;
+    const TYPE_LONG: u16 = 4;
+    const TYPE_UNDEFINED: u16 = 7;
+
+    /// Write the given bytes at the given offset of a TIFF buffer, extending it if required
+    fn write_at(tiff: &mut Vec<u8>, offset: u32, bytes: &[u8]) {
+        let offset = offset as usize;
+        if tiff.len() < offset + bytes.len() {
+            tiff.resize(offset + bytes.len(), 0);
+        }
+        tiff[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// Write a little-endian IFD made of (tag, type, count, value) entries at the given offset
+    fn write_ifd(tiff: &mut Vec<u8>, offset: u32, entries: &[(u16, u16, u32, u32)], next: u32) {
+        let mut ifd = (entries.len() as u16).to_le_bytes().to_vec();
+        for (tag, field_type, count, value) in entries {
+            ifd.extend(tag.to_le_bytes());
+            ifd.extend(field_type.to_le_bytes());
+            ifd.extend(count.to_le_bytes());
+            ifd.extend(value.to_le_bytes());
+        }
+        ifd.extend(next.to_le_bytes());
+        write_at(tiff, offset, &ifd);
+    }
+
+    /// Little-endian TIFF header pointing to a first IFD at offset 8
+    fn tiff_header() -> Vec<u8> {
+        let mut tiff = b"II*\0".to_vec();
+        tiff.extend(8u32.to_le_bytes());
+        tiff
+    }
+
+    /// Fake JPEG data of the given length
+    fn jpeg(length: usize) -> Vec<u8> {
+        let mut jpeg = vec![0x42; length];
+        jpeg[..2].copy_from_slice(&[0xff, 0xd8]);
+        jpeg
+    }
+
+    fn preview(tiff: Vec<u8>) -> Result<Vec<u8>, Error> {
+        read_preview(Cursor::new(tiff), Path::new("test.dng"))
+    }
+
+    #[test]
+    fn find_largest_preview() {
+        // Thumbnail in IFD0, larger JPEG-compressed strip in its sub-IFD, Panasonic blob in IFD1
+        let mut tiff = tiff_header();
+        write_ifd(
+            &mut tiff,
+            8,
+            &[
+                (TAG_SUB_IFDS, TYPE_LONG, 1, 100),
+                (TAG_JPEG_INTERCHANGE_FORMAT, TYPE_LONG, 1, 200),
+                (TAG_JPEG_INTERCHANGE_FORMAT_LENGTH, TYPE_LONG, 1, 16),
+            ],
+            60,
+        );
+        write_ifd(
+            &mut tiff,
+            60,
+            &[(TAG_JPG_FROM_RAW, TYPE_UNDEFINED, 32, 300)],
+            0,
+        );
+        write_ifd(
+            &mut tiff,
+            100,
+            &[
+                (TAG_COMPRESSION, TYPE_SHORT, 1, 7),
+                (TAG_STRIP_OFFSETS, TYPE_LONG, 1, 400),
+                (TAG_STRIP_BYTE_COUNTS, TYPE_LONG, 1, 64),
+            ],
+            0,
+        );
+        write_at(&mut tiff, 200, &jpeg(16));
+        write_at(&mut tiff, 300, &jpeg(32));
+        write_at(&mut tiff, 400, &jpeg(64));
+        assert_eq!(preview(tiff).unwrap(), jpeg(64));
+    }
+
+    #[test]
+    fn skip_data_that_isnt_jpeg() {
+        let mut tiff = tiff_header();
+        write_ifd(
+            &mut tiff,
+            8,
+            &[
+                (TAG_COMPRESSION, TYPE_SHORT, 1, 1),
+                (TAG_STRIP_OFFSETS, TYPE_LONG, 1, 200),
+                (TAG_STRIP_BYTE_COUNTS, TYPE_LONG, 1, 64),
+                (TAG_JPEG_INTERCHANGE_FORMAT, TYPE_LONG, 1, 300),
+                (TAG_JPEG_INTERCHANGE_FORMAT_LENGTH, TYPE_LONG, 1, 48),
+            ],
+            0,
+        );
+        write_at(&mut tiff, 200, &jpeg(64));
+        write_at(&mut tiff, 300, &[0u8; 48]);
+        assert!(matches!(preview(tiff), Err(Error::RawPreviewError(..))));
+    }
+
+    #[test]
+    fn ignore_truncated_ifds() {
+        // IFD1 claims 10 entries but the file ends after the first one
+        let mut tiff = tiff_header();
+        write_ifd(
+            &mut tiff,
+            8,
+            &[
+                (TAG_JPEG_INTERCHANGE_FORMAT, TYPE_LONG, 1, 100),
+                (TAG_JPEG_INTERCHANGE_FORMAT_LENGTH, TYPE_LONG, 1, 16),
+            ],
+            200,
+        );
+        write_at(&mut tiff, 100, &jpeg(16));
+        write_ifd(&mut tiff, 200, &[(TAG_COMPRESSION, TYPE_SHORT, 1, 7)], 0);
+        write_at(&mut tiff, 200, &10u16.to_le_bytes());
+        tiff.truncate(214);
+        assert_eq!(preview(tiff).unwrap(), jpeg(16));
+
+        // Only a truncated IFD
+        let mut tiff = tiff_header();
+        write_at(&mut tiff, 8, &10u16.to_le_bytes());
+        assert!(matches!(preview(tiff), Err(Error::RawPreviewError(..))));
+    }
+
+    #[test]
+    fn ignore_out_of_range_offsets() {
+        // The largest candidate and the sub-IFD point outside of the file
+        let mut tiff = tiff_header();
+        write_ifd(
+            &mut tiff,
+            8,
+            &[
+                (TAG_SUB_IFDS, TYPE_LONG, 1, u32::MAX - 4),
+                (TAG_JPEG_INTERCHANGE_FORMAT, TYPE_LONG, 1, 10_000),
+                (TAG_JPEG_INTERCHANGE_FORMAT_LENGTH, TYPE_LONG, 1, 100_000),
+                (TAG_JPG_FROM_RAW, TYPE_UNDEFINED, u32::MAX, 100),
+            ],
+            100,
+        );
+        write_ifd(
+            &mut tiff,
+            100,
+            &[
+                (TAG_JPEG_INTERCHANGE_FORMAT, TYPE_LONG, 1, 200),
+                (TAG_JPEG_INTERCHANGE_FORMAT_LENGTH, TYPE_LONG, 1, 16),
+            ],
+            u32::MAX,
+        );
+        write_at(&mut tiff, 200, &jpeg(16));
+        assert_eq!(preview(tiff).unwrap(), jpeg(16));
+    }
+
+    #[test]
+    fn stop_on_looping_ifds() {
+        // IFD0 and IFD1 point to each other, both as the next IFD and as a sub-IFD
+        let mut tiff = tiff_header();
+        write_ifd(&mut tiff, 8, &[(TAG_SUB_IFDS, TYPE_LONG, 1, 100)], 100);
+        write_ifd(
+            &mut tiff,
+            100,
+            &[
+                (TAG_SUB_IFDS, TYPE_LONG, 1, 8),
+                (TAG_JPEG_INTERCHANGE_FORMAT, TYPE_LONG, 1, 200),
+                (TAG_JPEG_INTERCHANGE_FORMAT_LENGTH, TYPE_LONG, 1, 16),
+            ],
+            8,
+        );
+        write_at(&mut tiff, 200, &jpeg(16));
+        assert_eq!(preview(tiff).unwrap(), jpeg(16));
+
+        // Without any preview
+        let mut tiff = tiff_header();
+        write_ifd(&mut tiff, 8, &[(TAG_SUB_IFDS, TYPE_LONG, 1, 8)], 8);
+        assert!(matches!(preview(tiff), Err(Error::RawPreviewError(..))));
+    }
+
+    #[test]
+    fn reject_files_that_arent_tiff() {
+        assert!(matches!(
+            preview(b"\xff\xd8\xff\xe0 not a RAW file".to_vec()),
+            Err(Error::MetadataParserError(..))
+        ));
+        assert!(matches!(preview(Vec::new()), Err(Error::FileError(..))));
+    }
+}
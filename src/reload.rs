@@ -0,0 +1,162 @@
+use crate::Error;
+use rocket::serde::Serialize;
+use rocket::tokio::sync::watch;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Phases through which the gallery goes when it is loaded
+#[derive(Serialize, Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ReloadPhase {
+    /// The gallery has never been loaded
+    #[default]
+    Idle,
+    /// Comparing the photos folder and the database, directory by directory
+    Scanning,
+    /// Hashing the content of the new files to detect the ones that have been moved
+    Hashing,
+    /// Reading the metadata of the new photos, and generating their thumbnails if required
+    Parsing,
+    /// Saving the changes into the database
+    Saving,
+    /// Sorting the photos into paths and collections
+    Indexing,
+    /// The last load has been completed successfully
+    Done,
+    /// The last load has been interrupted by an error
+    Failed,
+}
+
+/// Status of the last load of the gallery, which can be followed through the status routes
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ReloadStatus {
+    /// Number of loads started since the app was launched, the first one being at startup
+    pub id: u64,
+    pub phase: ReloadPhase,
+    /// Number of items (directories or files) already processed during the current phase
+    pub processed: usize,
+    /// Total number of items to process during the current phase, if known
+    pub total: Option<usize>,
+    /// Percentage of completion of the current phase, if the total number of items is known
+    pub percent: Option<usize>,
+    /// Number of photos in the gallery once loaded
+    pub photos: Option<usize>,
+    /// Unix timestamps of the start and end of the load
+    pub started_at: Option<u64>,
+    pub finished_at: Option<u64>,
+    /// Error that interrupted the load, if any
+    pub error: Option<String>,
+}
+
+impl ReloadStatus {
+    /// Check if a load is currently running
+    pub fn is_running(&self) -> bool {
+        !matches!(
+            self.phase,
+            ReloadPhase::Idle | ReloadPhase::Done | ReloadPhase::Failed
+        )
+    }
+}
+
+/// Outcome of a request to start a new load
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReloadStart {
+    /// A new load has been started
+    Started,
+    /// A load is already running, the request is merged into it
+    AlreadyRunning,
+    /// The previous load finished too recently, a new one can be started in this number of seconds
+    CoolingDown(u64),
+}
+
+/// Shared status of the loads of the gallery, which can be updated by the task running the current
+/// load and watched by any number of readers
+pub struct ReloadProgress(watch::Sender<ReloadStatus>);
+
+impl ReloadProgress {
+    pub fn new() -> Self {
+        Self(watch::Sender::new(ReloadStatus::default()))
+    }
+
+    /// Return a copy of the current status
+    pub fn status(&self) -> ReloadStatus {
+        self.0.borrow().clone()
+    }
+
+    /// Return a receiver notified every time the status is updated
+    pub fn subscribe(&self) -> watch::Receiver<ReloadStatus> {
+        self.0.subscribe()
+    }
+
+    /// Mark a new load as started, unless one is already running or the previous one finished less
+    /// than `cooldown` seconds ago
+    pub fn try_start(&self, cooldown: u64) -> ReloadStart {
+        let now = now();
+        let mut start = ReloadStart::Started;
+        self.0.send_if_modified(|status| {
+            if status.is_running() {
+                start = ReloadStart::AlreadyRunning;
+                return false;
+            }
+            if let Some(finished_at) = status.finished_at {
+                if now < finished_at + cooldown {
+                    start = ReloadStart::CoolingDown(finished_at + cooldown - now);
+                    return false;
+                }
+            }
+            *status = ReloadStatus {
+                id: status.id + 1,
+                phase: ReloadPhase::Scanning,
+                started_at: Some(now),
+                ..Default::default()
+            };
+            true
+        });
+        start
+    }
+
+    /// Move on to the given phase, with the total number of items to process if known
+    pub fn set_phase(&self, phase: ReloadPhase, total: Option<usize>) {
+        self.0.send_modify(|status| {
+            status.phase = phase;
+            status.processed = 0;
+            status.total = total;
+            status.percent = total.map(|_| 0);
+        });
+    }
+
+    /// Update the number of items processed during the current phase
+    pub fn set_processed(&self, processed: usize) {
+        self.0.send_modify(|status| {
+            status.processed = processed;
+            status.percent = status
+                .total
+                .map(|total| (processed * 100).checked_div(total).unwrap_or(100));
+        });
+    }
+
+    /// Mark the current load as finished, with the number of photos loaded or the error that
+    /// interrupted it
+    pub fn finish(&self, result: &Result<(), Error>, photos: usize) {
+        self.0.send_modify(|status| {
+            match result {
+                Ok(_) => {
+                    status.phase = ReloadPhase::Done;
+                    status.photos = Some(photos);
+                }
+                Err(error) => {
+                    status.phase = ReloadPhase::Failed;
+                    status.error = Some(error.to_string());
+                }
+            }
+            status.finished_at = Some(now());
+        });
+    }
+}
+
+/// Current Unix timestamp
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,225 @@
+use crate::db::{self, DB};
+use crate::Error;
+use rand::distributions::{Alphanumeric, DistString};
+use rocket::http::{Cookie, CookieJar};
+use rocket::request::{self, FromRequest, Request};
+use rocket::serde::Serialize;
+use rocket::tokio::sync::RwLock;
+use rocket::{fairing, Build, Rocket};
+use rocket_db_pools::sqlx::SqliteConnection;
+use rocket_db_pools::Database;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Number of characters of the tokens of the share links
+const TOKEN_LENGTH: usize = 24;
+
+/// Number of days after which a share link expires, if not specified when creating it
+pub const DEFAULT_EXPIRY_DAYS: u32 = 7;
+
+/// Maximum number of days after which a share link can expire
+pub const MAX_EXPIRY_DAYS: u32 = 365;
+
+/// Name of the private cookie that holds the tokens of the share links redeemed by the session
+const COOKIE_NAME: &str = "niobium_shares";
+
+/// Separator of the tokens in the session cookie
+const COOKIE_SEPARATOR: char = ',';
+
+/// A link that grants access to a path of the gallery or to a collection, and to everything inside
+/// it, without requiring the passwords that protect it
+#[derive(Serialize, Debug, Clone)]
+pub struct ShareLink {
+    pub token: String,
+    /// Path of the gallery or name of the collection shared by this link
+    pub path: String,
+    /// Unix timestamps of the creation and expiry of the link
+    pub created_at: i64,
+    pub expires_at: i64,
+    /// Maximum number of times this link can be opened to start a new session, if limited
+    pub max_uses: Option<u32>,
+    pub uses: u32,
+    pub revoked: bool,
+}
+
+impl ShareLink {
+    /// Check if this link still grants access to its path
+    pub fn is_valid(&self) -> bool {
+        !self.revoked && chrono::Utc::now().timestamp() < self.expires_at
+    }
+
+    /// Check if this link can still be opened to start a new session
+    pub fn is_redeemable(&self) -> bool {
+        self.is_valid() && self.max_uses.is_none_or(|max_uses| self.uses < max_uses)
+    }
+}
+
+/// Every share link, indexed by token. They are kept in memory in order to be checked on every
+/// request, and saved in the database.
+pub struct ShareLinks(RwLock<HashMap<String, ShareLink>>);
+
+impl ShareLinks {
+    /// Return every share link, sorted from the most recent to the oldest
+    pub async fn list(&self) -> Vec<ShareLink> {
+        let mut share_links: Vec<ShareLink> = self.0.read().await.values().cloned().collect();
+        share_links.sort_by_key(|share_link| std::cmp::Reverse(share_link.created_at));
+        share_links
+    }
+
+    /// Create a new share link for the given path, which expires in the given number of days
+    pub async fn create(
+        &self,
+        db_conn: &mut SqliteConnection,
+        path: &str,
+        expiry_days: u32,
+        max_uses: Option<u32>,
+    ) -> Result<ShareLink, Error> {
+        let now = chrono::Utc::now().timestamp();
+        let share_link = ShareLink {
+            token: Alphanumeric.sample_string(&mut rand::thread_rng(), TOKEN_LENGTH),
+            path: path.to_string(),
+            created_at: now,
+            expires_at: now + i64::from(expiry_days) * 24 * 60 * 60,
+            max_uses,
+            uses: 0,
+            revoked: false,
+        };
+        let mut share_links = self.0.write().await;
+        db::insert_share_link(db_conn, &share_link).await?;
+        share_links.insert(share_link.token.clone(), share_link.clone());
+        Ok(share_link)
+    }
+
+    /// Revoke the share link with the given token, which immediately stops granting access to the
+    /// sessions that have opened it. Return it, or None if it doesn't exist.
+    pub async fn revoke(
+        &self,
+        db_conn: &mut SqliteConnection,
+        token: &str,
+    ) -> Result<Option<ShareLink>, Error> {
+        let mut share_links = self.0.write().await;
+        let Some(share_link) = share_links.get_mut(token) else {
+            return Ok(None);
+        };
+        share_link.revoked = true;
+        db::update_share_link(db_conn, share_link).await?;
+        Ok(Some(share_link.clone()))
+    }
+
+    /// Open the share link with the given token, if it can still be opened, and add it to the
+    /// session cookies. Return the path it grants access to, or None if it can't be opened.
+    pub async fn redeem(
+        &self,
+        db_conn: &mut SqliteConnection,
+        token: &str,
+        cookies: &CookieJar<'_>,
+    ) -> Result<Option<String>, Error> {
+        let mut share_links = self.0.write().await;
+        let Some(share_link) = share_links.get_mut(token) else {
+            return Ok(None);
+        };
+
+        // Opening a link again in the same session doesn't count as a new use
+        let mut tokens = session_tokens(cookies);
+        if !tokens.iter().any(|t| t == token) {
+            if !share_link.is_redeemable() {
+                return Ok(None);
+            }
+            share_link.uses += 1;
+            db::update_share_link(db_conn, share_link).await?;
+
+            // Forget the links of this session that are no longer valid
+            tokens.retain(|t| share_links.get(t).is_some_and(ShareLink::is_valid));
+            tokens.push(token.to_string());
+            cookies.add_private(Cookie::new(
+                COOKIE_NAME,
+                tokens.join(&COOKIE_SEPARATOR.to_string()),
+            ));
+        }
+        Ok(share_links
+            .get(token)
+            .filter(|share_link| share_link.is_valid())
+            .map(|share_link| share_link.path.clone()))
+    }
+
+    /// Return the paths shared with the current session by the valid share links it has opened
+    async fn shared_paths(&self, cookies: &CookieJar<'_>) -> Vec<String> {
+        let share_links = self.0.read().await;
+        session_tokens(cookies)
+            .iter()
+            .filter_map(|token| share_links.get(token))
+            .filter(|share_link| share_link.is_valid())
+            .map(|share_link| share_link.path.clone())
+            .collect()
+    }
+}
+
+/// Return the tokens of the share links stored in the session cookies
+fn session_tokens(cookies: &CookieJar<'_>) -> Vec<String> {
+    cookies
+        .get_private(COOKIE_NAME)
+        .map(|cookie| {
+            cookie
+                .value()
+                .split(COOKIE_SEPARATOR)
+                .filter(|token| !token.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Request guard that returns the paths shared with the current session by share links
+pub struct SharedPaths(Vec<String>);
+
+impl SharedPaths {
+    /// Check if the password of `required_path` is granted by a share link when accessing `path`,
+    /// which is the case if `path` is inside a shared path, and `required_path` contains it. The
+    /// passwords of the directories inside the shared path are still required.
+    pub fn grants(&self, required_path: &str, path: &str) -> bool {
+        self.0.iter().any(|shared_path| {
+            Path::new(path).starts_with(shared_path)
+                && Path::new(shared_path).starts_with(required_path)
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SharedPaths {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let share_links = request
+            .rocket()
+            .state::<ShareLinks>()
+            .expect("Error : unable to obtain the share links");
+        request::Outcome::Success(SharedPaths(
+            share_links.shared_paths(request.cookies()).await,
+        ))
+    }
+}
+
+/// Fairing callback that loads the share links from the database
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    // Make sure the database has been initialized (fairings have been attached in the correct order)
+    let Some(db) = DB::fetch(&rocket) else {
+        return Err(rocket);
+    };
+    let share_links = match db.0.acquire().await {
+        Ok(mut db_conn) => db::get_share_links(&mut db_conn).await,
+        Err(error) => Err(error.into()),
+    };
+    match share_links {
+        Ok(share_links) => {
+            let share_links = share_links
+                .into_iter()
+                .map(|share_link| (share_link.token.clone(), share_link))
+                .collect();
+            Ok(rocket.manage(ShareLinks(RwLock::new(share_links))))
+        }
+        Err(error) => {
+            eprintln!("Error : unable to load the share links : {error}");
+            Err(rocket)
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use rocket::serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+
+/// Fields of photos that can be used to sort them
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SortField {
+    Id,
+    Filename,
+    Title,
+    DateTaken,
+    SortOrder,
+}
+
+impl SortField {
+    /// Every available field
+    pub const ALL: [SortField; 5] = [
+        SortField::Id,
+        SortField::Filename,
+        SortField::Title,
+        SortField::DateTaken,
+        SortField::SortOrder,
+    ];
+
+    /// Name of this field in SORT_ORDER
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortField::Id => "id",
+            SortField::Filename => "filename",
+            SortField::Title => "title",
+            SortField::DateTaken => "date_taken",
+            SortField::SortOrder => "sort_order",
+        }
+    }
+
+    /// Name of the column of the `photo` table this field is sorted by. Photos are sorted by the
+    /// parsed date they were taken rather than by the date read from their EXIF data.
+    pub fn column(&self) -> &'static str {
+        match self {
+            SortField::DateTaken => "taken_at",
+            _ => self.as_str(),
+        }
+    }
+}
+
+/// Direction in which photos are sorted according to a field
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortDirection {
+    /// Keyword of this direction in SORT_ORDER and in SQL queries
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortDirection::Asc => "ASC",
+            SortDirection::Desc => "DESC",
+        }
+    }
+
+    /// The opposite direction
+    pub fn reversed(&self) -> Self {
+        match self {
+            SortDirection::Asc => SortDirection::Desc,
+            SortDirection::Desc => SortDirection::Asc,
+        }
+    }
+}
+
+/// A single field of a sort order, with its direction
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    pub field: SortField,
+    pub direction: SortDirection,
+}
+
+/// Order in which photos are sorted, written in config files as a comma-separated list of fields
+/// each optionally followed by a direction, such as "date_taken desc, filename"
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SortOrder(Vec<SortKey>);
+
+impl SortOrder {
+    /// Sort order on a single field, in ascending order
+    pub fn by(field: SortField) -> Self {
+        Self(vec![SortKey {
+            field,
+            direction: SortDirection::Asc,
+        }])
+    }
+
+    /// Fields of this sort order, by priority
+    pub fn keys(&self) -> &[SortKey] {
+        &self.0
+    }
+
+    /// The same sort order with the direction of every field reversed
+    pub fn reversed(&self) -> Self {
+        Self(
+            self.0
+                .iter()
+                .map(|key| SortKey {
+                    field: key.field,
+                    direction: key.direction.reversed(),
+                })
+                .collect(),
+        )
+    }
+}
+
+impl TryFrom<String> for SortOrder {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let mut keys = Vec::new();
+        for key in value.split(',').map(|key| key.trim()) {
+            if key.is_empty() {
+                continue;
+            }
+            let mut words = key.split_whitespace();
+            let name = words.next().unwrap_or_default();
+            let field = SortField::ALL
+                .into_iter()
+                .find(|field| field.as_str().eq_ignore_ascii_case(name))
+                .ok_or_else(|| {
+                    format!(
+                        "unknown field \"{name}\" in sort order \"{value}\", available fields : {}",
+                        SortField::ALL.map(|field| field.as_str()).join(", ")
+                    )
+                })?;
+            let direction = match words.next() {
+                None => SortDirection::Asc,
+                Some(word) if word.eq_ignore_ascii_case("asc") => SortDirection::Asc,
+                Some(word) if word.eq_ignore_ascii_case("desc") => SortDirection::Desc,
+                Some(word) => {
+                    return Err(format!(
+                        "invalid direction \"{word}\" for field \"{name}\" in sort order \"{value}\", expected \"asc\" or \"desc\""
+                    ))
+                }
+            };
+            if let Some(word) = words.next() {
+                return Err(format!(
+                    "unexpected \"{word}\" after field \"{name}\" in sort order \"{value}\", fields must be separated by commas"
+                ));
+            }
+            keys.push(SortKey { field, direction });
+        }
+        Ok(Self(keys))
+    }
+}
+
+impl From<SortOrder> for String {
+    fn from(value: SortOrder) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for SortOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keys = self
+            .0
+            .iter()
+            .map(|key| match key.direction {
+                SortDirection::Asc => key.field.as_str().to_string(),
+                SortDirection::Desc => format!("{} desc", key.field.as_str()),
+            })
+            .collect::<Vec<String>>();
+        write!(f, "{}", keys.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: &str) -> Result<SortOrder, String> {
+        SortOrder::try_from(value.to_string())
+    }
+
+    #[test]
+    fn parse_fields_and_directions() {
+        let sort_order = parse(" DATE_TAKEN desc,filename , title ASC,").unwrap();
+        assert_eq!(
+            sort_order.keys(),
+            [
+                SortKey {
+                    field: SortField::DateTaken,
+                    direction: SortDirection::Desc
+                },
+                SortKey {
+                    field: SortField::Filename,
+                    direction: SortDirection::Asc
+                },
+                SortKey {
+                    field: SortField::Title,
+                    direction: SortDirection::Asc
+                },
+            ]
+        );
+        assert_eq!(parse("").unwrap(), SortOrder::default());
+    }
+
+    #[test]
+    fn reject_unknown_fields() {
+        let error = parse("date_taken, camera_model").unwrap_err();
+        assert!(error.contains("unknown field \"camera_model\""));
+        assert!(parse("id; DROP TABLE photo").is_err());
+        assert!(parse("(SELECT 1)").is_err());
+    }
+
+    #[test]
+    fn reject_bad_directions() {
+        let error = parse("filename descending").unwrap_err();
+        assert!(error.contains("invalid direction \"descending\""));
+        assert!(parse("filename --").is_err());
+    }
+
+    #[test]
+    fn reject_missing_commas() {
+        let error = parse("date_taken desc filename").unwrap_err();
+        assert!(error.contains("unexpected \"filename\""));
+        assert!(parse("title asc sort_order").is_err());
+    }
+
+    #[test]
+    fn display_round_trip() {
+        let sort_order = parse("date_taken DESC, filename asc, id").unwrap();
+        let display = sort_order.to_string();
+        assert_eq!(display, "date_taken desc, filename, id");
+        assert_eq!(parse(&display).unwrap(), sort_order);
+        assert_eq!(String::from(sort_order), display);
+    }
+
+    #[test]
+    fn reversed() {
+        let sort_order = parse("date_taken desc, filename").unwrap();
+        let reversed = sort_order.reversed();
+        assert_eq!(reversed.to_string(), "date_taken, filename desc");
+        assert_eq!(reversed.reversed(), sort_order);
+        assert_eq!(
+            SortOrder::by(SortField::Id).reversed().to_string(),
+            "id desc"
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use crate::Error;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::PathBuf;
+
+/// Maximum number of IFDs that will be visited in a single TIFF structure, to protect against loops
+/// in malformed files
+pub const MAX_IFDS: usize = 64;
+
+/// TIFF tag that points to the sub-IFDs of an image, such as its previews in RAW files
+pub const TAG_SUB_IFDS: u16 = 0x014a;
+
+/// Check if the given data starts with a TIFF header. The variants used by Olympus (ORF) and
+/// Panasonic (RW2) RAW files are also accepted.
+pub fn is_tiff_header(data: &[u8]) -> bool {
+    byte_order(data).is_some()
+}
+
+/// Return true if the TIFF header at the beginning of the given data is little-endian, false if it
+/// is big-endian, or None if it isn't a valid TIFF header
+fn byte_order(data: &[u8]) -> Option<bool> {
+    let little_endian = match data.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let magic = read_u16(data, 2, little_endian)?;
+    [42, 0x4f52, 0x5352, 0x55]
+        .contains(&magic)
+        .then_some(little_endian)
+}
+
+/// A single raw entry of an IFD
+pub struct IfdEntry {
+    pub tag: u16,
+    pub field_type: u16,
+    pub count: u32,
+    pub value: [u8; 4],
+    /// Offset of this entry in the TIFF structure
+    pub position: u64,
+}
+
+impl IfdEntry {
+    /// Size in bytes of a single value of this entry's type, or 0 if the type is unknown
+    pub fn type_size(&self) -> usize {
+        match self.field_type {
+            1 | 2 | 6 | 7 => 1,   // BYTE, ASCII, SBYTE, UNDEFINED
+            3 | 8 => 2,           // SHORT, SSHORT
+            4 | 9 | 11 | 13 => 4, // LONG, SLONG, FLOAT, IFD
+            5 | 10 | 12 => 8,     // RATIONAL, SRATIONAL, DOUBLE
+            _ => 0,
+        }
+    }
+
+    /// Interpret the value field of this entry as an offset in the TIFF structure
+    pub fn value_offset(&self, little_endian: bool) -> u64 {
+        read_u32(&self.value, 0, little_endian).unwrap_or_default() as u64
+    }
+
+    /// Return the location of the values of this entry in the TIFF structure, which are stored
+    /// inside the entry itself if they fit
+    pub fn value_range(&self, little_endian: bool) -> Range<u64> {
+        let size = (self.type_size() as u64).saturating_mul(self.count as u64);
+        let start = match size <= 4 {
+            true => self.position + 8,
+            false => self.value_offset(little_endian),
+        };
+        start..start.saturating_add(size)
+    }
+}
+
+/// An IFD, with its entries in the order in which they are stored
+pub struct Ifd {
+    pub offset: u64,
+    pub entries: Vec<IfdEntry>,
+    /// Offset of the next IFD of the chain, or 0 if this is the last one
+    pub next_offset: u64,
+}
+
+impl Ifd {
+    /// Return the first entry with the given tag
+    pub fn get(&self, tag: u16) -> Option<&IfdEntry> {
+        self.entries.iter().find(|entry| entry.tag == tag)
+    }
+
+    /// Offset of the end of the entries of this IFD, where the offset of the next IFD is stored
+    pub fn entries_end(&self) -> u64 {
+        self.offset + 2 + self.entries.len() as u64 * 12
+    }
+}
+
+/// Minimal reader for the structure of TIFF-based files
+pub struct TiffReader<R: Read + Seek> {
+    reader: R,
+    path: PathBuf,
+    pub little_endian: bool,
+    pub first_ifd_offset: u64,
+    length: u64,
+}
+
+impl<R: Read + Seek> TiffReader<R> {
+    /// Parse the TIFF header at the beginning of the given reader. The path of the file is only
+    /// used in errors.
+    pub fn new(mut reader: R, path: PathBuf) -> Result<Self, Error> {
+        let length = reader
+            .seek(SeekFrom::End(0))
+            .map_err(|e| Error::FileError(e, path.clone()))?;
+        let mut header = [0u8; 8];
+        reader
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| reader.read_exact(&mut header))
+            .map_err(|e| Error::FileError(e, path.clone()))?;
+        let Some(little_endian) = byte_order(&header) else {
+            return Err(Error::MetadataParserError(
+                "not a TIFF-based file".to_string(),
+                path,
+            ));
+        };
+        Ok(Self {
+            reader,
+            path,
+            little_endian,
+            first_ifd_offset: read_u32(&header, 4, little_endian).unwrap_or_default() as u64,
+            length,
+        })
+    }
+
+    /// Read `length` bytes at the given offset
+    pub fn read_at(&mut self, offset: u64, length: usize) -> Result<Vec<u8>, Error> {
+        if offset.saturating_add(length as u64) > self.length {
+            return Err(Error::MetadataParserError(
+                format!("invalid offset {offset} (length {length})"),
+                self.path.clone(),
+            ));
+        }
+        let mut buffer = vec![0u8; length];
+        self.reader
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| self.reader.read_exact(&mut buffer))
+            .map_err(|e| Error::FileError(e, self.path.clone()))?;
+        Ok(buffer)
+    }
+
+    /// Read the IFD at the given offset. A missing offset to the next IFD is read as 0.
+    pub fn read_ifd(&mut self, offset: u64) -> Result<Ifd, Error> {
+        let n_entries =
+            read_u16(&self.read_at(offset, 2)?, 0, self.little_endian).unwrap_or_default() as usize;
+        let data = self.read_at(offset + 2, n_entries * 12)?;
+        let entries = data
+            .chunks_exact(12)
+            .enumerate()
+            .map(|(index, entry)| IfdEntry {
+                tag: read_u16(entry, 0, self.little_endian).unwrap_or_default(),
+                field_type: read_u16(entry, 2, self.little_endian).unwrap_or_default(),
+                count: read_u32(entry, 4, self.little_endian).unwrap_or_default(),
+                value: [entry[8], entry[9], entry[10], entry[11]],
+                position: offset + 2 + index as u64 * 12,
+            })
+            .collect::<Vec<_>>();
+        let entries_end = offset + 2 + n_entries as u64 * 12;
+        let next_offset = self
+            .read_at(entries_end, 4)
+            .ok()
+            .and_then(|data| read_u32(&data, 0, self.little_endian))
+            .unwrap_or_default() as u64;
+        Ok(Ifd {
+            offset,
+            entries,
+            next_offset,
+        })
+    }
+
+    /// Read every integer value of the given entry, either inline or at the offset it points to
+    pub fn read_values(&mut self, entry: &IfdEntry) -> Result<Vec<u64>, Error> {
+        let type_size = entry.type_size();
+        if type_size == 0 {
+            return Ok(Vec::new());
+        }
+        let range = entry.value_range(self.little_endian);
+        let data = match range.end - range.start <= 4 {
+            true => entry.value.to_vec(),
+            false => self.read_at(range.start, (range.end - range.start) as usize)?,
+        };
+        Ok(data
+            .chunks_exact(type_size)
+            .take(entry.count as usize)
+            .filter_map(|value| match type_size {
+                1 => Some(value[0] as u64),
+                2 => read_u16(value, 0, self.little_endian).map(u64::from),
+                _ => read_u32(value, 0, self.little_endian).map(u64::from),
+            })
+            .collect())
+    }
+
+    /// Read the first integer value of the given entry
+    pub fn read_value(&mut self, entry: &IfdEntry) -> Result<u64, Error> {
+        Ok(self.read_values(entry)?.first().copied().unwrap_or(0))
+    }
+}
+
+/// Read a 16-bit integer at the given offset in the data, if it is long enough
+pub fn read_u16(data: &[u8], offset: usize, little_endian: bool) -> Option<u16> {
+    let bytes = data.get(offset..offset.checked_add(2)?)?.try_into().ok()?;
+    Some(match little_endian {
+        true => u16::from_le_bytes(bytes),
+        false => u16::from_be_bytes(bytes),
+    })
+}
+
+/// Read a 32-bit integer at the given offset in the data, if it is long enough
+pub fn read_u32(data: &[u8], offset: usize, little_endian: bool) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?.try_into().ok()?;
+    Some(match little_endian {
+        true => u32::from_le_bytes(bytes),
+        false => u32::from_be_bytes(bytes),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Big-endian TIFF with a single IFD of (tag, type, count, value) entries at offset 8, followed by
+    /// the given data at offset 64
+    fn tiff_fixture(entries: &[(u16, u16, u32, [u8; 4])], data: &[u8]) -> Vec<u8> {
+        let mut tiff = b"MM\0*".to_vec();
+        tiff.extend(8u32.to_be_bytes());
+        tiff.extend((entries.len() as u16).to_be_bytes());
+        for (tag, field_type, count, value) in entries {
+            tiff.extend(tag.to_be_bytes());
+            tiff.extend(field_type.to_be_bytes());
+            tiff.extend(count.to_be_bytes());
+            tiff.extend(value);
+        }
+        tiff.resize(64, 0);
+        tiff.extend(data);
+        tiff
+    }
+
+    fn reader(tiff: Vec<u8>) -> TiffReader<Cursor<Vec<u8>>> {
+        TiffReader::new(Cursor::new(tiff), PathBuf::from("test.tif")).unwrap()
+    }
+
+    #[test]
+    fn check_headers() {
+        assert!(is_tiff_header(b"II*\0\x08\0\0\0"));
+        assert!(is_tiff_header(b"MM\0*\0\0\0\x08"));
+        assert!(is_tiff_header(b"IIRO\x08\0\0\0"));
+        assert!(is_tiff_header(b"IIU\0\x18\0\0\0"));
+        assert!(!is_tiff_header(b"II+\0\x08\0\0\0"));
+        assert!(!is_tiff_header(b"\xff\xd8\xff\xe0"));
+        assert!(!is_tiff_header(b"II"));
+    }
+
+    #[test]
+    fn read_entries() {
+        let tiff = tiff_fixture(
+            &[
+                (0x0100, 3, 1, [0, 42, 0, 0]), // A single SHORT, inline
+                (0x0101, 3, 2, [0, 1, 0, 2]),  // Two SHORTs, inline
+                (0x0102, 4, 3, [0, 0, 0, 64]), // Three LONGs, at offset 64
+                (0x0103, 99, 1, [0, 0, 0, 7]), // Unknown type
+            ],
+            &[0, 0, 0, 5, 0, 0, 0, 6, 0, 0, 0, 7],
+        );
+        let mut reader = reader(tiff);
+        assert!(!reader.little_endian);
+        let ifd = reader.read_ifd(reader.first_ifd_offset).unwrap();
+        assert_eq!(ifd.entries.len(), 4);
+        assert_eq!(ifd.entries_end(), 8 + 2 + 4 * 12);
+        assert_eq!(ifd.next_offset, 0);
+        assert_eq!(reader.read_value(ifd.get(0x0100).unwrap()).unwrap(), 42);
+        assert_eq!(
+            reader.read_values(ifd.get(0x0101).unwrap()).unwrap(),
+            [1, 2]
+        );
+        assert_eq!(
+            reader.read_values(ifd.get(0x0102).unwrap()).unwrap(),
+            [5, 6, 7]
+        );
+        assert_eq!(ifd.get(0x0102).unwrap().value_range(false), 64..76);
+        assert!(reader
+            .read_values(ifd.get(0x0103).unwrap())
+            .unwrap()
+            .is_empty());
+        assert!(ifd.get(0x0104).is_none());
+    }
+
+    #[test]
+    fn reject_out_of_range_reads() {
+        let tiff = tiff_fixture(&[(0x0102, 4, 3, [0, 0, 1, 0])], &[]);
+        let mut reader = reader(tiff);
+        let ifd = reader.read_ifd(8).unwrap();
+        assert!(reader.read_values(&ifd.entries[0]).is_err());
+        assert!(reader.read_at(60, 8).is_err());
+        assert!(reader.read_at(u64::MAX, 8).is_err());
+        assert!(reader.read_ifd(63).is_err());
+    }
+
+    #[test]
+    fn read_integers() {
+        assert_eq!(read_u16(&[1, 2], 0, true), Some(0x0201));
+        assert_eq!(read_u16(&[1, 2], 0, false), Some(0x0102));
+        assert_eq!(read_u32(&[0, 1, 2, 3, 4], 1, true), Some(0x04030201));
+        assert_eq!(read_u32(&[1, 2, 3], 0, true), None);
+        assert_eq!(read_u16(&[1, 2], usize::MAX, true), None);
+    }
+}
@@ -6,8 +6,12 @@ use rocket::http::uri::fmt::UriDisplay;
 use rocket::http::{impl_from_uri_param_identity, RawStr};
 use rocket::request::FromParam;
 use rocket::serde::Serialize;
+use utoipa::openapi::schema::Schema;
+use utoipa::openapi::RefOr;
+use utoipa::{PartialSchema, ToSchema};
 
 #[derive(Default, Serialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(transparent)]
 #[allow(clippy::upper_case_acronyms)]
 pub struct UID {
     uid: String,
@@ -170,6 +174,15 @@ impl UriDisplay<rocket::http::uri::fmt::Query> for UID {
     }
 }
 
+/// UIDs are serialized as plain strings
+impl PartialSchema for UID {
+    fn schema() -> RefOr<Schema> {
+        String::schema()
+    }
+}
+
+impl ToSchema for UID {}
+
 // Macros used to automatically implement the FromUriParam trait based on the UriDisplay impls above
 impl_from_uri_param_identity!([rocket::http::uri::fmt::Path] UID);
 impl_from_uri_param_identity!([rocket::http::uri::fmt::Query] UID);
@@ -0,0 +1,115 @@
+use crate::Error;
+use std::path::Path;
+use std::process::Output;
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Maximum time given to ffprobe and ffmpeg to process a video, after which they are killed
+const VIDEO_TOOL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Metadata of a video file, as returned by ffprobe
+#[derive(Debug, Default)]
+pub struct VideoInfo {
+    pub width: u32,
+    pub height: u32,
+    pub duration: f64,
+}
+
+/// Read the dimensions of the first video stream and the duration of the given video file using
+/// the ffprobe binary at `ffprobe_path`
+pub async fn probe(ffprobe_path: &str, path: &Path) -> Result<VideoInfo, Error> {
+    let output = run(
+        Command::new(ffprobe_path)
+            .args(["-v", "error", "-select_streams", "v:0"])
+            .args(["-show_entries", "stream=width,height:format=duration"])
+            .args(["-of", "default=noprint_wrappers=1"])
+            .arg(path),
+        ffprobe_path,
+        path,
+    )
+    .await?;
+
+    // The output is a list of `key=value` lines
+    let mut info = VideoInfo::default();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        match line.trim().split_once('=') {
+            Some(("width", value)) => info.width = value.parse().unwrap_or(0),
+            Some(("height", value)) => info.height = value.parse().unwrap_or(0),
+            Some(("duration", value)) => info.duration = value.parse().unwrap_or(0.0),
+            _ => (),
+        }
+    }
+    if info.width == 0 || info.height == 0 {
+        return Err(Error::VideoToolError(
+            format!("{ffprobe_path} didn't return the dimensions of the video"),
+            path.to_path_buf(),
+        ));
+    }
+    Ok(info)
+}
+
+/// Extract a single frame of the given video file, to be used as a poster, using the ffmpeg binary
+/// at `ffmpeg_path`. The frame is returned encoded as PNG.
+pub async fn extract_poster_frame(
+    ffmpeg_path: &str,
+    path: &Path,
+    duration: f64,
+) -> Result<Vec<u8>, Error> {
+    // Skip the first moments of the video, which are often black
+    let timestamp = (duration / 10.0).min(5.0);
+    let output = run(
+        Command::new(ffmpeg_path)
+            .args(["-v", "error", "-ss", &format!("{timestamp:.3}"), "-i"])
+            .arg(path)
+            .args(["-frames:v", "1", "-f", "image2pipe", "-vcodec", "png", "-"]),
+        ffmpeg_path,
+        path,
+    )
+    .await?;
+    if output.stdout.is_empty() {
+        return Err(Error::VideoToolError(
+            format!("{ffmpeg_path} didn't return any frame"),
+            path.to_path_buf(),
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Run the given command and return its output if it succeeded. The command is killed if it takes
+/// longer than `VIDEO_TOOL_TIMEOUT`.
+async fn run(command: &mut Command, tool: &str, path: &Path) -> Result<Output, Error> {
+    if tool.is_empty() {
+        return Err(Error::VideoToolError(
+            "video processing is disabled in the config".to_string(),
+            path.to_path_buf(),
+        ));
+    }
+    let output = tokio::time::timeout(VIDEO_TOOL_TIMEOUT, command.kill_on_drop(true).output())
+        .await
+        .map_err(|_| {
+            Error::VideoToolError(
+                format!(
+                    "{tool} didn't finish after {} seconds",
+                    VIDEO_TOOL_TIMEOUT.as_secs()
+                ),
+                path.to_path_buf(),
+            )
+        })?
+        .map_err(|error| {
+            Error::VideoToolError(
+                format!("unable to run \"{tool}\" : {error}"),
+                path.to_path_buf(),
+            )
+        })?;
+    if !output.status.success() {
+        return Err(Error::VideoToolError(
+            format!(
+                "{tool} failed ({}) : {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            path.to_path_buf(),
+        ));
+    }
+    Ok(output)
+}
@@ -0,0 +1,191 @@
+use crate::config::{Config, SUBDIR_CONFIG_FILENAME};
+use crate::db::DB;
+use crate::photos::Gallery;
+use notify::event::{AccessKind, AccessMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use rocket::tokio::sync::mpsc::{self, UnboundedReceiver};
+use rocket::tokio::time::{timeout, Duration, Instant};
+use rocket::{tokio, Orbit, Rocket};
+use rocket_db_pools::{sqlx, Database};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Fairing callback used to start watching the photos folder for changes after launch, if the
+/// WATCH_PHOTOS_DIR setting is enabled
+pub async fn init(rocket: &Rocket<Orbit>) {
+    let config = rocket
+        .state::<Config>()
+        .expect("Error : unable to obtain the config");
+    if !config.WATCH_PHOTOS_DIR {
+        return;
+    }
+    let gallery = rocket
+        .state::<Arc<Gallery>>()
+        .expect("Error : unable to obtain the gallery");
+    let Some(db) = DB::fetch(rocket) else {
+        eprintln!("Error : unable to obtain a handle to the database");
+        return;
+    };
+
+    // Forward the events sent by the watcher's thread to the task that will process them
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let watcher = notify::recommended_watcher(move |result: notify::Result<Event>| match result {
+        Ok(event) => {
+            sender.send(event).ok();
+        }
+        Err(error) => eprintln!("Warning : error while watching the photos folder : {error}"),
+    });
+    let mut watcher = match watcher {
+        Ok(watcher) => watcher,
+        Err(error) => {
+            eprintln!("Error : unable to watch the photos folder : {error}");
+            return;
+        }
+    };
+    let recursive_mode = match config.INDEX_SUBDIRS {
+        true => RecursiveMode::Recursive,
+        false => RecursiveMode::NonRecursive,
+    };
+    if let Err(error) = watcher.watch(Path::new(&config.PHOTOS_DIR), recursive_mode) {
+        eprintln!(
+            "Error : unable to watch the photos folder \"{}\" : {}",
+            config.PHOTOS_DIR, error
+        );
+        return;
+    }
+    println!("Watching \"{}\" for changes", config.PHOTOS_DIR);
+
+    // The paths of the events are absolute
+    let watched_dirs = WatchedDirs {
+        photos_dir: absolute_path(&config.PHOTOS_DIR),
+        cache_dir: absolute_path(&config.CACHE_DIR),
+    };
+
+    tokio::spawn(watch(
+        watcher,
+        receiver,
+        watched_dirs,
+        Arc::clone(gallery),
+        db.0.clone(),
+        config.clone(),
+    ));
+}
+
+/// Absolute paths of the folders used by the app, to which the paths of the events are compared
+struct WatchedDirs {
+    photos_dir: PathBuf,
+    cache_dir: PathBuf,
+}
+
+/// Return the canonical absolute form of the given path from the config
+fn absolute_path(path: &str) -> PathBuf {
+    Path::new(path)
+        .canonicalize()
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| PathBuf::from(path))
+}
+
+/// Background task that waits for changes in the photos folder and synchronizes the affected
+/// directories once no more changes have been received for WATCH_DEBOUNCE_DELAY
+async fn watch(
+    _watcher: RecommendedWatcher, // The watcher stops when it is dropped
+    mut receiver: UnboundedReceiver<Event>,
+    watched_dirs: WatchedDirs,
+    gallery: Arc<Gallery>,
+    db: sqlx::SqlitePool,
+    config: Config,
+) {
+    let delay = Duration::from_millis(config.WATCH_DEBOUNCE_DELAY);
+    while let Some(event) = receiver.recv().await {
+        // Group this event with the ones that follow it closely
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        add_affected_dirs(&event, &watched_dirs, &config, &mut dirs);
+        loop {
+            match timeout(delay, receiver.recv()).await {
+                Ok(Some(event)) => add_affected_dirs(&event, &watched_dirs, &config, &mut dirs),
+                Ok(None) => return,
+                Err(_) => break,
+            }
+        }
+        if dirs.is_empty() {
+            continue;
+        }
+
+        println!(
+            "Changes detected in the photos folder, synchronizing : {}",
+            dirs.iter()
+                .map(|dir| format!("\"{}\"", dir.display()))
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+        let now = Instant::now();
+        let mut db_conn = match db.acquire().await {
+            Ok(db_conn) => db_conn,
+            Err(error) => {
+                eprintln!("Error : unable to acquire a connection to the database : {error}");
+                continue;
+            }
+        };
+        match gallery.sync_dirs(&config, &mut db_conn, &dirs).await {
+            Ok(_) => println!(
+                "Synchronized successfully in {}ms",
+                now.elapsed().as_millis()
+            ),
+            Err(error) => eprintln!("Error : unable to synchronize photos : {error}"),
+        }
+    }
+}
+
+/// Add the directories of the photos folder (relative to PHOTOS_DIR) that need to be synchronized
+/// because of the given event to the list, if they are not already in it
+fn add_affected_dirs(
+    event: &Event,
+    watched_dirs: &WatchedDirs,
+    config: &Config,
+    dirs: &mut Vec<PathBuf>,
+) {
+    // Files opened or read, by this app or any other, don't need to be synchronized
+    if let EventKind::Access(kind) = event.kind {
+        if kind != AccessKind::Close(AccessMode::Write) {
+            return;
+        }
+    }
+
+    for path in &event.paths {
+        if let Some(dir) = affected_dir(path, watched_dirs, config) {
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+    }
+}
+
+/// Return the directory of the photos folder (relative to PHOTOS_DIR) that needs to be synchronized
+/// when the given file or directory is modified, if any
+fn affected_dir(path: &Path, watched_dirs: &WatchedDirs, config: &Config) -> Option<PathBuf> {
+    // Ignore the files created by the app in the cache folder, in case it is inside the photos folder
+    if path.starts_with(&watched_dirs.cache_dir) {
+        return None;
+    }
+    let rel_path = path.strip_prefix(&watched_dirs.photos_dir).ok()?;
+
+    // Hidden files and directories are not indexed, except for the local config files
+    let is_hidden = |name: &OsStr| name.to_string_lossy().starts_with('.');
+    let filename = rel_path.file_name()?;
+    let is_config_file = filename == SUBDIR_CONFIG_FILENAME;
+    let mut dir = rel_path.parent()?;
+    if dir.iter().any(is_hidden) || (is_hidden(filename) && !is_config_file) {
+        return None;
+    }
+
+    // Files and subdirectories are listed when the directory that contains them is loaded. A local
+    // config file also applies to its own directory, which it may hide from its parent directory.
+    if is_config_file {
+        dir = dir.parent().unwrap_or(dir);
+    }
+    if !config.INDEX_SUBDIRS {
+        return Some(PathBuf::new());
+    }
+    Some(dir.to_path_buf())
+}
@@ -0,0 +1,401 @@
+use crate::Error;
+use roxmltree::{Document, Node};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Maximum number of bytes read at the beginning of a file when looking for an embedded XMP packet.
+/// Packets are usually stored in the header of the file (in the APP1 segment of JPEG files, or in
+/// the first IFD of TIFF-based files), so there is no need to read large files entirely.
+const MAX_EMBEDDED_SCAN_SIZE: u64 = 4 * 1024 * 1024;
+
+// XML namespaces of the properties that are read
+const NS_XML: &str = "http://www.w3.org/XML/1998/namespace";
+const NS_RDF: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+const NS_DC: &str = "http://purl.org/dc/elements/1.1/";
+const NS_XMP: &str = "http://ns.adobe.com/xap/1.0/";
+const NS_PHOTOSHOP: &str = "http://ns.adobe.com/photoshop/1.0/";
+const NS_IPTC_CORE: &str = "http://iptc.org/std/Iptc4xmpCore/1.0/xmlns/";
+const NS_IPTC_EXT: &str = "http://iptc.org/std/Iptc4xmpExt/2008-02-29/";
+
+/// Descriptive metadata of a photo, usually curated by its author in a photo management software
+/// and stored either in an XMP packet or in an IPTC block
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DescriptiveMetadata {
+    pub title: String,
+    pub description: String,
+    pub location: String,
+    pub city: String,
+    pub country: String,
+    pub rating: Option<i32>,
+    pub keywords: Vec<String>,
+    /// Every simple property found in the packet, including the ones above, indexed by their
+    /// qualified name (such as "exif:ExposureProgram"). Arrays are joined by "; ".
+    pub properties: BTreeMap<String, String>,
+}
+
+impl DescriptiveMetadata {
+    /// Fill the fields of this struct that are still empty with the values found in `other`
+    pub fn merge(&mut self, other: DescriptiveMetadata) {
+        fn merge_str(value: &mut String, other: String) {
+            if value.is_empty() {
+                *value = other;
+            }
+        }
+        merge_str(&mut self.title, other.title);
+        merge_str(&mut self.description, other.description);
+        merge_str(&mut self.location, other.location);
+        merge_str(&mut self.city, other.city);
+        merge_str(&mut self.country, other.country);
+        if self.rating.is_none() {
+            self.rating = other.rating;
+        }
+        if self.keywords.is_empty() {
+            self.keywords = other.keywords;
+        }
+        for (name, value) in other.properties {
+            self.properties.entry(name).or_insert(value);
+        }
+    }
+
+    /// Human-readable description of the place where the photo was taken, such as
+    /// "Eiffel Tower, Paris, France"
+    pub fn place(&self) -> String {
+        let mut components: Vec<&str> = Vec::new();
+        for component in [&self.location, &self.city, &self.country] {
+            if !component.is_empty() && !components.contains(&component.as_str()) {
+                components.push(component);
+            }
+        }
+        components.join(", ")
+    }
+}
+
+/// Return the path of the XMP sidecar file of the given photo, if there is one, with its last
+/// modification time as a Unix timestamp. Both the `IMG_0001.xmp` and `IMG_0001.jpg.xmp` naming
+/// conventions are supported.
+pub async fn find_sidecar(path: &Path) -> Option<(PathBuf, i64)> {
+    let mut candidates = vec![path.with_extension("xmp"), path.with_extension("XMP")];
+    for ext in ["xmp", "XMP"] {
+        let mut filename = path.file_name()?.to_os_string();
+        filename.push(".");
+        filename.push(ext);
+        candidates.push(path.with_file_name(filename));
+    }
+    for candidate in candidates {
+        if let Ok(metadata) = tokio::fs::metadata(&candidate).await {
+            if metadata.is_file() {
+                let mtime = match metadata.modified().map(|m| m.duration_since(UNIX_EPOCH)) {
+                    Ok(Ok(duration)) => duration.as_secs() as i64,
+                    Ok(Err(error)) => -(error.duration().as_secs() as i64),
+                    Err(_) => 0,
+                };
+                return Some((candidate, mtime));
+            }
+        }
+    }
+    None
+}
+
+/// Read and parse the given XMP sidecar file
+pub async fn read_sidecar(path: &Path) -> Result<DescriptiveMetadata, Error> {
+    let xml = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| Error::FileError(e, path.to_path_buf()))?;
+    parse(&xml, path)
+}
+
+/// Look for an XMP packet embedded inside the given content of the file at `path` and parse it.
+/// Returns Ok(None) if the file doesn't contain any XMP packet.
+pub fn read_embedded(data: &[u8], path: &Path) -> Result<Option<DescriptiveMetadata>, Error> {
+    match find_packet(data) {
+        Some(xml) => parse(xml, path).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Read the first bytes of the given file, up to MAX_EMBEDDED_SCAN_SIZE
+pub fn read_header(path: &Path) -> Result<Vec<u8>, Error> {
+    let mut data = Vec::new();
+    File::open(path)
+        .and_then(|file| file.take(MAX_EMBEDDED_SCAN_SIZE).read_to_end(&mut data))
+        .map_err(|e| Error::FileError(e, path.to_path_buf()))?;
+    Ok(data)
+}
+
+/// Find the boundaries of an XMP packet in the given data. The packet is located by its XML content
+/// instead of the container's structure, which works for every file format.
+fn find_packet(data: &[u8]) -> Option<&str> {
+    for (start_tag, end_tag) in [
+        (&b"<x:xmpmeta"[..], &b"</x:xmpmeta>"[..]),
+        (&b"<rdf:RDF"[..], &b"</rdf:RDF>"[..]),
+    ] {
+        let Some(start) = find_bytes(data, start_tag) else {
+            continue;
+        };
+        if let Some(length) = find_bytes(&data[start..], end_tag) {
+            return std::str::from_utf8(&data[start..start + length + end_tag.len()]).ok();
+        }
+    }
+    None
+}
+
+/// Return the position of the first occurrence of `needle` in `haystack`
+pub fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Parse the given XMP packet
+pub fn parse(xml: &str, path: &Path) -> Result<DescriptiveMetadata, Error> {
+    let document = Document::parse(xml)
+        .map_err(|e| Error::MetadataParserError(e.to_string(), path.to_path_buf()))?;
+
+    // Properties can either be written as attributes of an element (usually rdf:Description), or
+    // as child elements. When a property is found several times, the first value is kept.
+    let mut metadata = DescriptiveMetadata::default();
+    for node in document.descendants().filter(|node| node.is_element()) {
+        for attribute in node.attributes() {
+            if let Some(namespace) = attribute.namespace() {
+                let value = attribute.value().trim().to_string();
+                save_property(&mut metadata, node, namespace, attribute.name(), &value);
+                set_property(&mut metadata, namespace, attribute.name(), vec![value]);
+            }
+        }
+        if let Some(namespace) = node.tag_name().namespace() {
+            let values = property_values(node);
+            // Only the default value of language alternatives is kept
+            let value = match node
+                .children()
+                .any(|child| child.has_tag_name((NS_RDF, "Alt")))
+            {
+                true => values.first().cloned().unwrap_or_default(),
+                false => values.join("; "),
+            };
+            save_property(
+                &mut metadata,
+                node,
+                namespace,
+                node.tag_name().name(),
+                &value,
+            );
+            set_property(&mut metadata, namespace, node.tag_name().name(), values);
+        }
+    }
+    Ok(metadata)
+}
+
+/// Save the given value of a property in the list of every property of `metadata`, unless it is
+/// empty, it is part of the RDF syntax, or a value has already been found for this property
+fn save_property(
+    metadata: &mut DescriptiveMetadata,
+    node: Node,
+    namespace: &str,
+    name: &str,
+    value: &str,
+) {
+    if value.is_empty() || namespace == NS_RDF || namespace == NS_XML {
+        return;
+    }
+    if let Some(prefix) = node.lookup_prefix(namespace) {
+        metadata
+            .properties
+            .entry(format!("{prefix}:{name}"))
+            .or_insert_with(|| value.to_string());
+    }
+}
+
+/// Return the values of the given property element, which is either a simple text value, or an array
+/// of values (rdf:Alt, rdf:Bag or rdf:Seq). For language alternatives, the default value comes first.
+fn property_values(node: Node) -> Vec<String> {
+    let array = node.children().find(|child| {
+        child.tag_name().namespace() == Some(NS_RDF)
+            && ["Alt", "Bag", "Seq"].contains(&child.tag_name().name())
+    });
+    match array {
+        Some(array) => {
+            let mut items = array
+                .children()
+                .filter(|item| item.has_tag_name((NS_RDF, "li")))
+                .collect::<Vec<Node>>();
+            items.sort_by_key(|item| item.attribute((NS_XML, "lang")) != Some("x-default"));
+            items
+                .iter()
+                .filter_map(|item| item.text())
+                .map(|text| text.trim().to_string())
+                .filter(|text| !text.is_empty())
+                .collect()
+        }
+        None => node
+            .text()
+            .map(|text| text.trim().to_string())
+            .filter(|text| !text.is_empty())
+            .into_iter()
+            .collect(),
+    }
+}
+
+/// Save the given values of a property into the corresponding field of `metadata`, if it is known
+/// and hasn't already been set
+fn set_property(
+    metadata: &mut DescriptiveMetadata,
+    namespace: &str,
+    name: &str,
+    values: Vec<String>,
+) {
+    let field = match (namespace, name) {
+        (NS_DC, "title") => &mut metadata.title,
+        (NS_DC, "description") => &mut metadata.description,
+        (NS_IPTC_CORE, "Location") | (NS_IPTC_EXT, "Sublocation") => &mut metadata.location,
+        (NS_PHOTOSHOP, "City") | (NS_IPTC_CORE, "City") | (NS_IPTC_EXT, "City") => {
+            &mut metadata.city
+        }
+        (NS_PHOTOSHOP, "Country") | (NS_IPTC_CORE, "Country") | (NS_IPTC_EXT, "CountryName") => {
+            &mut metadata.country
+        }
+        (NS_DC, "subject") => {
+            if metadata.keywords.is_empty() {
+                metadata.keywords = values;
+            }
+            return;
+        }
+        (NS_XMP, "Rating") => {
+            if metadata.rating.is_none() {
+                // Some software write the rating as a decimal value
+                metadata.rating = values
+                    .first()
+                    .and_then(|value| value.parse::<f32>().ok())
+                    .map(|rating| rating.round() as i32);
+            }
+            return;
+        }
+        _ => return,
+    };
+    if field.is_empty() {
+        if let Some(value) = values.into_iter().next() {
+            *field = value;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKET: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:photoshop="http://ns.adobe.com/photoshop/1.0/"
+    xmlns:exif="http://ns.adobe.com/exif/1.0/"
+    xmp:Rating="4"
+    photoshop:City=" Paris "
+    exif:ExposureProgram="2">
+   <dc:title>
+    <rdf:Alt>
+     <rdf:li xml:lang="fr-FR">Coucher de soleil</rdf:li>
+     <rdf:li xml:lang="x-default">Sunset</rdf:li>
+    </rdf:Alt>
+   </dc:title>
+   <dc:description>
+    <rdf:Alt>
+     <rdf:li xml:lang="x-default">Over the Seine</rdf:li>
+    </rdf:Alt>
+   </dc:description>
+   <dc:subject>
+    <rdf:Bag>
+     <rdf:li>sunset</rdf:li>
+     <rdf:li> travel </rdf:li>
+     <rdf:li></rdf:li>
+    </rdf:Bag>
+   </dc:subject>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+
+    fn parse_str(xml: &str) -> Result<DescriptiveMetadata, Error> {
+        parse(xml, Path::new("test.xmp"))
+    }
+
+    #[test]
+    fn parse_packet() {
+        let metadata = parse_str(PACKET).unwrap();
+        assert_eq!(metadata.title, "Sunset");
+        assert_eq!(metadata.description, "Over the Seine");
+        assert_eq!(metadata.city, "Paris");
+        assert_eq!(metadata.rating, Some(4));
+        assert_eq!(metadata.keywords, ["sunset", "travel"]);
+        assert_eq!(metadata.properties["dc:title"], "Sunset");
+        assert_eq!(metadata.properties["dc:subject"], "sunset; travel");
+        assert_eq!(metadata.properties["exif:ExposureProgram"], "2");
+        assert!(!metadata.properties.contains_key("rdf:about"));
+    }
+
+    #[test]
+    fn parse_ratings() {
+        for (value, rating) in [
+            ("5", Some(5)),
+            ("-1", Some(-1)),
+            ("3.6", Some(4)),
+            ("", None),
+            ("high", None),
+        ] {
+            let xml = format!(
+                r#"<rdf:RDF xmlns:rdf="{NS_RDF}"><rdf:Description xmlns:xmp="{NS_XMP}" xmp:Rating="{value}"/></rdf:RDF>"#
+            );
+            assert_eq!(parse_str(&xml).unwrap().rating, rating, "{value}");
+        }
+
+        // The rating can also be written as an element, and only the first one is kept
+        let xml = format!(
+            r#"<rdf:RDF xmlns:rdf="{NS_RDF}"><rdf:Description xmlns:xmp="{NS_XMP}" xmp:Rating="2"><xmp:Rating>5</xmp:Rating></rdf:Description></rdf:RDF>"#
+        );
+        assert_eq!(parse_str(&xml).unwrap().rating, Some(2));
+    }
+
+    #[test]
+    fn reject_malformed_packets() {
+        for xml in [
+            "",
+            "not XML",
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">",
+            "<rdf:RDF><rdf:Description/></rdf:RDF>",
+        ] {
+            assert!(
+                matches!(parse_str(xml), Err(Error::MetadataParserError(..))),
+                "{xml}"
+            );
+        }
+    }
+
+    #[test]
+    fn find_embedded_packets() {
+        let mut data = b"\xff\xd8\xff\xe1\x00\x10http://ns.adobe.com/xap/1.0/\0".to_vec();
+        data.extend(PACKET.as_bytes());
+        data.extend(b"\xff\xd9");
+        let metadata = read_embedded(&data, Path::new("test.jpg"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(metadata.title, "Sunset");
+
+        // Unterminated or missing packet
+        let data = &data[..data.len() - 40];
+        assert!(read_embedded(data, Path::new("test.jpg"))
+            .unwrap()
+            .is_none());
+        assert!(read_embedded(b"\xff\xd8\xff\xd9", Path::new("test.jpg"))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn find_bytes_in_data() {
+        assert_eq!(find_bytes(b"abcabc", b"ca"), Some(2));
+        assert_eq!(find_bytes(b"abc", b"abcd"), None);
+        assert_eq!(find_bytes(b"", b"a"), None);
+    }
+}
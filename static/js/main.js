@@ -155,7 +155,7 @@ function loadNav(preselectedSubdir=undefined, password=undefined) {
     loadNavRequest = new XMLHttpRequest();
     loadNavRequest.onreadystatechange = function() {
         if (this.readyState == 4) {
-            if (this.status == 401) {
+            if (this.status == 401 || this.status == 429) {
                 openPasswordPrompt(loadNavRequest.responseText, password!=undefined);
                 loadNavRequest.abort();
                 loadNavRequest = undefined;
@@ -164,6 +164,7 @@ function loadNav(preselectedSubdir=undefined, password=undefined) {
                 closePasswordPrompt();
                 $('.nav-loading').addClass('hidden');
                 $('.navigation-panel-content').replaceWith(loadNavRequest.responseText);
+                $('.navigation-panel-search input').val(new URLSearchParams(window.location.search).get('search') || '');
                 let selectedLink = $('.navigation-panel-subdir.selected');
                 if (selectedLink.length == 0 && preselectedSubdir) {
                     $('.navigation-panel-subdir a[href="' + preselectedSubdir + '"]').parent().addClass('selected');
@@ -278,7 +279,7 @@ function loadGrid(before=false, preselectedUID=undefined, around=false, password
                 }
                 $('.grid-actions-topright').removeClass('hidden');
                 updateNPhotos();
-            } else if (this.status != 401) {
+            } else if (this.status != 401 && this.status != 429) {
                 $('.grid-loading').addClass('hidden');
                 $('.grid-loading-error').removeClass('hidden');
             }
@@ -677,6 +678,8 @@ function setLoupePhoto(gridItem) {
         $('.loupe-photo-index').children('span').text(($(gridItem).data('index') + 1) + " / " + $('.grid-item').first().data('count'));
         loupeElement = $(gridItem).children('.photo');
         let photo = $('.loupe .photo-large');
+        let video = $('.loupe .video-large');
+        let isVideo = $(loupeElement).data('media-type') == 'video';
         let loadNext = function() {
             opacityTransitionInProgress = false;
             if (opacityTransitionTimeout) {
@@ -684,17 +687,20 @@ function setLoupePhoto(gridItem) {
                 opacityTransitionTimeout = undefined;
             }
             photo.attr('src', '');
+            stopLoupeVideo();
             loupeLoadingDisplayTimeout = setTimeout(() => {
                 $('.loupe-loading').removeClass('hidden');
                 loupeLoadingDisplayTimeout = undefined;
             }, 200);
-            photo.one('load', function() {
+            let onLoad = function() {
                 if (loupeLoadingDisplayTimeout) {
                     clearTimeout(loupeLoadingDisplayTimeout);
                     loupeLoadingDisplayTimeout = undefined;
                 }
                 $('.loupe-loading').addClass('hidden');
-                photo.removeClass('transparent');
+                if (!isVideo) {
+                    photo.removeClass('transparent');
+                }
                 if (showMetadata) {
                     $('.loupe-metadata').removeClass('invisible');
                 }
@@ -711,8 +717,19 @@ function setLoupePhoto(gridItem) {
                     let prevPhoto = new Image();
                     prevPhoto.src = prevGridItem.children().first().data('src-large');
                 }
-            });
-            photo.attr('src', $(loupeElement).data('src-large'));
+            };
+            if (isVideo) {
+                // Videos are played in a dedicated element, using their large resized version as a poster
+                $('.loupe-photo').addClass('video');
+                video.one('loadeddata error', onLoad);
+                video.attr('poster', $(loupeElement).data('src-large'));
+                video.attr('src', $(loupeElement).data('src-video'));
+                video.removeClass('hidden');
+            } else {
+                $('.loupe-photo').removeClass('video');
+                photo.one('load', onLoad);
+                photo.attr('src', $(loupeElement).data('src-large'));
+            }
             $('.loupe').css('background-color', $(loupeElement).data('color') + 'FC');
             if ($(loupeElement).parent().prev().length > 0) {
                 $('.loupe-prev').removeClass('hidden');
@@ -730,8 +747,19 @@ function setLoupePhoto(gridItem) {
                 event.stopPropagation();
                 downloadCurrentPhoto();
             });
+            $('.loupe-action-download-raw').off('click');
+            if ($(loupeElement).data('src-download-raw')) {
+                $('.loupe-action-download-raw').removeClass('hidden');
+                $('.loupe-action-download-raw').on('click', function(event) {
+                    event.preventDefault();
+                    event.stopPropagation();
+                    downloadCurrentPhotoRaw();
+                });
+            } else {
+                $('.loupe-action-download-raw').addClass('hidden');
+            }
             if ($('.loupe-metadata').length > 0) {
-                const properties = ['title', 'date', 'place', 'camera', 'lens', 'focal-length', 'aperture', 'exposure-time', 'sensitivity'];
+                const properties = ['title', 'description', 'date', 'place', 'rating', 'keywords', 'camera', 'lens', 'focal-length', 'aperture', 'exposure-time', 'sensitivity'];
                 let showInfoButton = false;
                 let showGear = false;
                 let showSettings = false;
@@ -742,6 +770,9 @@ function setLoupePhoto(gridItem) {
                     if (typeof(value) == 'string') {
                         value = value.trim();
                     }
+                    if (property == 'date' && typeof($(loupeElement).data('timestamp')) == 'number') {
+                        value = formatDateTaken($(loupeElement).data('timestamp'), $(loupeElement).data('timezone-offset'));
+                    }
                     if (value) {
                         showInfoButton = true;
                     }
@@ -775,6 +806,14 @@ function setLoupePhoto(gridItem) {
                             infoElement.text("ISO " + value);
                             showSettings = true;
                         }
+                    } else if (property == 'rating') {
+                        if (value) {
+                            let rating = Math.min(parseInt(value), 5);
+                            infoElement.text('★'.repeat(rating) + '☆'.repeat(5 - rating));
+                            infoElement.parent().removeClass('hidden');
+                        } else {
+                            infoElement.parent().addClass('hidden');
+                        }
                     } else {
                         if (value) {
                             infoElement.text(value);
@@ -784,6 +823,18 @@ function setLoupePhoto(gridItem) {
                         }
                     }
                 });
+                let latitude = parseFloat($(loupeElement).data('latitude'));
+                let longitude = parseFloat($(loupeElement).data('longitude'));
+                let coordinatesElement = $('.loupe-metadata-coordinates');
+                if (!isNaN(latitude) && !isNaN(longitude)) {
+                    coordinatesElement.text(formatCoordinates(latitude, longitude));
+                    coordinatesElement.attr('href', '?map#' + $(loupeElement).parent().data('uid'));
+                    coordinatesElement.parent().removeClass('hidden');
+                    showInfoButton = true;
+                } else {
+                    coordinatesElement.parent().addClass('hidden');
+                }
+                loadExtraMetadata(loupeElement);
                 if (showInfoButton) {
                     $('.loupe-action-info').removeClass('hidden');
                 } else {
@@ -824,10 +875,23 @@ function setLoupePhoto(gridItem) {
     });
 }
 
+function stopLoupeVideo() {
+    let video = $('.loupe .video-large');
+    video.off('loadeddata error');
+    if (video.attr('src')) {
+        video.trigger('pause');
+        video.removeAttr('src');
+        video.removeAttr('poster');
+        video.get(0).load();
+    }
+    video.addClass('hidden');
+}
+
 function closeLoupe() {
     let gridItem = $(loupeElement).parent();
     $(gridItem).addClass('selected');
     stopSlideshow();
+    stopLoupeVideo();
     window.location.hash = '';
     $('.container').removeClass('show-loupe');
     if ($('.grid-item').first().data('index') > 0) {
@@ -975,6 +1039,56 @@ function downloadCurrentPhoto() {
     window.open($(loupeElement).data('src-download'));
 }
 
+function downloadCurrentPhotoRaw() {
+    window.open($(loupeElement).data('src-download-raw'));
+}
+
+// Fetch the additional fields listed in LOUPE_METADATA_FIELDS, which are not part of the grid,
+// and show the ones available for this photo
+function loadExtraMetadata(photoElement) {
+    $('.loupe-metadata-extra').remove();
+    if (METADATA_FIELDS.length == 0 || !$(photoElement).data('src-metadata')) {
+        return;
+    }
+    let request = new XMLHttpRequest();
+    request.onreadystatechange = function() {
+        if (this.readyState == 4 && this.status == 200 && loupeElement == photoElement) {
+            let metadata = JSON.parse(request.responseText);
+            METADATA_FIELDS.forEach(function(field) {
+                if (metadata[field]) {
+                    // "exif:ExposureProgram" is shown as "Exposure Program"
+                    let label = field.split(':').pop().replace(/([a-z])([A-Z])/g, '$1 $2');
+                    let element = $('<div class="loupe-metadata-element loupe-metadata-extra"><i class="fa-solid fa-circle-info fa-fw"></i> <span></span></div>');
+                    element.children('span').text(label + ' : ' + metadata[field]);
+                    element.appendTo($('.loupe-metadata-bottom'));
+                    $('.loupe-action-info').removeClass('hidden');
+                }
+            });
+        }
+    };
+    request.open('GET', $(photoElement).data('src-metadata'), true);
+    request.send();
+}
+
+// Format the date a photo was taken in the local time of the place where it was taken, according to
+// the locale of the browser. When the time zone is unknown, the local time is stored as if it was UTC.
+function formatDateTaken(timestamp, offset) {
+    let offsetKnown = typeof(offset) == 'number';
+    let date = new Date((timestamp + (offsetKnown ? offset * 60 : 0)) * 1000);
+    let text = date.toLocaleString(undefined, {'timeZone': 'UTC', 'dateStyle': 'long', 'timeStyle': 'medium'});
+    if (offsetKnown) {
+        let hours = String(Math.floor(Math.abs(offset) / 60)).padStart(2, '0');
+        let minutes = String(Math.abs(offset) % 60).padStart(2, '0');
+        text += ' (UTC' + (offset < 0 ? '-' : '+') + hours + ':' + minutes + ')';
+    }
+    return text;
+}
+
+function formatCoordinates(latitude, longitude) {
+    return Math.abs(latitude).toFixed(5) + '° ' + (latitude >= 0 ? 'N' : 'S') + ', '
+        + Math.abs(longitude).toFixed(5) + '° ' + (longitude >= 0 ? 'E' : 'W');
+}
+
 function isTouchscreen() {
     return "maxTouchPoints" in navigator && navigator.maxTouchPoints > 0;
 }
@@ -1018,6 +1132,14 @@ $(function() {
         event.preventDefault();
         event.stopPropagation();
     });
+    $('.loupe .video-large').on('click', function(event) {
+        // Let the video controls handle the click instead of closing the loupe
+        event.stopPropagation();
+    });
+    $('.loupe-metadata-coordinates').on('click', function(event) {
+        // Follow the link to the map instead of closing the loupe
+        event.stopPropagation();
+    });
     $('.loupe').on('mousemove', function(event) {
         showLoupeUI();
     });
@@ -0,0 +1,268 @@
+const TILE_SIZE = 256; // px
+const MIN_ZOOM = 1;
+const FOCUS_ZOOM = 15;
+const MARKER_SIZE = 64; // px
+const CLUSTER_DISTANCE = 48; // px
+const DRAG_THRESHOLD = 5; // px
+
+let mapElement = undefined;
+let markers = [];
+let clusters = [];
+let tilesURL = '';
+let maxZoom = 19;
+let zoom = MIN_ZOOM;
+let centerX = 0; // Coordinates of the center of the view, in pixels at the current zoom level
+let centerY = 0;
+let loadedTiles = {};
+let pointers = {};
+let pinchDistance = undefined;
+let dragDistance = 0;
+let wheelTimeout = undefined;
+
+// Convert GPS coordinates to pixels at the given zoom level, using the Web Mercator projection
+function project(latitude, longitude, z) {
+    let scale = TILE_SIZE * Math.pow(2, z);
+    let sinLatitude = Math.min(Math.max(Math.sin(latitude * Math.PI / 180), -0.9999), 0.9999);
+    return [
+        (longitude + 180) / 360 * scale,
+        (0.5 - Math.log((1 + sinLatitude) / (1 - sinLatitude)) / (4 * Math.PI)) * scale,
+    ];
+}
+
+function mapWidth() {
+    return mapElement.clientWidth;
+}
+
+function mapHeight() {
+    return mapElement.clientHeight;
+}
+
+function render() {
+    renderTiles();
+    renderMarkers();
+}
+
+function renderTiles() {
+    let left = centerX - mapWidth() / 2;
+    let top = centerY - mapHeight() / 2;
+    let nTiles = Math.pow(2, zoom);
+    let visibleTiles = {};
+    for (let x = Math.floor(left / TILE_SIZE); x <= Math.floor((left + mapWidth() - 1) / TILE_SIZE); x++) {
+        for (let y = Math.max(Math.floor(top / TILE_SIZE), 0); y <= Math.min(Math.floor((top + mapHeight() - 1) / TILE_SIZE), nTiles - 1); y++) {
+            let key = zoom + '/' + x + '/' + y;
+            visibleTiles[key] = true;
+            let tile = loadedTiles[key];
+            if (!tile) {
+                // The world repeats itself horizontally
+                let wrappedX = ((x % nTiles) + nTiles) % nTiles;
+                tile = $('<img class="map-tile" draggable="false" alt="">');
+                tile.attr('src', tilesURL.replace('{z}', zoom).replace('{x}', wrappedX).replace('{y}', y));
+                tile.appendTo($('.map-tiles'));
+                loadedTiles[key] = tile;
+            }
+            tile.css({
+                'left': Math.round(x * TILE_SIZE - left) + 'px',
+                'top': Math.round(y * TILE_SIZE - top) + 'px',
+            });
+        }
+    }
+    for (let key in loadedTiles) {
+        if (!visibleTiles[key]) {
+            loadedTiles[key].remove();
+            delete loadedTiles[key];
+        }
+    }
+}
+
+// Group the markers that would overlap at the current zoom level, and create an element for each group
+function updateClusters() {
+    clusters = [];
+    markers.forEach(function(marker) {
+        let [x, y] = project(marker.latitude, marker.longitude, zoom);
+        let cluster = clusters.find(c => Math.abs(c.x - x) < CLUSTER_DISTANCE && Math.abs(c.y - y) < CLUSTER_DISTANCE);
+        if (cluster) {
+            cluster.markers.push(marker);
+        } else {
+            clusters.push({'x': x, 'y': y, 'markers': [marker]});
+        }
+    });
+
+    $('.map-markers').empty();
+    clusters.forEach(function(cluster) {
+        let marker = cluster.markers[0];
+        cluster.element = $('<a class="map-marker"></a>');
+        cluster.element.attr('href', marker.url_photo);
+        cluster.element.attr('title', cluster.markers.length > 1 ? cluster.markers.length + ' photos' : marker.title);
+        cluster.element.css('background-image', 'url("' + marker.url_thumbnail + '")');
+        if (cluster.markers.length > 1) {
+            $('<div class="map-marker-count"></div>').text(cluster.markers.length).appendTo(cluster.element);
+        }
+        cluster.element.on('click', function(event) {
+            if (dragDistance > DRAG_THRESHOLD) {
+                // The map has been dragged, this is not a click
+                event.preventDefault();
+            } else if (cluster.markers.length > 1 && zoom < maxZoom) {
+                // Zoom in to split the group
+                event.preventDefault();
+                setZoom(Math.min(zoom + 2, maxZoom), cluster.x - centerX + mapWidth() / 2, cluster.y - centerY + mapHeight() / 2);
+            }
+        });
+        cluster.element.appendTo($('.map-markers'));
+    });
+}
+
+function renderMarkers() {
+    let left = centerX - mapWidth() / 2;
+    let top = centerY - mapHeight() / 2;
+    clusters.forEach(function(cluster) {
+        let x = cluster.x - left;
+        let y = cluster.y - top;
+        if (x < -MARKER_SIZE || y < -MARKER_SIZE || x > mapWidth() + MARKER_SIZE || y > mapHeight() + MARKER_SIZE) {
+            cluster.element.addClass('hidden');
+        } else {
+            cluster.element.removeClass('hidden');
+            cluster.element.css({
+                'left': Math.round(x) + 'px',
+                'top': Math.round(y) + 'px',
+            });
+        }
+    });
+}
+
+// Change the zoom level, keeping the point at the given position in the view (the center by default) in place
+function setZoom(newZoom, anchorX=undefined, anchorY=undefined) {
+    newZoom = Math.min(Math.max(newZoom, MIN_ZOOM), maxZoom);
+    if (newZoom == zoom) {
+        return;
+    }
+    let offsetX = (anchorX === undefined ? mapWidth() / 2 : anchorX) - mapWidth() / 2;
+    let offsetY = (anchorY === undefined ? mapHeight() / 2 : anchorY) - mapHeight() / 2;
+    let factor = Math.pow(2, newZoom - zoom);
+    centerX = (centerX + offsetX) * factor - offsetX;
+    centerY = (centerY + offsetY) * factor - offsetY;
+    zoom = newZoom;
+    updateClusters();
+    render();
+}
+
+// Choose the highest zoom level that shows every marker, and center the view on them
+function fitMarkers() {
+    let points = markers.map(marker => project(marker.latitude, marker.longitude, 0));
+    let minX = Math.min(...points.map(p => p[0]));
+    let maxX = Math.max(...points.map(p => p[0]));
+    let minY = Math.min(...points.map(p => p[1]));
+    let maxY = Math.max(...points.map(p => p[1]));
+    let availableWidth = Math.max(mapWidth() - 2 * MARKER_SIZE, 1);
+    let availableHeight = Math.max(mapHeight() - 2 * MARKER_SIZE, 1);
+    zoom = Math.min(FOCUS_ZOOM, maxZoom);
+    while (zoom > MIN_ZOOM && ((maxX - minX) * Math.pow(2, zoom) > availableWidth || (maxY - minY) * Math.pow(2, zoom) > availableHeight)) {
+        zoom--;
+    }
+    centerX = (minX + maxX) / 2 * Math.pow(2, zoom);
+    centerY = (minY + maxY) / 2 * Math.pow(2, zoom);
+}
+
+// Center the view on the marker with the given UID, if there is one
+function focusMarker(uid) {
+    let marker = markers.find(m => m.uid == uid);
+    if (marker) {
+        zoom = Math.max(zoom, Math.min(FOCUS_ZOOM, maxZoom));
+        [centerX, centerY] = project(marker.latitude, marker.longitude, zoom);
+    }
+}
+
+function onPointerMove(event) {
+    let pointer = pointers[event.pointerId];
+    if (!pointer) {
+        return;
+    }
+    let ids = Object.keys(pointers);
+    if (ids.length == 1) {
+        // Drag the map
+        centerX -= event.clientX - pointer.x;
+        centerY -= event.clientY - pointer.y;
+        dragDistance += Math.abs(event.clientX - pointer.x) + Math.abs(event.clientY - pointer.y);
+        pointer.x = event.clientX;
+        pointer.y = event.clientY;
+        render();
+    } else if (ids.length == 2) {
+        // Pinch to zoom
+        pointer.x = event.clientX;
+        pointer.y = event.clientY;
+        let [a, b] = ids.map(id => pointers[id]);
+        let distance = Math.hypot(a.x - b.x, a.y - b.y);
+        dragDistance += DRAG_THRESHOLD;
+        if (pinchDistance === undefined) {
+            pinchDistance = distance;
+        } else if (distance > pinchDistance * 1.5 || distance < pinchDistance / 1.5) {
+            let rect = mapElement.getBoundingClientRect();
+            setZoom(zoom + (distance > pinchDistance ? 1 : -1), (a.x + b.x) / 2 - rect.left, (a.y + b.y) / 2 - rect.top);
+            pinchDistance = distance;
+        }
+    }
+}
+
+function onPointerUp(event) {
+    delete pointers[event.pointerId];
+    pinchDistance = undefined;
+}
+
+
+$(function() {
+    mapElement = $('.map').get(0);
+    if (!mapElement) {
+        return;
+    }
+    markers = JSON.parse(mapElement.dataset.markers);
+    tilesURL = mapElement.dataset.tilesUrl;
+    maxZoom = parseInt(mapElement.dataset.maxZoom);
+
+    fitMarkers();
+    if (window.location.hash) {
+        focusMarker(window.location.hash.substr(1));
+    }
+    updateClusters();
+    render();
+
+    $(window).on('resize', function() {
+        render();
+    });
+    $(mapElement).on('pointerdown', function(event) {
+        if (Object.keys(pointers).length == 0) {
+            dragDistance = 0;
+        }
+        pointers[event.pointerId] = {'x': event.clientX, 'y': event.clientY};
+    });
+    $(window).on('pointermove', onPointerMove);
+    $(window).on('pointerup', onPointerUp);
+    $(window).on('pointercancel', onPointerUp);
+    $(mapElement).on('dragstart', function(event) {
+        event.preventDefault();
+    });
+    $(mapElement).on('dblclick', function(event) {
+        let rect = mapElement.getBoundingClientRect();
+        setZoom(zoom + 1, event.clientX - rect.left, event.clientY - rect.top);
+        event.preventDefault();
+    });
+    $(mapElement).on('wheel', function(event) {
+        event.preventDefault();
+        if (wheelTimeout || event.deltaY == 0) {
+            return;
+        }
+        let rect = mapElement.getBoundingClientRect();
+        setZoom(zoom + (event.deltaY < 0 ? 1 : -1), event.clientX - rect.left, event.clientY - rect.top);
+        wheelTimeout = setTimeout(function() {
+            wheelTimeout = undefined;
+        }, 200);
+    });
+    $('.map-action-zoom-in').on('click', function(event) {
+        setZoom(zoom + 1);
+        event.preventDefault();
+        event.stopPropagation();
+    });
+    $('.map-action-zoom-out').on('click', function(event) {
+        setZoom(zoom - 1);
+        event.preventDefault();
+        event.stopPropagation();
+    });
+});